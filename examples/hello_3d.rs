@@ -15,7 +15,7 @@ use rivik_assets::{
 use rivik_render::{
     draw::{pixel_mesh, PixelMesh},
     lights::sun::SunLight,
-    load::{GpuMesh, GpuTexture},
+    load::{GpuIndexedMesh, GpuTexture},
     tracing::UiSubscriber,
     Transform,
 };
@@ -31,7 +31,7 @@ impl rivik::App for App {
         // load a model
         let mesh = load(
             "file:../render/assets/fighter_smooth.obj",
-            GpuMesh(ObjMesh, pixel_mesh::vertex_buffer),
+            GpuIndexedMesh(ObjMesh, pixel_mesh::compact_vertex_buffer),
         )
         .unwrap();
         let tex = load(
@@ -40,7 +40,7 @@ impl rivik::App for App {
         )
         .unwrap();
 
-        let mesh = PixelMesh::new(mesh, Transform::default(), tex);
+        let mesh = PixelMesh::compact(mesh, Transform::default(), tex);
         scene.insert_light(SunLight::new(
             Vec3::new(1.0, 1.0, 1.0),
             Vec3::new(-1., 1., 0.),
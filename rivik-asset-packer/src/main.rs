@@ -28,16 +28,27 @@ struct Args {
 
     #[arg(short, long)]
     manifest: Option<String>,
+
+    // number of levels of detail baked for .obj files
+    #[arg(short, long)]
+    lods: Option<usize>,
 }
 
 fn main() {
     let args = Args::parse();
 
     let manifest = match args.manifest {
-        None => args.files.into_iter().map(|file| Entry::new(file)).fold(
-            ManifestBuilder::new().with_hash(args.hash),
-            |manifest, file| manifest.push(file),
-        ),
+        None => args
+            .files
+            .into_iter()
+            .map(|file| match args.lods {
+                Some(levels) if file.ends_with(".obj") => Entry::new(file).lods(levels),
+                _ => Entry::new(file),
+            })
+            .fold(
+                ManifestBuilder::new().with_hash(args.hash),
+                |manifest, file| manifest.push(file),
+            ),
         Some(path) => toml::from_str(&fs::read_to_string(path).unwrap()).unwrap(),
     };
 
@@ -9,10 +9,14 @@
 use std::{
     collections::HashMap,
     fs::File,
-    io::{self, Read},
+    io::{self, Cursor, Read},
     path::Path,
 };
 
+use assets::{
+    formats::mesh::{write_lods, Lods, ObjMesh},
+    Format,
+};
 use brotli::enc::BrotliEncoderParams;
 use serde::{Deserialize, Serialize};
 
@@ -29,6 +33,7 @@ pub struct Entry {
     path: String,
     name: Option<String>,
     compress: Option<CompressionAlg>,
+    lods: Option<usize>,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -39,18 +44,20 @@ enum EntryRepr {
         path: String,
         compress: Option<CompressionAlg>,
         name: Option<String>,
+        lods: Option<usize>,
     },
 }
 
 impl From<Entry> for EntryRepr {
     fn from(value: Entry) -> Self {
-        if value.compress.is_none() && value.name.is_none() {
+        if value.compress.is_none() && value.name.is_none() && value.lods.is_none() {
             EntryRepr::Path(value.path)
         } else {
             EntryRepr::Entry {
                 path: value.path,
                 compress: value.compress,
                 name: value.name,
+                lods: value.lods,
             }
         }
     }
@@ -64,10 +71,12 @@ impl From<EntryRepr> for Entry {
                 path,
                 compress,
                 name,
+                lods,
             } => Entry {
                 path,
                 name,
                 compress,
+                lods,
             },
         }
     }
@@ -80,6 +89,7 @@ impl Entry {
             path: path.into(),
             name: None,
             compress: None,
+            lods: None,
         }
     }
 
@@ -91,6 +101,16 @@ impl Entry {
         }
     }
 
+    /// Bake up to `levels` levels of detail of an obj mesh into this entry
+    ///
+    /// The entry can then be loaded with the `BakedLods` mesh format
+    pub fn lods(self, levels: usize) -> Self {
+        Self {
+            lods: Some(levels),
+            ..self
+        }
+    }
+
     /// Rename the ID used to lookup this entry in the LUMP file
     pub fn rename(self, name: impl Into<String>) -> Self {
         Self {
@@ -114,9 +134,12 @@ impl Entry {
 
     /// Get a reader to the contents of this entry
     ///
-    /// Note that this will apply any compression that has been setup for this entry
+    /// Note that this will apply any LOD baking and compression that has been setup for this entry
     pub fn reader(&self) -> io::Result<Box<dyn Read>> {
-        let file = File::open(&self.path)?;
+        let file: Box<dyn Read> = match self.lods {
+            Some(levels) => Box::new(Cursor::new(self.bake_lods(levels)?)),
+            None => Box::new(File::open(&self.path)?),
+        };
         Ok(match &self.compress {
             Some(alg) => match alg {
                 CompressionAlg::Brotli => Box::new(brotli::CompressorReader::with_params(
@@ -132,9 +155,23 @@ impl Entry {
                     flate2::Compression::best(),
                 )),
             },
-            None => Box::new(file),
+            None => file,
         })
     }
+
+    fn bake_lods(&self, levels: usize) -> io::Result<Vec<u8>> {
+        let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidData, e);
+        let path = assets::Path::try_from(format!("file:{}", self.path))
+            .map_err(|e| invalid(e.to_string()))?;
+        let lods = Lods(ObjMesh, levels)
+            .parse(&path)
+            .map_err(|e| invalid(e.to_string()))?;
+        println!("Baked {} levels of detail for {}", lods.len(), self.path);
+
+        let mut bytes = vec![];
+        write_lods(&mut bytes, &lods)?;
+        Ok(bytes)
+    }
 }
 
 /// Describes the contents of a LUMP file
@@ -0,0 +1,213 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public License,
+ * v. 2.0. If a copy of the MPL was not distributed with this file, You can
+ * obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+//! Color lookup tables used for color grading
+
+use std::{
+    io,
+    num::{ParseFloatError, ParseIntError},
+};
+
+use image::GenericImageView;
+use snafu::{ensure, Backtrace, OptionExt, ResultExt, Snafu};
+
+use crate::{formats::Format, Path, ReaderCreationError};
+
+use super::{ImageFormat, ImageParseError, Img};
+
+/// A 3D color lookup table
+///
+/// Entries are stored with red changing fastest, then green, then blue.
+#[derive(Debug, Clone)]
+pub struct Lut {
+    /// Number of entries along each axis
+    pub size: u32,
+    /// `size * size * size` output colors
+    pub data: Vec<[f32; 3]>,
+}
+
+impl Lut {
+    /// Fetch the output color for a set of indices into the table
+    pub fn get(&self, r: u32, g: u32, b: u32) -> [f32; 3] {
+        let size = self.size as usize;
+        self.data[r as usize + size * (g as usize + size * b as usize)]
+    }
+}
+
+#[derive(Snafu, Debug)]
+pub enum LutError {
+    #[snafu(display("Failed loading LUT"))]
+    CreationError {
+        #[snafu(backtrace)]
+        source: ReaderCreationError,
+    },
+    #[snafu(display("Failed to read LUT"))]
+    ReadError {
+        source: io::Error,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("Failed to load LUT image"))]
+    LutImageError {
+        #[snafu(backtrace)]
+        source: ImageParseError,
+    },
+    #[snafu(display("Invalid LUT size '{value}' on line {line}"))]
+    InvalidSize {
+        line: usize,
+        value: String,
+        source: ParseIntError,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("Invalid value '{value}' on line {line}"))]
+    InvalidValue {
+        line: usize,
+        value: String,
+        source: ParseFloatError,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("Expected 3 values on line {line}"))]
+    MissingValue { line: usize, backtrace: Backtrace },
+    #[snafu(display("Only 3D LUTs are supported"))]
+    Unsupported { backtrace: Backtrace },
+    #[snafu(display("LUT is missing its size"))]
+    MissingSize { backtrace: Backtrace },
+    #[snafu(display("LUT size on line {line} must be at least 1"))]
+    ZeroSize { line: usize, backtrace: Backtrace },
+    #[snafu(display("LUT of size {size} has too many entries"))]
+    TooLarge { size: u32, backtrace: Backtrace },
+    #[snafu(display("Expected {expected} LUT entries but found {found}"))]
+    WrongLength {
+        expected: usize,
+        found: usize,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("LUT strip of {width}x{height} is not {height} square slices"))]
+    InvalidStrip {
+        width: u32,
+        height: u32,
+        backtrace: Backtrace,
+    },
+}
+
+/// Number of entries in a table of `size`, `None` if it doesn't fit in memory
+fn entries(size: u32) -> Option<usize> {
+    let size = usize::try_from(size).ok()?;
+    size.checked_mul(size)?.checked_mul(size)
+}
+
+/// File format definition for Adobe/Resolve `.cube` LUTs
+///
+/// Only 3D tables over the default `0..1` domain are supported.
+#[derive(Clone, Copy)]
+pub struct CubeLut;
+
+impl Format for CubeLut {
+    type Output = Lut;
+    type Error = LutError;
+
+    fn parse(&self, path: &Path) -> Result<Lut, LutError> {
+        let mut text = String::new();
+        path.reader()
+            .context(CreationSnafu)?
+            .read_to_string(&mut text)
+            .context(ReadSnafu)?;
+
+        let mut size = None;
+        let mut data = Vec::new();
+        for (i, line) in text.lines().enumerate() {
+            let line_num = i + 1;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut words = line.split_whitespace();
+            let keyword = words.next().unwrap();
+            match keyword {
+                "LUT_3D_SIZE" => {
+                    let value = words.next().unwrap_or_default();
+                    let value = value.parse::<u32>().context(InvalidSizeSnafu {
+                        line: line_num,
+                        value,
+                    })?;
+                    ensure!(value > 0, ZeroSizeSnafu { line: line_num });
+                    size = Some(value);
+                }
+                "LUT_1D_SIZE" => return UnsupportedSnafu.fail(),
+                "DOMAIN_MIN" | "DOMAIN_MAX" => {
+                    let default = if keyword == "DOMAIN_MIN" { 0.0 } else { 1.0 };
+                    for value in words {
+                        let v = value.parse::<f32>().context(InvalidValueSnafu {
+                            line: line_num,
+                            value,
+                        })?;
+                        ensure!(v == default, UnsupportedSnafu);
+                    }
+                }
+                // titles and other metadata don't change the table
+                k if k.starts_with(|c: char| c.is_ascii_alphabetic()) => {}
+                _ => {
+                    let mut entry = [0.0; 3];
+                    let mut values = line.split_whitespace();
+                    for c in &mut entry {
+                        let value = values
+                            .next()
+                            .context(MissingValueSnafu { line: line_num })?;
+                        *c = value.parse().context(InvalidValueSnafu {
+                            line: line_num,
+                            value,
+                        })?;
+                    }
+                    data.push(entry);
+                }
+            }
+        }
+
+        let size = size.context(MissingSizeSnafu)?;
+        let expected = entries(size).context(TooLargeSnafu { size })?;
+        ensure!(
+            data.len() == expected,
+            WrongLengthSnafu {
+                expected,
+                found: data.len()
+            }
+        );
+        Ok(Lut { size, data })
+    }
+}
+
+/// File format definition for LUTs stored as a horizontal strip of square slices
+///
+/// An `N` sized table is an `N * N` by `N` image. Each slice is one blue value, with red
+/// increasing to the right and green increasing downwards.
+#[derive(Clone, Copy)]
+pub struct StripLut(pub ImageFormat);
+
+impl Format for StripLut {
+    type Output = Lut;
+    type Error = LutError;
+
+    fn parse(&self, path: &Path) -> Result<Lut, LutError> {
+        let image = Img(self.0).parse(path).context(LutImageSnafu)?;
+        let (width, height) = image.dimensions();
+        ensure!(
+            height > 0 && u64::from(width) == u64::from(height) * u64::from(height),
+            InvalidStripSnafu { width, height }
+        );
+
+        let size = height;
+        let image = image.to_rgb32f();
+        let mut data = Vec::with_capacity(entries(size).context(TooLargeSnafu { size })?);
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    data.push(image.get_pixel(b * size + r, g).0);
+                }
+            }
+        }
+        Ok(Lut { size, data })
+    }
+}
@@ -6,9 +6,16 @@
 
 //! 3d mesh formats
 
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 
+use crate::{formats::Format, Path};
+
+mod lod;
 mod obj;
+pub mod simplify;
+pub use lod::*;
 pub use obj::*;
 
 /// Vertex type, rexported from mint
@@ -77,6 +84,97 @@ impl<'a> Iterator for VertIter<'a> {
     }
 }
 
+/// Mesh with deduplicated vertices that are referenced by an index buffer
+///
+/// Normals and uvs are either empty or have one entry per vertex
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct IndexedMesh<T> {
+    pub verts: Vec<Vertex<T>>,
+    pub normals: Vec<Vertex<T>>,
+    pub uvs: Vec<Vertex2<T>>,
+    pub indices: Vec<u32>,
+}
+
+impl IndexedMesh<f32> {
+    /// Fetch a single vertex
+    pub fn vert(&self, index: u32) -> Vert {
+        let index = index as usize;
+        Vert {
+            pos: self.verts[index],
+            norm: self.normals.get(index).copied(),
+            uv: self.uvs.get(index).copied(),
+        }
+    }
+
+    /// Iterate over the unique vertices of this mesh
+    pub fn verts(&self) -> impl Iterator<Item = Vert> + '_ {
+        (0..self.verts.len() as u32).map(|i| self.vert(i))
+    }
+
+    /// Iterate over the triangles of this mesh
+    pub fn faces(&self) -> impl Iterator<Item = (Vert, Vert, Vert)> + '_ {
+        self.indices
+            .chunks_exact(3)
+            .map(|tri| (self.vert(tri[0]), self.vert(tri[1]), self.vert(tri[2])))
+    }
+}
+
+impl From<&Mesh<f32>> for IndexedMesh<f32> {
+    /// Build an indexed mesh by merging vertices with identical attributes
+    fn from(mesh: &Mesh<f32>) -> Self {
+        let has_norm = mesh.normals.len() >= mesh.verts.len();
+        let has_uv = mesh.uvs.len() >= mesh.verts.len();
+
+        let mut out = IndexedMesh::default();
+        let mut seen: HashMap<[u32; 8], u32> = HashMap::new();
+        for v in mesh.verts() {
+            let norm = v.norm.filter(|_| has_norm).unwrap_or(Vertex {
+                x: 0.0,
+                y: 0.0,
+                z: 0.0,
+            });
+            let uv =
+                v.uv.filter(|_| has_uv)
+                    .unwrap_or(Vertex2 { x: 0.0, y: 0.0 });
+
+            // compare bit patterns so that every attribute must match exactly
+            let key = [
+                v.pos.x, v.pos.y, v.pos.z, norm.x, norm.y, norm.z, uv.x, uv.y,
+            ]
+            .map(f32::to_bits);
+
+            let index = *seen.entry(key).or_insert_with(|| {
+                out.verts.push(v.pos);
+                if has_norm {
+                    out.normals.push(norm);
+                }
+                if has_uv {
+                    out.uvs.push(uv);
+                }
+                out.verts.len() as u32 - 1
+            });
+            out.indices.push(index);
+        }
+        out
+    }
+}
+
+/// Wraps a mesh format to deduplicate its vertices on import
+#[derive(Clone, Copy)]
+pub struct Indexed<F>(pub F);
+
+impl<F> Format for Indexed<F>
+where
+    F: Format<Output = Mesh<f32>>,
+{
+    type Output = IndexedMesh<f32>;
+    type Error = F::Error;
+
+    fn parse(&self, path: &Path) -> Result<Self::Output, Self::Error> {
+        Ok(IndexedMesh::from(&self.0.parse(path)?))
+    }
+}
+
 /// Common scene type for importing
 ///
 /// # Todo
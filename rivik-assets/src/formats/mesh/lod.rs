@@ -0,0 +1,242 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public License,
+ * v. 2.0. If a copy of the MPL was not distributed with this file, You can
+ * obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+//! Levels of detail for meshes
+//!
+//! LOD chains can either be generated when a mesh is loaded with [`Lods`] or baked ahead of time
+//! with [`write_lods`], which is what the asset packer does, and loaded with [`BakedLods`].
+//!
+//! Baked chains are stored as a little endian `u32` level count followed by each level, which is
+//! the `u32` lengths of its vertices, normals, uvs and indices followed by their contents.
+//! Levels are checked as they are read: normals and uvs must be empty or have one entry per
+//! vertex and every index must be below the vertex count.
+
+use std::io::{self, Read, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use mint::{Point2, Point3};
+use snafu::{ensure, Backtrace, ResultExt, Snafu};
+
+use crate::{formats::Format, Path, ReaderCreationError};
+
+use super::{simplify::lods, IndexedMesh, Mesh};
+
+/// Generates a chain of LOD meshes from a mesh format on import
+///
+/// The second field is the most levels generated, including the original mesh.
+#[derive(Clone, Copy)]
+pub struct Lods<F>(pub F, pub usize);
+
+impl<F> Format for Lods<F>
+where
+    F: Format<Output = Mesh<f32>>,
+{
+    type Output = Vec<IndexedMesh<f32>>;
+    type Error = F::Error;
+
+    fn parse(&self, path: &Path) -> Result<Self::Output, Self::Error> {
+        let mesh = IndexedMesh::from(&self.0.parse(path)?);
+        Ok(lods(&mesh, self.1))
+    }
+}
+
+/// File format definition for a LOD chain written by [`write_lods`]
+#[derive(Clone, Copy)]
+pub struct BakedLods;
+
+#[derive(Snafu, Debug)]
+pub enum BakedLodsError {
+    #[snafu(display("Failed loading baked LODs"))]
+    CreationError {
+        #[snafu(backtrace)]
+        source: ReaderCreationError,
+    },
+    #[snafu(display("Failed to read baked LODs"))]
+    ReadError {
+        source: io::Error,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("LOD {level} has {len} {attribute} for {verts} vertices"))]
+    AttributeLength {
+        level: usize,
+        attribute: &'static str,
+        len: usize,
+        verts: usize,
+        backtrace: Backtrace,
+    },
+    #[snafu(display("LOD {level} has index {index} past its {verts} vertices"))]
+    IndexOutOfRange {
+        level: usize,
+        index: u32,
+        verts: usize,
+        backtrace: Backtrace,
+    },
+}
+
+impl Format for BakedLods {
+    type Output = Vec<IndexedMesh<f32>>;
+    type Error = BakedLodsError;
+
+    fn parse(&self, path: &Path) -> Result<Self::Output, Self::Error> {
+        read_lods(&mut path.reader().context(CreationSnafu)?)
+    }
+}
+
+/// Write a LOD chain so it can be loaded with [`BakedLods`]
+pub fn write_lods(w: &mut impl Write, lods: &[IndexedMesh<f32>]) -> io::Result<()> {
+    w.write_u32::<LittleEndian>(lods.len() as u32)?;
+    for mesh in lods {
+        for len in [
+            mesh.verts.len(),
+            mesh.normals.len(),
+            mesh.uvs.len(),
+            mesh.indices.len(),
+        ] {
+            w.write_u32::<LittleEndian>(len as u32)?;
+        }
+        for v in mesh.verts.iter().chain(&mesh.normals) {
+            w.write_f32::<LittleEndian>(v.x)?;
+            w.write_f32::<LittleEndian>(v.y)?;
+            w.write_f32::<LittleEndian>(v.z)?;
+        }
+        for uv in &mesh.uvs {
+            w.write_f32::<LittleEndian>(uv.x)?;
+            w.write_f32::<LittleEndian>(uv.y)?;
+        }
+        for &index in &mesh.indices {
+            w.write_u32::<LittleEndian>(index)?;
+        }
+    }
+    Ok(())
+}
+
+fn read_lods(r: &mut impl Read) -> Result<Vec<IndexedMesh<f32>>, BakedLodsError> {
+    let levels = r.read_u32::<LittleEndian>().context(ReadSnafu)?;
+    (0..levels as usize)
+        .map(|level| {
+            let mesh = read_level(r).context(ReadSnafu)?;
+            check_level(level, &mesh)?;
+            Ok(mesh)
+        })
+        .collect()
+}
+
+fn read_level(r: &mut impl Read) -> io::Result<IndexedMesh<f32>> {
+    let mut lens = [0; 4];
+    for len in &mut lens {
+        *len = r.read_u32::<LittleEndian>()?;
+    }
+    let [verts, normals, uvs, indices] = lens;
+
+    let mut point = || -> io::Result<_> {
+        Ok(Point3 {
+            x: r.read_f32::<LittleEndian>()?,
+            y: r.read_f32::<LittleEndian>()?,
+            z: r.read_f32::<LittleEndian>()?,
+        })
+    };
+    let verts = (0..verts).map(|_| point()).collect::<io::Result<_>>()?;
+    let normals = (0..normals).map(|_| point()).collect::<io::Result<_>>()?;
+    let uvs = (0..uvs)
+        .map(|_| {
+            Ok(Point2 {
+                x: r.read_f32::<LittleEndian>()?,
+                y: r.read_f32::<LittleEndian>()?,
+            })
+        })
+        .collect::<io::Result<_>>()?;
+    let indices = (0..indices)
+        .map(|_| r.read_u32::<LittleEndian>())
+        .collect::<io::Result<_>>()?;
+
+    Ok(IndexedMesh {
+        verts,
+        normals,
+        uvs,
+        indices,
+    })
+}
+
+/// Check that the attributes and indices of a level match its vertices
+fn check_level(level: usize, mesh: &IndexedMesh<f32>) -> Result<(), BakedLodsError> {
+    let verts = mesh.verts.len();
+    for (attribute, len) in [("normals", mesh.normals.len()), ("uvs", mesh.uvs.len())] {
+        ensure!(
+            len == 0 || len == verts,
+            AttributeLengthSnafu {
+                level,
+                attribute,
+                len,
+                verts
+            }
+        );
+    }
+    if let Some(&index) = mesh.indices.iter().find(|&&i| i as usize >= verts) {
+        return IndexOutOfRangeSnafu {
+            level,
+            index,
+            verts,
+        }
+        .fail();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triangle() -> IndexedMesh<f32> {
+        let point = |x, y| Point3 { x, y, z: 0.0 };
+        IndexedMesh {
+            verts: vec![point(0.0, 0.0), point(1.0, 0.0), point(0.0, 1.0)],
+            normals: vec![],
+            uvs: vec![],
+            indices: vec![0, 1, 2],
+        }
+    }
+
+    fn round_trip(lods: &[IndexedMesh<f32>]) -> Result<Vec<IndexedMesh<f32>>, BakedLodsError> {
+        let mut bytes = vec![];
+        write_lods(&mut bytes, lods).unwrap();
+        read_lods(&mut bytes.as_slice())
+    }
+
+    #[test]
+    fn reads_written_lods() {
+        let lods = round_trip(&[triangle(), triangle()]).unwrap();
+        assert_eq!(lods.len(), 2);
+        assert_eq!(lods[1].indices, [0, 1, 2]);
+    }
+
+    #[test]
+    fn rejects_out_of_range_indices() {
+        let mut mesh = triangle();
+        mesh.indices[2] = 3;
+        assert!(matches!(
+            round_trip(&[triangle(), mesh]),
+            Err(BakedLodsError::IndexOutOfRange {
+                level: 1,
+                index: 3,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn rejects_mismatched_attributes() {
+        let mut mesh = triangle();
+        mesh.uvs = vec![Point2 { x: 0.0, y: 0.0 }];
+        assert!(matches!(
+            round_trip(&[mesh]),
+            Err(BakedLodsError::AttributeLength {
+                attribute: "uvs",
+                len: 1,
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,526 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public License,
+ * v. 2.0. If a copy of the MPL was not distributed with this file, You can
+ * obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+//! Mesh simplification using quadric error metrics
+//!
+//! Edges are collapsed one at a time, cheapest first, where the cost of moving a vertex is the
+//! squared distance to the planes of the triangles it used to touch. Vertices are only ever moved
+//! onto one of their neighbours so no new vertices or attributes are invented.
+
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap, HashSet},
+};
+
+use super::IndexedMesh;
+
+/// Open edges are weighted this much more than surface planes so outlines are kept
+const BOUNDARY_WEIGHT: f64 = 10.0;
+
+/// Reduce `mesh` to at most `target` triangles
+///
+/// Simplification stops early when no edge can be collapsed without flipping a triangle, so the
+/// result may have more triangles than requested.
+pub fn simplify(mesh: &IndexedMesh<f32>, target: usize) -> IndexedMesh<f32> {
+    let mut state = Simplifier::new(mesh);
+    state.run(target);
+    state.finish(mesh)
+}
+
+/// Generate a chain of `levels` meshes, each with roughly half the triangles of the last
+///
+/// The first level is `mesh` itself. The chain is cut short once a level can't be simplified
+/// any further.
+pub fn lods(mesh: &IndexedMesh<f32>, levels: usize) -> Vec<IndexedMesh<f32>> {
+    let mut lods = vec![mesh.clone()];
+    while lods.len() < levels {
+        let last = lods.last().unwrap();
+        let triangles = last.indices.len() / 3;
+        let next = simplify(last, triangles / 2);
+
+        // don't keep levels that are barely cheaper than the last one
+        if next.indices.len() / 3 * 10 >= triangles * 9 {
+            break;
+        }
+        lods.push(next);
+    }
+    lods
+}
+
+/// Symmetric 4x4 matrix measuring the squared distance to a set of planes
+#[derive(Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    fn plane([a, b, c]: [f64; 3], d: f64, weight: f64) -> Self {
+        Self(
+            [
+                a * a,
+                a * b,
+                a * c,
+                a * d,
+                b * b,
+                b * c,
+                b * d,
+                c * c,
+                c * d,
+                d * d,
+            ]
+            .map(|x| x * weight),
+        )
+    }
+
+    fn add(&mut self, other: &Self) {
+        for (a, b) in self.0.iter_mut().zip(other.0) {
+            *a += b;
+        }
+    }
+
+    fn error(&self, [x, y, z]: [f64; 3]) -> f64 {
+        let q = &self.0;
+        q[0] * x * x
+            + 2.0 * q[1] * x * y
+            + 2.0 * q[2] * x * z
+            + 2.0 * q[3] * x
+            + q[4] * y * y
+            + 2.0 * q[5] * y * z
+            + 2.0 * q[6] * y
+            + q[7] * z * z
+            + 2.0 * q[8] * z
+            + q[9]
+    }
+}
+
+/// A candidate collapse of position `from` onto position `to`
+struct Collapse {
+    cost: f64,
+    from: usize,
+    to: usize,
+    /// versions of both positions when the cost was computed
+    versions: (u32, u32),
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // reversed so the heap pops the cheapest collapse first
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+struct Simplifier {
+    /// unique positions, vertices that only differ in their attributes share a position
+    positions: Vec<[f64; 3]>,
+    /// position of each vertex
+    position_of: Vec<usize>,
+    /// vertices at each position
+    vertices_at: Vec<Vec<u32>>,
+    /// normal and uv of each vertex
+    attributes: Vec<[f64; 5]>,
+    quadrics: Vec<Quadric>,
+    /// triangles touching each position, may include removed triangles
+    triangles_at: Vec<Vec<usize>>,
+    triangles: Vec<[u32; 3]>,
+    alive: Vec<bool>,
+    live_triangles: usize,
+    removed: Vec<bool>,
+    versions: Vec<u32>,
+    heap: BinaryHeap<Collapse>,
+}
+
+impl Simplifier {
+    fn new(mesh: &IndexedMesh<f32>) -> Self {
+        // merge vertices split by normals or uvs so seams don't tear open
+        let mut positions = vec![];
+        let mut vertices_at: Vec<Vec<u32>> = vec![];
+        let mut seen = HashMap::new();
+        let position_of = mesh
+            .verts
+            .iter()
+            .enumerate()
+            .map(|(i, v)| {
+                let key = [v.x, v.y, v.z].map(f32::to_bits);
+                let position = *seen.entry(key).or_insert_with(|| {
+                    positions.push([v.x as f64, v.y as f64, v.z as f64]);
+                    vertices_at.push(vec![]);
+                    positions.len() - 1
+                });
+                vertices_at[position].push(i as u32);
+                position
+            })
+            .collect();
+
+        let triangles: Vec<[u32; 3]> = mesh
+            .indices
+            .chunks_exact(3)
+            .map(|t| [t[0], t[1], t[2]])
+            .collect();
+
+        let attributes = (0..mesh.verts.len() as u32)
+            .map(|i| {
+                let v = mesh.vert(i);
+                let n = v.norm.map_or([0.0; 3], |n| [n.x, n.y, n.z]);
+                let uv = v.uv.map_or([0.0; 2], |uv| [uv.x, uv.y]);
+                [n[0], n[1], n[2], uv[0], uv[1]].map(|x| x as f64)
+            })
+            .collect();
+
+        let count = positions.len();
+        let mut state = Self {
+            positions,
+            position_of,
+            vertices_at,
+            attributes,
+            quadrics: vec![Quadric::default(); count],
+            triangles_at: vec![vec![]; count],
+            alive: vec![true; triangles.len()],
+            live_triangles: triangles.len(),
+            triangles,
+            removed: vec![false; count],
+            versions: vec![0; count],
+            heap: BinaryHeap::new(),
+        };
+
+        // count how many triangles use each edge to find the open ones
+        let mut edges: HashMap<(usize, usize), u32> = HashMap::new();
+        for t in 0..state.triangles.len() {
+            let corners = state.corners(t);
+            for &p in &corners {
+                state.triangles_at[p].push(t);
+            }
+
+            let Some((normal, area)) = state.normal(corners) else {
+                continue;
+            };
+            let d = -dot(normal, state.positions[corners[0]]);
+            let plane = Quadric::plane(normal, d, area);
+            for &p in &corners {
+                state.quadrics[p].add(&plane);
+            }
+            for i in 0..3 {
+                let (a, b) = (corners[i], corners[(i + 1) % 3]);
+                *edges.entry((a.min(b), a.max(b))).or_default() += 1;
+            }
+        }
+
+        // open edges get a plane standing on them so they can only slide along the outline
+        for t in 0..state.triangles.len() {
+            let corners = state.corners(t);
+            let Some((normal, _)) = state.normal(corners) else {
+                continue;
+            };
+            for i in 0..3 {
+                let (a, b) = (corners[i], corners[(i + 1) % 3]);
+                if edges.get(&(a.min(b), a.max(b))) != Some(&1) {
+                    continue;
+                }
+                let edge = sub(state.positions[b], state.positions[a]);
+                let length = dot(edge, edge).sqrt();
+                let side = cross(edge, normal);
+                let side_length = dot(side, side).sqrt();
+                if side_length <= f64::EPSILON {
+                    continue;
+                }
+                let side = side.map(|x| x / side_length);
+                let d = -dot(side, state.positions[a]);
+                let plane = Quadric::plane(side, d, length * length * BOUNDARY_WEIGHT);
+                state.quadrics[a].add(&plane);
+                state.quadrics[b].add(&plane);
+            }
+        }
+
+        for (a, b) in edges.into_keys() {
+            state.push(a, b);
+        }
+        state
+    }
+
+    /// Positions at the corners of a triangle
+    fn corners(&self, t: usize) -> [usize; 3] {
+        self.triangles[t].map(|v| self.position_of[v as usize])
+    }
+
+    /// Unit normal and area of a triangle, degenerate triangles have none
+    fn normal(&self, [a, b, c]: [usize; 3]) -> Option<([f64; 3], f64)> {
+        let (a, b, c) = (self.positions[a], self.positions[b], self.positions[c]);
+        let n = cross(sub(b, a), sub(c, a));
+        let length = dot(n, n).sqrt();
+        (length > f64::EPSILON).then(|| (n.map(|x| x / length), length / 2.0))
+    }
+
+    /// Queue the cheaper direction of collapsing the edge between `a` and `b`
+    fn push(&mut self, a: usize, b: usize) {
+        let mut quadric = self.quadrics[a];
+        quadric.add(&self.quadrics[b]);
+        let onto_b = quadric.error(self.positions[b]);
+        let onto_a = quadric.error(self.positions[a]);
+        let (from, to, cost) = if onto_b <= onto_a {
+            (a, b, onto_b)
+        } else {
+            (b, a, onto_a)
+        };
+        self.heap.push(Collapse {
+            cost,
+            from,
+            to,
+            versions: (self.versions[from], self.versions[to]),
+        });
+    }
+
+    fn run(&mut self, target: usize) {
+        while self.live_triangles > target {
+            let Some(collapse) = self.heap.pop() else {
+                break;
+            };
+            let Collapse {
+                from, to, versions, ..
+            } = collapse;
+            if self.removed[from]
+                || self.removed[to]
+                || versions != (self.versions[from], self.versions[to])
+            {
+                continue;
+            }
+            if self.flips(from, to) {
+                continue;
+            }
+            self.collapse(from, to);
+        }
+    }
+
+    /// Check if moving `from` onto `to` would turn any remaining triangle over
+    fn flips(&self, from: usize, to: usize) -> bool {
+        self.triangles_at[from]
+            .iter()
+            .filter(|&&t| self.alive[t])
+            .any(|&t| {
+                let corners = self.corners(t);
+                if corners.contains(&to) {
+                    return false;
+                }
+                let moved = corners.map(|p| if p == from { to } else { p });
+                match (self.normal(corners), self.normal(moved)) {
+                    (Some((before, _)), Some((after, _))) => dot(before, after) < 0.2,
+                    _ => true,
+                }
+            })
+    }
+
+    fn collapse(&mut self, from: usize, to: usize) {
+        // each vertex moves onto the vertex at `to` with the closest attributes
+        let remap: HashMap<u32, u32> = self.vertices_at[from]
+            .iter()
+            .map(|&v| {
+                let closest = self.vertices_at[to]
+                    .iter()
+                    .copied()
+                    .min_by(|&a, &b| {
+                        self.attribute_distance(v, a)
+                            .total_cmp(&self.attribute_distance(v, b))
+                    })
+                    .unwrap();
+                (v, closest)
+            })
+            .collect();
+
+        for t in std::mem::take(&mut self.triangles_at[from]) {
+            if !self.alive[t] {
+                continue;
+            }
+            for v in &mut self.triangles[t] {
+                if let Some(&to) = remap.get(v) {
+                    *v = to;
+                }
+            }
+            let [a, b, c] = self.corners(t);
+            if a == b || b == c || a == c {
+                self.alive[t] = false;
+                self.live_triangles -= 1;
+            } else {
+                self.triangles_at[to].push(t);
+            }
+        }
+
+        let quadric = self.quadrics[from];
+        self.quadrics[to].add(&quadric);
+        self.removed[from] = true;
+        self.versions[to] += 1;
+        self.triangles_at[to].retain(|&t| self.alive[t]);
+
+        let neighbours: HashSet<usize> = self.triangles_at[to]
+            .iter()
+            .flat_map(|&t| self.corners(t))
+            .filter(|&p| p != to)
+            .collect();
+        for neighbour in neighbours {
+            self.push(to, neighbour);
+        }
+    }
+
+    /// How different the normals and uvs of two vertices are
+    fn attribute_distance(&self, a: u32, b: u32) -> f64 {
+        let (a, b) = (self.attributes[a as usize], self.attributes[b as usize]);
+        a.iter().zip(b).map(|(a, b)| (a - b) * (a - b)).sum()
+    }
+
+    /// Build a mesh from the remaining triangles, dropping unused vertices
+    fn finish(&self, mesh: &IndexedMesh<f32>) -> IndexedMesh<f32> {
+        let mut out = IndexedMesh::default();
+        let mut new_index = HashMap::new();
+        for t in (0..self.triangles.len()).filter(|&t| self.alive[t]) {
+            for v in self.triangles[t] {
+                let index = *new_index.entry(v).or_insert_with(|| {
+                    let v = v as usize;
+                    out.verts.push(mesh.verts[v]);
+                    if let Some(&norm) = mesh.normals.get(v) {
+                        out.normals.push(norm);
+                    }
+                    if let Some(&uv) = mesh.uvs.get(v) {
+                        out.uvs.push(uv);
+                    }
+                    out.verts.len() as u32 - 1
+                });
+                out.indices.push(index);
+            }
+        }
+        out
+    }
+}
+
+fn sub(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::mesh::Vertex;
+
+    /// A cube centered on the origin with each face split into a `n` by `n` grid
+    ///
+    /// Faces don't share vertices, like a cube with flat normals, and with `round` every vertex is
+    /// pushed out onto the unit sphere.
+    fn cube(n: u32, round: bool) -> IndexedMesh<f32> {
+        let mut mesh = IndexedMesh::default();
+        let axes = [
+            ([1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]),
+            ([0.0, 1.0, 0.0], [0.0, 0.0, 1.0], [1.0, 0.0, 0.0]),
+            ([0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+        ];
+        for (normal, u, v) in axes {
+            for sign in [1.0f32, -1.0] {
+                let first = mesh.verts.len() as u32;
+                for j in 0..=n {
+                    for i in 0..=n {
+                        let (s, t) = (
+                            i as f32 / n as f32 * 2.0 - 1.0,
+                            j as f32 / n as f32 * 2.0 - 1.0,
+                        );
+                        let mut p =
+                            [0, 1, 2].map(|k| normal[k] * sign + u[k] * s + v[k] * t * sign);
+                        if round {
+                            let length = p.iter().map(|x| x * x).sum::<f32>().sqrt();
+                            p = p.map(|x| x / length);
+                        }
+                        mesh.verts.push(Vertex::from(p));
+                        mesh.normals.push(Vertex::from(normal.map(|x| x * sign)));
+                    }
+                }
+                for j in 0..n {
+                    for i in 0..n {
+                        let a = first + j * (n + 1) + i;
+                        let (b, c, d) = (a + 1, a + n + 1, a + n + 2);
+                        mesh.indices.extend([a, b, d, a, d, c]);
+                    }
+                }
+            }
+        }
+        mesh
+    }
+
+    /// Check that a mesh around the origin is well formed and faces outwards
+    fn check(mesh: &IndexedMesh<f32>) {
+        assert_eq!(mesh.indices.len() % 3, 0);
+        assert_eq!(mesh.normals.len(), mesh.verts.len());
+        let pos = |i: u32| {
+            let v = mesh.verts[i as usize];
+            [v.x, v.y, v.z].map(|x| x as f64)
+        };
+        for t in mesh.indices.chunks_exact(3) {
+            assert!(t.iter().all(|&i| (i as usize) < mesh.verts.len()));
+            let (a, b, c) = (pos(t[0]), pos(t[1]), pos(t[2]));
+            let normal = cross(sub(b, a), sub(c, a));
+            assert!(
+                dot(normal, normal).sqrt() > 1e-9,
+                "degenerate triangle {t:?}"
+            );
+            let center = [0, 1, 2].map(|k| (a[k] + b[k] + c[k]) / 3.0);
+            assert!(dot(normal, center) > 0.0, "flipped triangle {t:?}");
+        }
+    }
+
+    #[test]
+    fn test_inputs_are_valid() {
+        check(&cube(8, false));
+        check(&cube(8, true));
+    }
+
+    #[test]
+    fn simplify_cube() {
+        let mesh = cube(8, false);
+        assert_eq!(mesh.indices.len() / 3, 768);
+        let simple = simplify(&mesh, 48);
+        assert!(simple.indices.len() / 3 <= 48);
+        assert!(simple.indices.len() / 3 >= 12);
+        check(&simple);
+    }
+
+    #[test]
+    fn simplify_sphere() {
+        let mesh = cube(16, true);
+        let simple = simplify(&mesh, 300);
+        assert!(simple.indices.len() / 3 <= 300);
+        assert!(!simple.indices.is_empty());
+        check(&simple);
+    }
+
+    #[test]
+    fn lod_chain() {
+        let mesh = cube(16, true);
+        let lods = lods(&mesh, 4);
+        assert_eq!(lods.len(), 4);
+        assert_eq!(lods[0].indices, mesh.indices);
+        for pair in lods.windows(2) {
+            assert!(pair[1].indices.len() <= pair[0].indices.len() / 2);
+            check(&pair[1]);
+        }
+    }
+}
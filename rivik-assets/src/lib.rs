@@ -92,6 +92,8 @@ pub mod formats {
     pub mod misc;
     pub mod img {
         mod general;
+        mod lut;
         pub use general::*;
+        pub use lut::*;
     }
 }
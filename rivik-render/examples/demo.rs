@@ -11,11 +11,12 @@ use egui::plot::Plot;
 use image::ImageFormat;
 use pollster::block_on;
 use rivik_render::{
+    camera,
     context::{resize, surface_config},
     draw::{self, pixel_mesh, Mesh, PixelMesh},
     filters::display::DisplayFilter,
     lights::{ambient::AmbientLight, sun::SunLight},
-    load::{GpuMesh, GpuTexture},
+    load::{GpuIndexedMesh, GpuTexture},
     tracing::{display_traces, generate_chart, UiSubscriber},
     transform::Spatial,
     Frame, Transform,
@@ -44,7 +45,7 @@ async fn run() -> Result<(), Whatever> {
     // load a mesh
     let mesh = load(
         "file:assets/fighter_smooth.obj",
-        GpuMesh(ObjMesh, draw::mesh::vertex_buffer),
+        GpuIndexedMesh(ObjMesh, draw::mesh::indexed_vertex_buffer),
     )
     .whatever_context("Failed to fetch fighter ship")?;
     let tex = load(
@@ -66,7 +67,7 @@ async fn run() -> Result<(), Whatever> {
 
     let mut model = ultraviolet::Mat4::identity();
 
-    let transform = Transform::new(proj, view, model);
+    let transform = Transform::new(model);
     let mesh_bundle = Mesh::new(mesh, tex);
 
     let mut i = 0;
@@ -144,8 +145,9 @@ async fn run() -> Result<(), Whatever> {
                     Vec3::unit_y(),
                 );
 
-                mesh_bundle.transform().update(proj, view, model);
-                sun.transform().update(proj, view, model);
+                camera::set(proj, view);
+                mesh_bundle.transform().update(model);
+                sun.transform().update(model);
                 mem::drop(span);
 
                 {
@@ -192,7 +194,7 @@ async fn run() -> Result<(), Whatever> {
                             .show_axes([false, false])
                             .show_background(false)
                             .show(ui, |plot| {
-                                display_traces(plot, spans.0, spans.1);
+                                display_traces(plot, spans.0, spans.1, spans.2);
                             });
                     });
                 });
@@ -0,0 +1,279 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public License,
+ * v. 2.0. If a copy of the MPL was not distributed with this file, You can
+ * obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+//! Antialiasing filters applied to the lit image
+//!
+//! The filter is chosen with [`set_antialiasing`](crate::context::set_antialiasing) and is run
+//! between the lighting and post-processing passes. Multisampling is configured separately
+//! through [`Settings::msaa_samples`](crate::Settings::msaa_samples) and covers both the
+//! G-buffer and the particles drawn over the lit image.
+
+use std::{borrow::Cow, sync::Mutex};
+
+use once_cell::sync::OnceCell;
+use wgpu::{
+    AddressMode, BindGroup, BindGroupLayout, BindGroupLayoutEntry, BindingType, ColorTargetState,
+    ColorWrites, CommandEncoder, Extent3d, FilterMode, ImageCopyTexture, Origin3d,
+    PushConstantRange, RenderPipeline, SamplerBindingType, ShaderStages, Texture, TextureAspect,
+    TextureDescriptor, TextureDimension, TextureSampleType, TextureUsages, TextureView,
+    TextureViewDimension,
+};
+
+use crate::{
+    context::{device, gbuffer, settings},
+    pipeline::GBuffer,
+    shader,
+    stats::{self, DrawStats},
+};
+
+/// How much of each new frame is blended into the TAA history
+const TAA_BLEND: f32 = 0.1;
+
+/// An antialiasing filter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Antialiasing {
+    /// Do not antialias
+    #[default]
+    None,
+    /// Fast approximate antialiasing, a cheap edge blur
+    Fxaa,
+    /// Temporal antialiasing
+    ///
+    /// Jitters the camera by a sub-pixel amount each frame and accumulates the frames using the
+    /// motion vectors in the G-buffer
+    Taa,
+}
+
+static RESOLVE: OnceCell<Resolve> = OnceCell::new();
+
+struct Resolve {
+    history: [Texture; 2],
+    history_views: [TextureView; 2],
+    bind_groups: [BindGroup; 2],
+    taa: RenderPipeline,
+    fxaa: RenderPipeline,
+    state: Mutex<ResolveState>,
+}
+
+#[derive(Default)]
+struct ResolveState {
+    /// the history texture holding the last resolved frame
+    index: usize,
+    /// is the history texture holding a frame that can be reprojected
+    valid: bool,
+}
+
+/// Run the selected antialiasing filter over the HDR buffer
+pub(crate) fn resolve(encoder: &mut CommandEncoder) {
+    let mode = settings().read().unwrap().antialiasing;
+    if mode == Antialiasing::None {
+        if let Some(resolve) = RESOLVE.get() {
+            resolve.state.lock().unwrap().valid = false;
+        }
+        return;
+    }
+
+    let resolve = RESOLVE.get_or_init(Resolve::new);
+    let mut state = resolve.state.lock().unwrap();
+
+    // read from one history texture and write to the other
+    let read = state.index;
+    let write = 1 - read;
+    {
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Antialiasing"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &resolve.history_views[write],
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+
+        match mode {
+            Antialiasing::Taa => {
+                let blend: f32 = if state.valid { TAA_BLEND } else { 1.0 };
+                rpass.set_pipeline(&resolve.taa);
+                rpass.set_push_constants(ShaderStages::FRAGMENT, 0, &blend.to_le_bytes());
+            }
+            _ => rpass.set_pipeline(&resolve.fxaa),
+        }
+        rpass.set_bind_group(0, &resolve.bind_groups[read], &[]);
+        rpass.draw(0..6, 0..1);
+        stats::record_draw(DrawStats::new(1, 6));
+    }
+
+    // copy the result back so filters can read it from the HDR buffer
+    let (width, height) = gbuffer().size();
+    encoder.copy_texture_to_texture(
+        ImageCopyTexture {
+            texture: &resolve.history[write],
+            mip_level: 0,
+            origin: Origin3d::ZERO,
+            aspect: TextureAspect::All,
+        },
+        ImageCopyTexture {
+            texture: &gbuffer().hdr_tex,
+            mip_level: 0,
+            origin: Origin3d::ZERO,
+            aspect: TextureAspect::All,
+        },
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+
+    state.index = write;
+    state.valid = mode == Antialiasing::Taa;
+}
+
+impl Resolve {
+    fn new() -> Self {
+        let device = device();
+        let gbuffer = gbuffer();
+        let (width, height) = gbuffer.size();
+
+        let history = [0, 1].map(|_| {
+            device.create_texture(&TextureDescriptor {
+                label: Some("Antialiasing History"),
+                size: Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: GBuffer::hdr_format(),
+                usage: TextureUsages::RENDER_ATTACHMENT
+                    | TextureUsages::TEXTURE_BINDING
+                    | TextureUsages::COPY_SRC,
+                view_formats: Default::default(),
+            })
+        });
+        let history_views = [0, 1].map(|i| history[i].create_view(&Default::default()));
+
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Antialiasing"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+                texture_entry(1),
+                texture_entry(2),
+                texture_entry(3),
+            ],
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            ..Default::default()
+        });
+
+        let bind_groups = [0, 1].map(|i| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Antialiasing"),
+                layout: &layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::Sampler(&sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&gbuffer.hdr_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::TextureView(&history_views[i]),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: wgpu::BindingResource::TextureView(&gbuffer.velocity_view),
+                    },
+                ],
+            })
+        });
+
+        Self {
+            taa: pipeline(&shader!("shaders/taa.wgsl").unwrap(), &layout, true),
+            fxaa: pipeline(&shader!("shaders/fxaa.wgsl").unwrap(), &layout, false),
+            history,
+            history_views,
+            bind_groups,
+            state: Mutex::new(ResolveState::default()),
+        }
+    }
+}
+
+fn texture_entry(binding: u32) -> BindGroupLayoutEntry {
+    BindGroupLayoutEntry {
+        binding,
+        visibility: ShaderStages::FRAGMENT,
+        ty: BindingType::Texture {
+            multisampled: false,
+            sample_type: TextureSampleType::Float { filterable: true },
+            view_dimension: TextureViewDimension::D2,
+        },
+        count: None,
+    }
+}
+
+fn pipeline(source: &str, layout: &BindGroupLayout, push_blend: bool) -> RenderPipeline {
+    let device = device();
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: None,
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(source)),
+    });
+
+    let push_constant_ranges: &[PushConstantRange] = if push_blend {
+        &[PushConstantRange {
+            stages: ShaderStages::FRAGMENT,
+            range: 0..4,
+        }]
+    } else {
+        &[]
+    };
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts: &[layout],
+        push_constant_ranges,
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Antialiasing"),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[Some(ColorTargetState {
+                format: GBuffer::hdr_format(),
+                blend: None,
+                write_mask: ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    })
+}
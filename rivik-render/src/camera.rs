@@ -0,0 +1,239 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public License,
+ * v. 2.0. If a copy of the MPL was not distributed with this file, You can
+ * obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+//! The camera that geometry is rendered from
+//!
+//! The camera's matrices are stored in a single uniform buffer that is shared by every
+//! [`Transform`](crate::Transform). Call [`set`] once per frame before drawing. Additional
+//! [`Camera`]s can be used to draw [render targets](crate::target).
+//!
+//! When temporal antialiasing is enabled the projection is offset by a sub-pixel jitter that
+//! changes every frame. The unjittered matrices of this and the previous frame are also kept so
+//! geometry can write motion vectors.
+
+use std::sync::Mutex;
+
+use mint::{ColumnMatrix4, Vector3};
+use once_cell::sync::OnceCell;
+use ultraviolet::{Mat4, Vec2, Vec3, Vec4};
+use wgpu::{Buffer, BufferDescriptor, BufferUsages, CommandEncoder, RenderPass};
+
+use crate::{
+    antialias::Antialiasing,
+    context::{device, gbuffer, queue, settings},
+    stats,
+};
+
+/// Size of the camera uniform in bytes
+pub const CAMERA_SIZE: u64 = 64 * 4 + 16;
+
+/// Number of frames before the jitter sequence repeats
+const JITTER_PHASES: u32 = 8;
+
+static UNIFORM: OnceCell<Buffer> = OnceCell::new();
+static MAIN: OnceCell<Camera> = OnceCell::new();
+
+/// A normalized rectangle of the frame that a camera is drawn into
+///
+/// `(0, 0)` is the top left corner of the frame and `(1, 1)` is the bottom right.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    /// Left edge of the viewport
+    pub x: f32,
+    /// Top edge of the viewport
+    pub y: f32,
+    /// Width of the viewport
+    pub width: f32,
+    /// Height of the viewport
+    pub height: f32,
+}
+
+impl Viewport {
+    /// A viewport covering the whole frame
+    pub const FULL: Self = Self {
+        x: 0.0,
+        y: 0.0,
+        width: 1.0,
+        height: 1.0,
+    };
+
+    /// Create a viewport
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// The ratio of width to height of this viewport on a frame of `size`
+    pub fn aspect(&self, (width, height): (u32, u32)) -> f32 {
+        (self.width * width as f32) / (self.height * height as f32)
+    }
+
+    /// The `(x, y, width, height)` of this viewport in G-buffer pixels
+    pub(crate) fn pixels(&self) -> (u32, u32, u32, u32) {
+        let (width, height) = gbuffer().size();
+        let x = ((self.x * width as f32).round() as u32).min(width);
+        let y = ((self.y * height as f32).round() as u32).min(height);
+        let w = ((self.width * width as f32).round() as u32).min(width - x);
+        let h = ((self.height * height as f32).round() as u32).min(height - y);
+        (x, y, w, h)
+    }
+
+    /// Restrict drawing in `rpass` to this viewport of the G-buffer
+    pub(crate) fn apply(&self, rpass: &mut RenderPass) {
+        let (x, y, w, h) = self.pixels();
+        rpass.set_viewport(x as f32, y as f32, w as f32, h as f32, 0.0, 1.0);
+        rpass.set_scissor_rect(x, y, w, h);
+    }
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self::FULL
+    }
+}
+
+/// A view that geometry can be rendered from
+///
+/// Each camera holds its matrices in its own buffer which is copied into the shared camera
+/// uniform before a view is drawn, so several cameras can be rendered in one frame.
+#[derive(Debug)]
+pub struct Camera {
+    buffer: Buffer,
+    jitter: bool,
+    state: Mutex<CameraState>,
+}
+
+#[derive(Debug, Default)]
+struct CameraState {
+    frame: u32,
+    prev_view_proj: Option<Mat4>,
+    /// unjittered projection and view matrix of the current frame
+    matrices: Option<(Mat4, Mat4)>,
+}
+
+impl Camera {
+    /// Create a camera
+    pub fn new() -> Self {
+        Self::with_jitter(false)
+    }
+
+    fn with_jitter(jitter: bool) -> Self {
+        Self {
+            buffer: device().create_buffer(&BufferDescriptor {
+                label: Some("Camera"),
+                size: CAMERA_SIZE,
+                usage: BufferUsages::COPY_SRC | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            jitter,
+            state: Mutex::new(CameraState::default()),
+        }
+    }
+
+    /// Set the projection and view matrix for the next frame
+    pub fn set(&self, proj: impl Into<ColumnMatrix4<f32>>, view: impl Into<ColumnMatrix4<f32>>) {
+        let proj = Mat4::from(proj.into());
+        let view = Mat4::from(view.into());
+        let mut state = self.state.lock().unwrap();
+
+        let view_proj = proj * view;
+        let prev_view_proj = state.prev_view_proj.unwrap_or(view_proj);
+        state.prev_view_proj = Some(view_proj);
+        state.matrices = Some((proj, view));
+        state.frame = state.frame.wrapping_add(1);
+
+        // offset the projection by a sub-pixel amount in clip space
+        let jitter = if self.jitter && settings().read().unwrap().antialiasing == Antialiasing::Taa
+        {
+            let (width, height) = gbuffer().size();
+            let phase = state.frame % JITTER_PHASES + 1;
+            let offset = Vec2::new(halton(phase, 2) - 0.5, halton(phase, 3) - 0.5);
+            Vec2::new(
+                offset.x * 2.0 / width as f32,
+                offset.y * 2.0 / height as f32,
+            )
+        } else {
+            Vec2::zero()
+        };
+        let mut jittered = Mat4::identity();
+        jittered.cols[3] = Vec4::new(jitter.x, jitter.y, 0.0, 1.0);
+        let jittered = jittered * proj;
+
+        let mut buffer = Vec::with_capacity(CAMERA_SIZE as usize);
+        buffer.extend_from_slice((jittered * view).as_byte_slice());
+        buffer.extend_from_slice(view.as_byte_slice());
+        buffer.extend_from_slice(prev_view_proj.as_byte_slice());
+        buffer.extend_from_slice(view_proj.as_byte_slice());
+        buffer.extend_from_slice(Vec4::new(jitter.x, jitter.y, 0.0, 0.0).as_byte_slice());
+        queue().write_buffer(&self.buffer, 0, &buffer);
+        stats::record_buffer_write();
+    }
+
+    /// Fraction of the view's height covered by a sphere in world space
+    ///
+    /// This is used to pick levels of detail. Spheres are treated as infinitely large before the
+    /// camera has been set.
+    pub fn screen_size(&self, center: impl Into<Vector3<f32>>, radius: f32) -> f32 {
+        let Some((proj, view)) = self.state.lock().unwrap().matrices else {
+            return f32::INFINITY;
+        };
+        let center = Vec3::from(center.into()).into_homogeneous_point();
+        // w is the distance along the view direction for perspective projections and 1 otherwise
+        let w = (proj * view * center).w;
+        radius * proj.cols[1].y / w.max(f32::EPSILON)
+    }
+
+    /// Make this the camera used by the commands recorded after this
+    pub(crate) fn bind(&self, encoder: &mut CommandEncoder) {
+        encoder.copy_buffer_to_buffer(&self.buffer, 0, buffer(), 0, CAMERA_SIZE);
+    }
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Fetch the camera uniform buffer
+pub fn buffer() -> &'static Buffer {
+    UNIFORM.get_or_init(|| {
+        device().create_buffer(&BufferDescriptor {
+            label: Some("Camera Uniform"),
+            size: CAMERA_SIZE,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    })
+}
+
+/// Fetch the camera the frame is drawn from
+///
+/// This is the only camera that is jittered for temporal antialiasing
+pub fn main() -> &'static Camera {
+    MAIN.get_or_init(|| Camera::with_jitter(true))
+}
+
+/// Set the projection and view matrix of the [`main`] camera for the next frame
+pub fn set(proj: impl Into<ColumnMatrix4<f32>>, view: impl Into<ColumnMatrix4<f32>>) {
+    main().set(proj, view);
+}
+
+/// Element of the halton sequence with the given base
+fn halton(mut index: u32, base: u32) -> f32 {
+    let mut f = 1.0;
+    let mut r = 0.0;
+    while index > 0 {
+        f /= base as f32;
+        r += f * (index % base) as f32;
+        index /= base;
+    }
+    r
+}
@@ -10,11 +10,11 @@ use std::sync::RwLock;
 
 use egui_wgpu::Renderer;
 use once_cell::sync::OnceCell;
-use snafu::{Backtrace, OptionExt, Snafu};
-use wgpu::{Device, Queue, Surface, SurfaceConfiguration};
+use snafu::{ensure, Backtrace, OptionExt, Snafu};
+use wgpu::{Device, PresentMode, Queue, Surface, SurfaceConfiguration};
 use winit::window::Window;
 
-use crate::pipeline::GBuffer;
+use crate::{antialias::Antialiasing, debug::DebugMode, pipeline::GBuffer, ssao::Ssao};
 
 static WGPU_DEVICE: OnceCell<Device> = OnceCell::new();
 static WGPU_SURFACE: OnceCell<Surface> = OnceCell::new();
@@ -22,6 +22,45 @@ static WGPU_QUEUE: OnceCell<Queue> = OnceCell::new();
 static G_BUFFER: OnceCell<GBuffer> = OnceCell::new();
 static WGPU_SURF_CONF: OnceCell<RwLock<SurfaceConfiguration>> = OnceCell::new();
 static EGUI_RENDER: OnceCell<RwLock<Renderer>> = OnceCell::new();
+static SETTINGS: OnceCell<RwLock<Settings>> = OnceCell::new();
+static PRESENT_MODES: OnceCell<Vec<PresentMode>> = OnceCell::new();
+
+/// Options used to configure the renderer
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    /// Number of samples per pixel used when drawing geometry
+    ///
+    /// Only 1 and 4 samples are supported. The G-buffer is resolved before lighting by averaging
+    /// colors and taking positions and normals from the closest sample, so multisampling smooths
+    /// the edges of geometry without shading surfaces that don't exist. Particles are drawn after
+    /// lighting into a multisampled copy of the lit image, which is resolved back afterwards.
+    /// This is best left at 1 for pixel art. This can only be set when initializing the renderer.
+    pub msaa_samples: u32,
+    /// The antialiasing filter applied to the lit image
+    pub antialiasing: Antialiasing,
+    /// Screen space ambient occlusion applied to ambient lights, disabled when `None`
+    pub ambient_occlusion: Option<Ssao>,
+    /// How frames are queued for display
+    ///
+    /// `Fifo` waits for vertical sync, `Mailbox` replaces queued frames without tearing and
+    /// `Immediate` presents frames as soon as they are finished. Modes that the surface does not
+    /// support fall back to `Fifo`.
+    pub present_mode: PresentMode,
+    /// View used to inspect the geometry of the scene, see [`debug`](crate::debug)
+    pub debug_mode: DebugMode,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            msaa_samples: 1,
+            antialiasing: Antialiasing::None,
+            ambient_occlusion: None,
+            present_mode: PresentMode::Fifo,
+            debug_mode: DebugMode::Off,
+        }
+    }
+}
 
 /// Fetches the GBuffer being used by this renderer
 pub fn gbuffer() -> &'static GBuffer {
@@ -48,6 +87,52 @@ pub fn surface_config() -> &'static RwLock<SurfaceConfiguration> {
     WGPU_SURF_CONF.get().expect("WGPU should be initialized")
 }
 
+/// Fetches the settings the renderer is using
+pub fn settings() -> &'static RwLock<Settings> {
+    SETTINGS.get().expect("WGPU should be initialized")
+}
+
+/// Change the antialiasing filter applied to each frame
+pub fn set_antialiasing(antialiasing: Antialiasing) {
+    settings().write().unwrap().antialiasing = antialiasing;
+}
+
+/// Change the ambient occlusion applied to ambient lights, `None` disables it
+pub fn set_ambient_occlusion(ssao: Option<Ssao>) {
+    settings().write().unwrap().ambient_occlusion = ssao;
+}
+
+/// Change the view used to inspect the geometry of the scene
+pub fn set_debug_mode(mode: DebugMode) {
+    settings().write().unwrap().debug_mode = mode;
+}
+
+/// Change how frames are queued for display
+///
+/// Returns the present mode that is actually used, which will be `Fifo` if the requested mode
+/// is not supported by the surface
+pub fn set_present_mode(mode: PresentMode) -> PresentMode {
+    let mode = supported_present_mode(mode);
+    settings().write().unwrap().present_mode = mode;
+
+    let mut config = surface_config().write().unwrap();
+    if config.present_mode != mode {
+        config.present_mode = mode;
+        surface().configure(device(), &config);
+    }
+    mode
+}
+
+fn supported_present_mode(mode: PresentMode) -> PresentMode {
+    let supported = PRESENT_MODES.get().expect("WGPU should be initialized");
+    match mode {
+        // these are always supported
+        PresentMode::AutoVsync | PresentMode::AutoNoVsync | PresentMode::Fifo => mode,
+        mode if supported.contains(&mode) => mode,
+        _ => PresentMode::Fifo,
+    }
+}
+
 pub(crate) fn egui_render() -> &'static RwLock<Renderer> {
     EGUI_RENDER.get().expect("WGPU should be initialized")
 }
@@ -62,16 +147,34 @@ pub fn resize(width: u32, height: u32) {
     surface().configure(device(), &config);
 }
 
-/// The renderer has already been initialized
+/// The renderer could not be initialized
 #[allow(missing_docs)]
 #[derive(Snafu, Debug)]
 pub enum InitError {
     #[snafu(display("An instance of the renderer has already been initialized"))]
     AlreadyInitialized { backtrace: Backtrace },
+    #[snafu(display("{samples} MSAA samples are not supported, use 1 or 4"))]
+    UnsupportedSampleCount { samples: u32, backtrace: Backtrace },
 }
 
 /// Initialize the renderer
 pub async fn init(window: &Window, gbuffer_size: (u32, u32)) -> Result<(), InitError> {
+    init_with_settings(window, gbuffer_size, Settings::default()).await
+}
+
+/// Initialize the renderer with the given settings
+pub async fn init_with_settings(
+    window: &Window,
+    gbuffer_size: (u32, u32),
+    mut settings: Settings,
+) -> Result<(), InitError> {
+    ensure!(
+        matches!(settings.msaa_samples, 1 | 4),
+        UnsupportedSampleCountSnafu {
+            samples: settings.msaa_samples
+        }
+    );
+
     // create stuff
     let size = window.inner_size();
     let instance = wgpu::Instance::default();
@@ -95,7 +198,15 @@ pub async fn init(window: &Window, gbuffer_size: (u32, u32)) -> Result<(), InitE
         .request_device(
             &wgpu::DeviceDescriptor {
                 label: None,
-                features: wgpu::Features::PUSH_CONSTANTS,
+                // timestamp queries are only used for profiling, static batches fall back to one
+                // indirect draw at a time without multi draw and wireframes fall back to a
+                // barycentric shader without line polygons, so they are optional
+                features: wgpu::Features::PUSH_CONSTANTS
+                    | (adapter.features()
+                        & (wgpu::Features::TIMESTAMP_QUERY
+                            | wgpu::Features::MULTI_DRAW_INDIRECT
+                            | wgpu::Features::INDIRECT_FIRST_INSTANCE
+                            | wgpu::Features::POLYGON_MODE_LINE)),
                 // Make sure we use the texture resolution limits from the adapter, so we can support images the size of the swapchain.
                 limits: wgpu::Limits {
                     max_push_constant_size: 128,
@@ -117,10 +228,17 @@ pub async fn init(window: &Window, gbuffer_size: (u32, u32)) -> Result<(), InitE
         .ok()
         .context(AlreadyInitializedSnafu)?;
 
+    let _ = PRESENT_MODES
+        .try_insert(surface.get_capabilities(&adapter).present_modes)
+        .ok()
+        .context(AlreadyInitializedSnafu)?;
+    settings.present_mode = supported_present_mode(settings.present_mode);
+
     // configure surface
-    let config = surface
+    let mut config = surface
         .get_default_config(&adapter, size.width, size.height)
         .unwrap();
+    config.present_mode = settings.present_mode;
     let swapchain_format = config.format;
 
     surface.configure(device, &config);
@@ -129,8 +247,14 @@ pub async fn init(window: &Window, gbuffer_size: (u32, u32)) -> Result<(), InitE
         .ok()
         .context(AlreadyInitializedSnafu)?;
 
+    let samples = settings.msaa_samples;
+    let _ = SETTINGS
+        .try_insert(RwLock::new(settings))
+        .ok()
+        .context(AlreadyInitializedSnafu)?;
+
     let _ = G_BUFFER
-        .try_insert(GBuffer::new(gbuffer_size.0, gbuffer_size.1))
+        .try_insert(GBuffer::new(gbuffer_size.0, gbuffer_size.1, samples))
         .ok()
         .context(AlreadyInitializedSnafu)?;
 
@@ -0,0 +1,494 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public License,
+ * v. 2.0. If a copy of the MPL was not distributed with this file, You can
+ * obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+//! Debug views for inspecting the topology of geometry
+//!
+//! While a [`DebugMode`] is active, geometry drawn with [`Frame::draw_geom`](crate::Frame) uses
+//! its [debug bundle](crate::Drawable::debug_bundle) instead of its material and the lights of
+//! the frame are skipped. Debug colors are written as emission, so they are shown as they are.
+//! [Static batches](crate::draw::StaticBatch) aren't drawn, other drawables without a debug
+//! bundle are drawn as usual but only show their emission.
+//!
+//! Debug bundles are only recorded the first time a mode is used, so they cost nothing until a
+//! debug view is opened.
+
+use std::{collections::HashMap, fmt, sync::RwLock};
+
+use egui::{ComboBox, Ui};
+use once_cell::{sync::Lazy, unsync};
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BlendComponent, BlendFactor, BlendOperation,
+    BlendState, Buffer, BufferUsages, CompareFunction, DepthStencilState, Features, PipelineLayout,
+    PolygonMode, PrimitiveState, PushConstantRange, RenderBundle, RenderBundleDescriptor,
+    RenderBundleEncoderDescriptor, RenderPipeline, ShaderStages, TextureFormat, VertexAttribute,
+    VertexBufferLayout, VertexFormat, VertexStepMode,
+};
+
+use crate::{
+    context::{device, gbuffer, set_debug_mode, settings},
+    load::CountedBuffer,
+    material::Material,
+    pipeline::{compact::MESH_LAYOUT, GBuffer},
+    shader, transform, Transform,
+};
+
+/// Debug pipelines by the positions they read, mode and if they fetch the vertices of indexed
+/// meshes
+type PipelineKey = (Positions, DebugMode, bool);
+
+static PIPELINES: Lazy<RwLock<HashMap<PipelineKey, &'static RenderPipeline>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// A renderer-wide view used to inspect geometry
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum DebugMode {
+    /// Draw geometry normally
+    #[default]
+    Off,
+    /// Draw the edges of every triangle
+    ///
+    /// Uses a line polygon mode when the device supports it, otherwise the edges are found from
+    /// barycentric coordinates, see [`DebugMode::needs_barycentrics`].
+    Wireframe,
+    /// Draw every layer of geometry additively without depth testing, brighter pixels are drawn
+    /// more often
+    Overdraw,
+    /// Shade triangles from blue to red as they get smaller on screen
+    ///
+    /// This needs barycentric coordinates, see [`DebugMode::needs_barycentrics`].
+    TriangleDensity,
+}
+
+impl DebugMode {
+    /// Every debug mode
+    pub const ALL: [DebugMode; 4] = [
+        DebugMode::Off,
+        DebugMode::Wireframe,
+        DebugMode::Overdraw,
+        DebugMode::TriangleDensity,
+    ];
+
+    /// Check if this mode finds edges from barycentric coordinates generated per vertex
+    ///
+    /// Indexed meshes share vertices between triangles, so in these modes they are drawn without
+    /// their index buffer by fetching their vertices from storage buffers. This needs both of the
+    /// mesh's buffers to be created with [`BufferUsages::STORAGE`], which meshes loaded by the
+    /// renderer are.
+    pub fn needs_barycentrics(self) -> bool {
+        match self {
+            DebugMode::Wireframe => !line_mode(),
+            DebugMode::TriangleDensity => true,
+            DebugMode::Off | DebugMode::Overdraw => false,
+        }
+    }
+
+    /// Index of a mode other than [`DebugMode::Off`]
+    pub(crate) fn index(self) -> usize {
+        self as usize - 1
+    }
+}
+
+impl fmt::Display for DebugMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DebugMode::Off => "Off",
+            DebugMode::Wireframe => "Wireframe",
+            DebugMode::Overdraw => "Overdraw",
+            DebugMode::TriangleDensity => "Triangle density",
+        };
+        f.write_str(name)
+    }
+}
+
+/// The debug mode the renderer is currently using
+pub fn mode() -> DebugMode {
+    settings().read().unwrap().debug_mode
+}
+
+/// Check if wireframes are drawn with a line polygon mode
+fn line_mode() -> bool {
+    device().features().contains(Features::POLYGON_MODE_LINE)
+}
+
+/// Check if `mesh` can be drawn in modes that need barycentric coordinates
+fn supports_barycentrics(mesh: &CountedBuffer) -> bool {
+    match mesh.index_buffer() {
+        Some(index) => {
+            mesh.usage().contains(BufferUsages::STORAGE)
+                && index.usage().contains(BufferUsages::STORAGE)
+        }
+        None => true,
+    }
+}
+
+/// Where debug views find the `vec3<f32>` position of each vertex
+///
+/// Debug pipelines only read positions, so every vertex type with the same layout of positions
+/// shares them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct Positions {
+    /// bytes between vertices
+    stride: u64,
+    /// bytes from the start of a vertex to its position
+    offset: u64,
+}
+
+impl Positions {
+    /// Find the position at location 0 of a vertex layout, if it is a `vec3<f32>`
+    pub(crate) fn of(layout: &VertexBufferLayout) -> Option<Self> {
+        layout
+            .attributes
+            .iter()
+            .find(|attribute| {
+                attribute.shader_location == 0 && attribute.format == VertexFormat::Float32x3
+            })
+            .map(|attribute| Self {
+                stride: layout.array_stride,
+                offset: attribute.offset,
+            })
+    }
+
+    /// Positions of vertices `stride` bytes apart which start with their position
+    pub(crate) fn packed(stride: u64) -> Self {
+        Self { stride, offset: 0 }
+    }
+
+    /// Words between vertices and from the start of a vertex to its position, pushed to
+    /// pipelines that fetch vertices from storage
+    pub(crate) fn words(self) -> [u32; 2] {
+        [self.stride as u32 / 4, self.offset as u32 / 4]
+    }
+}
+
+/// Positions of the vertices drawn with the material `M`
+///
+/// # Panics
+///
+/// Panics if the vertices of `M` have no `vec3<f32>` position at location 0
+pub(crate) fn positions<M: Material>() -> Positions {
+    Positions::of(&M::vertex_layout())
+        .expect("Debug views need a vec3<f32> position at location 0 of the vertex layout")
+}
+
+/// Defines picking `mode` in a shader that includes `debug_view.wgsl`
+///
+/// # Panics
+///
+/// Panics if `mode` is [`DebugMode::Off`]
+pub(crate) fn defines(mode: DebugMode) -> [&'static str; 2] {
+    let view = match mode {
+        DebugMode::Off => panic!("Geometry has no debug pipeline when debugging is off"),
+        DebugMode::Wireframe => "WIREFRAME",
+        DebugMode::Overdraw => "OVERDRAW",
+        DebugMode::TriangleDensity => "DENSITY",
+    };
+    let lines = mode == DebugMode::Wireframe && line_mode();
+    [view, if lines { "LINES" } else { "FILL" }]
+}
+
+/// Build a pipeline drawing geometry into the G-buffer in a debug mode
+///
+/// `source` should be preprocessed with the [`defines`] of `mode`.
+pub(crate) fn build_pipeline(
+    mode: DebugMode,
+    source: &str,
+    layout: &PipelineLayout,
+    buffers: &[VertexBufferLayout],
+) -> RenderPipeline {
+    let device = device();
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: None,
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    });
+
+    // overdraw adds up the color of every layer regardless of depth
+    let overdraw = mode == DebugMode::Overdraw;
+    let additive = BlendComponent {
+        src_factor: BlendFactor::One,
+        dst_factor: BlendFactor::One,
+        operation: BlendOperation::Add,
+    };
+    let mut targets = GBuffer::TARGETS.to_vec();
+    if overdraw {
+        // debug colors are written to the luminance attachment
+        if let Some(color) = &mut targets[3] {
+            color.blend = Some(BlendState {
+                color: additive,
+                alpha: additive,
+            });
+        }
+    }
+
+    let lines = mode == DebugMode::Wireframe && line_mode();
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Debug View"),
+        layout: Some(layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers,
+        },
+        primitive: PrimitiveState {
+            polygon_mode: if lines {
+                PolygonMode::Line
+            } else {
+                PolygonMode::Fill
+            },
+            ..Default::default()
+        },
+        depth_stencil: Some(DepthStencilState {
+            format: TextureFormat::Depth24Plus,
+            depth_write_enabled: !overdraw,
+            depth_compare: if overdraw {
+                CompareFunction::Always
+            } else {
+                CompareFunction::LessEqual
+            },
+            stencil: Default::default(),
+            bias: Default::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: gbuffer().sample_count(),
+            ..Default::default()
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &targets,
+        }),
+        multiview: None,
+    })
+}
+
+/// Fetch the pipeline drawing meshes of a material type in a debug mode
+///
+/// When `indexed` is set the pipeline has no vertex buffers and draws an indexed mesh by
+/// fetching its vertices and indices from storage buffers bound at group 1, see
+/// [`DebugMode::needs_barycentrics`].
+///
+/// # Panics
+///
+/// Panics if `mode` is [`DebugMode::Off`], or if the vertices of `M` have no `vec3<f32>` position
+/// at location 0
+pub fn pipeline<M: Material>(mode: DebugMode, indexed: bool) -> &'static RenderPipeline {
+    positions_pipeline(positions::<M>(), mode, indexed)
+}
+
+/// Fetch the pipeline drawing meshes with `positions` in a debug mode, see [`pipeline`]
+pub(crate) fn positions_pipeline(
+    positions: Positions,
+    mode: DebugMode,
+    indexed: bool,
+) -> &'static RenderPipeline {
+    let key = (positions, mode, indexed);
+    if let Some(pipe) = PIPELINES.read().unwrap().get(&key).copied() {
+        return pipe;
+    }
+
+    let [view, lines] = defines(mode);
+    let source = shader!(
+        "shaders/debug.wgsl",
+        view,
+        lines,
+        if indexed { "INDEXED" } else { "VERTICES" }
+    )
+    .unwrap();
+
+    let device = device();
+    let layout = if indexed {
+        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[transform::layout(), &MESH_LAYOUT],
+            push_constant_ranges: &[PushConstantRange {
+                stages: ShaderStages::VERTEX,
+                range: 0..8,
+            }],
+        })
+    } else {
+        device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[transform::layout()],
+            push_constant_ranges: &[],
+        })
+    };
+    let attributes = [VertexAttribute {
+        format: VertexFormat::Float32x3,
+        offset: positions.offset,
+        shader_location: 0,
+    }];
+    let buffers = [VertexBufferLayout {
+        array_stride: positions.stride,
+        step_mode: VertexStepMode::Vertex,
+        attributes: &attributes,
+    }];
+
+    let pipe = build_pipeline(mode, &source, &layout, if indexed { &[] } else { &buffers });
+    let pipe: &'static RenderPipeline = Box::leak(Box::new(pipe));
+    *PIPELINES.write().unwrap().entry(key).or_insert(pipe)
+}
+
+/// Bind the vertices and indices of a mesh for pipelines that fetch them from storage
+pub(crate) fn storage_group(vertices: &Buffer, indices: &Buffer) -> BindGroup {
+    device().create_bind_group(&BindGroupDescriptor {
+        label: Some("Debug View"),
+        layout: &MESH_LAYOUT,
+        entries: &[
+            BindGroupEntry {
+                binding: 0,
+                resource: vertices.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: indices.as_entire_binding(),
+            },
+        ],
+    })
+}
+
+/// Record a render bundle that draws `mesh` in a debug mode
+///
+/// `M` is the material the mesh is normally drawn with, its vertices need a `vec3<f32>` position
+/// at location 0.
+///
+/// # Panics
+///
+/// Panics if `mode` is [`DebugMode::Off`], if the vertices of `M` have no position or if `mode`
+/// needs barycentric coordinates and the mesh has an index buffer but its buffers can't be
+/// bound as storage buffers
+pub fn record<M: Material>(
+    mesh: &CountedBuffer,
+    transform: &Transform,
+    mode: DebugMode,
+) -> RenderBundle {
+    record_positions(positions::<M>(), mesh, transform, mode)
+}
+
+/// Record a render bundle that draws `mesh` with `positions` in a debug mode, see [`record`]
+fn record_positions(
+    positions: Positions,
+    mesh: &CountedBuffer,
+    transform: &Transform,
+    mode: DebugMode,
+) -> RenderBundle {
+    // indexed meshes fetch their own vertices so every triangle gets its own barycentrics
+    let storage = mesh
+        .index_buffer()
+        .filter(|_| mode.needs_barycentrics())
+        .map(|index| storage_group(mesh, index));
+
+    let mut bundle = device().create_render_bundle_encoder(&RenderBundleEncoderDescriptor {
+        label: None,
+        color_formats: GBuffer::color_formats(),
+        depth_stencil: GBuffer::depth_format(),
+        sample_count: gbuffer().sample_count(),
+        multiview: None,
+    });
+    bundle.set_pipeline(positions_pipeline(positions, mode, storage.is_some()));
+    bundle.set_bind_group(0, transform.bind_group(), &[]);
+    match &storage {
+        Some(storage) => {
+            bundle.set_bind_group(1, storage, &[]);
+            bundle.set_push_constants(
+                ShaderStages::VERTEX,
+                0,
+                bytemuck::cast_slice(&positions.words()),
+            );
+            bundle.draw(0..mesh.len(), 0..1);
+        }
+        None => mesh.draw(&mut bundle),
+    }
+    bundle.finish(&RenderBundleDescriptor {
+        label: Some("Debug View"),
+    })
+}
+
+/// A bundle for each debug mode, recorded the first time the mode is used
+#[derive(Default)]
+pub(crate) struct ModeBundles([unsync::OnceCell<RenderBundle>; 3]);
+
+impl ModeBundles {
+    /// Fetch the bundle of `mode`, recording it the first time
+    ///
+    /// Returns `None` when debugging is off.
+    pub(crate) fn get(
+        &self,
+        mode: DebugMode,
+        record: impl FnOnce() -> RenderBundle,
+    ) -> Option<&RenderBundle> {
+        if mode == DebugMode::Off {
+            return None;
+        }
+        Some(self.0[mode.index()].get_or_init(record))
+    }
+}
+
+/// Lazily recorded debug bundles of a mesh
+///
+/// Drawables hold one of these for each mesh they draw to implement
+/// [`Drawable::debug_bundle`](crate::Drawable).
+pub struct DebugBundles {
+    /// where the mesh's positions are, `None` if it can't be shown in debug views
+    positions: Option<Positions>,
+    bundles: ModeBundles,
+}
+
+impl DebugBundles {
+    /// Create the debug bundles of a mesh drawn with the material `M`
+    pub fn new<M: Material>() -> Self {
+        Self::with_positions(Positions::of(&M::vertex_layout()).filter(|_| M::debug_views()))
+    }
+
+    /// Create the debug bundles of a mesh with `positions`, or a mesh without debug views
+    pub(crate) fn with_positions(positions: Option<Positions>) -> Self {
+        Self {
+            positions,
+            bundles: ModeBundles::default(),
+        }
+    }
+
+    /// Fetch the bundle drawing `mesh` in `mode`, recording it if it is used for the first time
+    ///
+    /// `mesh` should be the same mesh every time. Returns `None` when debugging is off, if the
+    /// material doesn't support debug views or if `mode` needs barycentric coordinates and the
+    /// mesh's buffers can't be bound as storage buffers.
+    pub fn get(
+        &self,
+        mode: DebugMode,
+        mesh: &CountedBuffer,
+        transform: &Transform,
+    ) -> Option<&RenderBundle> {
+        let positions = self.positions?;
+        if mode.needs_barycentrics() && !supports_barycentrics(mesh) {
+            return None;
+        }
+        self.bundles
+            .get(mode, || record_positions(positions, mesh, transform, mode))
+    }
+}
+
+impl fmt::Debug for DebugBundles {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DebugBundles")
+            .field("supported", &self.positions.is_some())
+            .finish()
+    }
+}
+
+/// Display a selector for the renderer's debug mode in an EGUI ui
+pub fn display_debug_modes(ui: &mut Ui) {
+    let current = mode();
+    let mut selected = current;
+    let _ = ComboBox::from_label("Debug view")
+        .selected_text(selected.to_string())
+        .show_ui(ui, |ui| {
+            for mode in DebugMode::ALL {
+                let _ = ui.selectable_value(&mut selected, mode, mode.to_string());
+            }
+        });
+    if selected != current {
+        set_debug_mode(selected);
+    }
+}
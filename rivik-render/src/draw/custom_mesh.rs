@@ -0,0 +1,82 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public License,
+ * v. 2.0. If a copy of the MPL was not distributed with this file, You can
+ * obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+//! A mesh drawn with a user built pipeline
+use std::{rc::Rc, sync::Arc};
+
+use wgpu::{BindGroup, RenderBundle};
+
+use crate::{
+    debug::{DebugBundles, DebugMode},
+    load::CountedBuffer,
+    pipeline::GeomPipeline,
+    stats::DrawStats,
+    transform::Spatial,
+    Drawable, Transform,
+};
+
+/// A mesh renderable drawn with a [`GeomPipeline`]
+///
+/// The pipeline can be shared between any number of meshes, each with their own bind groups.
+/// Meshes are shown in [debug views](crate::debug) if location 0 of the pipeline's vertices is a
+/// `vec3<f32>` position.
+pub struct CustomMesh {
+    bundle: RenderBundle,
+    debug: DebugBundles,
+    transform: Transform,
+
+    //keep the following assets alive
+    mesh: Rc<Arc<CountedBuffer>>,
+    #[allow(dead_code)]
+    bind_groups: Vec<BindGroup>,
+}
+
+impl CustomMesh {
+    /// Create a new renderable drawing `mesh` with `pipeline`
+    ///
+    /// `bind_groups` are bound from group 1 onwards, matching the layouts the pipeline was built
+    /// with.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of bind groups doesn't match the pipeline
+    pub fn new(
+        pipeline: &GeomPipeline,
+        mesh: Rc<Arc<CountedBuffer>>,
+        bind_groups: Vec<BindGroup>,
+    ) -> Self {
+        let transform = Transform::default();
+        let groups: Vec<_> = bind_groups.iter().collect();
+        let bundle = pipeline.record(&mesh, &transform, &groups);
+        Self {
+            bundle,
+            debug: DebugBundles::with_positions(pipeline.positions()),
+            transform,
+            mesh,
+            bind_groups,
+        }
+    }
+}
+
+impl Drawable for CustomMesh {
+    fn bundle(&self) -> &RenderBundle {
+        &self.bundle
+    }
+
+    fn stats(&self) -> DrawStats {
+        self.mesh.stats()
+    }
+
+    fn debug_bundle(&self, mode: DebugMode) -> Option<&RenderBundle> {
+        self.debug.get(mode, &self.mesh, &self.transform)
+    }
+}
+
+impl Spatial for CustomMesh {
+    fn transform(&self) -> &Transform {
+        &self.transform
+    }
+}
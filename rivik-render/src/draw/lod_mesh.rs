@@ -0,0 +1,139 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public License,
+ * v. 2.0. If a copy of the MPL was not distributed with this file, You can
+ * obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+//! A mesh that swaps between levels of detail based on its size on screen
+use std::{rc::Rc, sync::Arc};
+
+use ultraviolet::Vec3;
+use wgpu::RenderBundle;
+
+use crate::{
+    camera,
+    debug::{DebugBundles, DebugMode},
+    load::{LoadedTexture, LodBuffers},
+    material::{self, StandardMaterial},
+    stats::DrawStats,
+    transform::Spatial,
+    Drawable, Transform,
+};
+
+/// Mesh renderable with several levels of detail
+///
+/// Each frame the level is picked in [`Drawable::prepare`] from how much of the
+/// [main camera's](camera::main) view the mesh's bounding sphere covers. The most detailed level
+/// is used while the mesh covers at least [`screen_size`](LodMesh::screen_size) of the view's
+/// height and each following level covers half the size of the previous one.
+///
+/// Only the main camera is used, so every other view of the frame, such as split screen views
+/// and [render targets](crate::target::RenderTarget), draws the level picked for it.
+pub struct LodMesh {
+    bundles: Vec<RenderBundle>,
+    debug: Vec<DebugBundles>,
+    selected: usize,
+    transform: Transform,
+    screen_size: f32,
+
+    //keep the following assets alive
+    lods: Rc<Arc<LodBuffers>>,
+    #[allow(dead_code)]
+    material: StandardMaterial,
+}
+
+impl Drawable for LodMesh {
+    fn bundle(&self) -> &RenderBundle {
+        &self.bundles[self.selected]
+    }
+
+    fn stats(&self) -> DrawStats {
+        self.lods.levels[self.selected].stats()
+    }
+
+    fn prepare(&mut self) {
+        let model = self.transform.model();
+        let center = model.transform_point3(Vec3::from(self.lods.center));
+
+        // scale the bounds by the largest axis of the model matrix
+        let scale = (0..3)
+            .map(|i| model.cols[i].xyz().mag())
+            .fold(0.0, f32::max);
+        let size = camera::main().screen_size(center, self.lods.radius * scale);
+
+        let mut threshold = self.screen_size;
+        self.selected = 0;
+        while self.selected + 1 < self.bundles.len() && size < threshold {
+            self.selected += 1;
+            threshold /= 2.0;
+        }
+    }
+
+    fn debug_bundle(&self, mode: DebugMode) -> Option<&RenderBundle> {
+        let level = &self.lods.levels[self.selected];
+        self.debug[self.selected].get(mode, level, &self.transform)
+    }
+}
+
+impl Spatial for LodMesh {
+    fn transform(&self) -> &Transform {
+        &self.transform
+    }
+}
+
+impl LodMesh {
+    /// Create a new mesh renderable from levels of detail loaded with
+    /// [`GpuLodMesh`](crate::load::GpuLodMesh)
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are no levels
+    pub fn new(lods: Rc<Arc<LodBuffers>>, tex: Rc<Arc<LoadedTexture>>) -> Self {
+        Self::with_material(lods, StandardMaterial::new(tex))
+    }
+
+    /// Create a new mesh renderable drawn with `material`
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are no levels
+    pub fn with_material(lods: Rc<Arc<LodBuffers>>, material: StandardMaterial) -> Self {
+        assert!(
+            !lods.levels.is_empty(),
+            "LOD meshes need at least one level"
+        );
+        let transform = Transform::default();
+        let bundles = lods
+            .levels
+            .iter()
+            .map(|level| material::record(level, &material, &transform))
+            .collect();
+        let debug = lods
+            .levels
+            .iter()
+            .map(|_| DebugBundles::new::<StandardMaterial>())
+            .collect();
+        Self {
+            bundles,
+            debug,
+            selected: 0,
+            transform,
+            screen_size: 0.5,
+            lods,
+            material,
+        }
+    }
+
+    /// Set the fraction of the view's height below which the mesh drops to its second level
+    pub fn screen_size(self, screen_size: f32) -> Self {
+        Self {
+            screen_size,
+            ..self
+        }
+    }
+
+    /// The level currently drawn, 0 is the most detailed
+    pub fn level(&self) -> usize {
+        self.selected
+    }
+}
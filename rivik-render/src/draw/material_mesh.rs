@@ -0,0 +1,70 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public License,
+ * v. 2.0. If a copy of the MPL was not distributed with this file, You can
+ * obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+//! A mesh drawn with any [`Material`]
+use std::{rc::Rc, sync::Arc};
+
+use wgpu::RenderBundle;
+
+use crate::{
+    debug::{DebugBundles, DebugMode},
+    load::CountedBuffer,
+    material::{self, Material},
+    stats::DrawStats,
+    transform::Spatial,
+    Drawable, Transform,
+};
+
+/// A mesh renderable that is shaded with a user provided material
+pub struct MaterialMesh<M: Material> {
+    bundle: RenderBundle,
+    debug: DebugBundles,
+    transform: Transform,
+    material: M,
+
+    //keep the mesh alive
+    mesh: Rc<Arc<CountedBuffer>>,
+}
+
+impl<M: Material> MaterialMesh<M> {
+    /// Create a new renderable drawing `mesh` with `material`
+    pub fn new(mesh: Rc<Arc<CountedBuffer>>, material: M) -> Self {
+        let transform = Transform::default();
+        let bundle = material::record(&mesh, &material, &transform);
+        Self {
+            bundle,
+            debug: DebugBundles::new::<M>(),
+            transform,
+            material,
+            mesh,
+        }
+    }
+
+    /// Get the material this mesh is drawn with
+    pub fn material(&self) -> &M {
+        &self.material
+    }
+}
+
+impl<M: Material> Drawable for MaterialMesh<M> {
+    fn bundle(&self) -> &RenderBundle {
+        &self.bundle
+    }
+
+    fn stats(&self) -> DrawStats {
+        self.mesh.stats()
+    }
+
+    fn debug_bundle(&self, mode: DebugMode) -> Option<&RenderBundle> {
+        self.debug.get(mode, &self.mesh, &self.transform)
+    }
+}
+
+impl<M: Material> Spatial for MaterialMesh<M> {
+    fn transform(&self) -> &Transform {
+        &self.transform
+    }
+}
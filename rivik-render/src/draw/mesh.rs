@@ -5,38 +5,61 @@
  */
 
 //! Utilities for rendering a static mesh
-use std::{borrow::Borrow, rc::Rc, sync::Arc};
+use std::{rc::Rc, sync::Arc};
 
-use assets::formats::{self, mesh::Vert};
-use wgpu::{
-    RenderBundle, RenderBundleDescriptor, RenderBundleEncoderDescriptor, Texture, TextureView,
+use assets::formats::{
+    self,
+    mesh::{IndexedMesh, Vert},
 };
+use wgpu::RenderBundle;
 
 use crate::{
-    context::device,
-    load::CountedBuffer,
-    pipeline::{
-        mesh::{self, MeshVertex},
-        simple, GBuffer,
-    },
-    transform::{self, Spatial},
-    Transform,
+    debug::{DebugBundles, DebugMode},
+    dynamic::DynamicMesh,
+    load::{CountedBuffer, LoadedTexture},
+    material::{self, StandardMaterial},
+    pipeline::mesh::MeshVertex,
+    stats::DrawStats,
+    transform::Spatial,
+    Drawable, Transform,
 };
 
 /// Basic mesh renderable
 pub struct Mesh {
     bundle: RenderBundle,
+    debug: DebugBundles,
     transform: Transform,
 
     //keep the following assets alive
     mesh: Rc<Arc<CountedBuffer>>,
-    tex: Rc<Arc<(Texture, TextureView)>>,
+    material: StandardMaterial,
+    /// the dynamic mesh this is drawn from and the version the bundle was recorded at
+    dynamic: Option<(Rc<DynamicMesh>, u64)>,
 }
 
-impl Borrow<RenderBundle> for Mesh {
-    fn borrow(&self) -> &RenderBundle {
+impl Drawable for Mesh {
+    fn bundle(&self) -> &RenderBundle {
         &self.bundle
     }
+
+    fn stats(&self) -> DrawStats {
+        self.mesh.stats()
+    }
+
+    fn prepare(&mut self) {
+        if let Some((dynamic, version)) = &mut self.dynamic {
+            if dynamic.version() != *version {
+                *version = dynamic.version();
+                self.mesh = dynamic.buffer();
+                self.bundle = material::record(&self.mesh, &self.material, &self.transform);
+                self.debug = DebugBundles::new::<StandardMaterial>();
+            }
+        }
+    }
+
+    fn debug_bundle(&self, mode: DebugMode) -> Option<&RenderBundle> {
+        self.debug.get(mode, &self.mesh, &self.transform)
+    }
 }
 
 impl Spatial for Mesh {
@@ -49,79 +72,69 @@ impl Spatial for Mesh {
 /// needs.
 impl Mesh {
     /// Create a new mesh renderable
-    pub fn new(mesh: Rc<Arc<CountedBuffer>>, tex: Rc<Arc<(Texture, TextureView)>>) -> Self {
-        let device = device();
-        let mut bundle = device.create_render_bundle_encoder(&RenderBundleEncoderDescriptor {
-            label: None,
-            color_formats: GBuffer::color_formats(),
-            depth_stencil: GBuffer::depth_format(),
-            sample_count: 1,
-            multiview: None,
-        });
+    pub fn new(mesh: Rc<Arc<CountedBuffer>>, tex: Rc<Arc<LoadedTexture>>) -> Self {
+        Self::with_material(mesh, StandardMaterial::new(tex))
+    }
+
+    /// Create a new mesh renderable with an emissive texture
+    ///
+    /// See [`StandardMaterial::emissive`]
+    pub fn emissive(
+        mesh: Rc<Arc<CountedBuffer>>,
+        tex: Rc<Arc<LoadedTexture>>,
+        emissive: Rc<Arc<LoadedTexture>>,
+        intensity: f32,
+    ) -> Self {
+        Self::with_material(
+            mesh,
+            StandardMaterial::new(tex).emissive(emissive, intensity),
+        )
+    }
 
+    /// Create a new mesh renderable drawn with `material`
+    pub fn with_material(mesh: Rc<Arc<CountedBuffer>>, material: StandardMaterial) -> Self {
         let transform = Transform::default();
-        let transform_binding = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: transform::layout(),
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: transform.buffer().as_entire_binding(),
-            }],
-            label: None,
-        });
-
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::Repeat,
-            address_mode_v: wgpu::AddressMode::Repeat,
-            ..Default::default()
-        });
-        let texture_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: None,
-            layout: &*simple::TEX_LAYOUT,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::Sampler(&sampler),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::TextureView(&tex.1),
-                },
-            ],
-        });
-
-        // start recording render commands
-        bundle.set_pipeline(&*mesh::PIPELINE);
-        bundle.set_bind_group(0, &texture_group, &[]);
-        bundle.set_bind_group(1, &transform_binding, &[]);
-        bundle.set_vertex_buffer(0, mesh.slice(..));
-        bundle.draw(0..mesh.len(), 0..1);
-        let bundle = bundle.finish(&RenderBundleDescriptor { label: None });
+        let bundle = material::record(&mesh, &material, &transform);
         Self {
             bundle,
+            debug: DebugBundles::new::<StandardMaterial>(),
             transform,
             mesh,
-            tex,
+            material,
+            dynamic: None,
+        }
+    }
+
+    /// Create a mesh renderable from a [`DynamicMesh`] of [`MeshVertex`]s
+    ///
+    /// Changes to the mesh are picked up in [`Drawable::prepare`]
+    pub fn dynamic(mesh: Rc<DynamicMesh>, material: StandardMaterial) -> Self {
+        let version = mesh.version();
+        Self {
+            dynamic: Some((mesh.clone(), version)),
+            ..Self::with_material(mesh.buffer(), material)
         }
     }
 }
 
+fn gen_vert(v: Vert) -> MeshVertex {
+    let norm = v.norm.unwrap_or(mint::Point3 {
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+    });
+    let uv = v.uv.unwrap_or(mint::Point2 { x: 0.0, y: 0.0 });
+    MeshVertex {
+        pos: [v.pos.x, v.pos.y, v.pos.z],
+        norm: [norm.x, norm.y, norm.z],
+        uv: [uv.x, uv.y],
+    }
+}
+
 /// Generate a vertex buffer for a given mesh
 pub fn vertex_buffer(mesh: &formats::mesh::Mesh<f32>) -> (Vec<u8>, usize) {
     let mut verts: Vec<MeshVertex> = vec![];
     for (a, b, c) in mesh.faces() {
-        let gen_vert = |v: Vert| {
-            let norm = v.norm.unwrap_or(mint::Point3 {
-                x: 0.0,
-                y: 0.0,
-                z: 0.0,
-            });
-            let uv = v.uv.unwrap_or(mint::Point2 { x: 0.0, y: 0.0 });
-            MeshVertex {
-                pos: [v.pos.x, v.pos.y, v.pos.z],
-                norm: [norm.x, norm.y, norm.z],
-                uv: [uv.x, uv.y],
-            }
-        };
         verts.push(gen_vert(a));
         verts.push(gen_vert(b));
         verts.push(gen_vert(c));
@@ -134,3 +147,11 @@ pub fn vertex_buffer(mesh: &formats::mesh::Mesh<f32>) -> (Vec<u8>, usize) {
     }
     (buffer, verts.len())
 }
+
+/// Generate a vertex and index buffer for a given indexed mesh
+///
+/// Use with [`GpuIndexedMesh`](crate::load::GpuIndexedMesh)
+pub fn indexed_vertex_buffer(mesh: &IndexedMesh<f32>) -> (Vec<u8>, Vec<u32>) {
+    let verts: Vec<MeshVertex> = mesh.verts().map(gen_vert).collect();
+    (bytemuck::cast_slice(&verts).to_vec(), mesh.indices.clone())
+}
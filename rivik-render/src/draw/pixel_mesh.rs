@@ -5,20 +5,22 @@
  */
 
 //! Utilities for rendering a pixelated mesh
-use std::{borrow::Borrow, rc::Rc, sync::Arc};
+use std::{mem::size_of, rc::Rc, sync::Arc};
 
-use assets::formats::mesh::{Mesh, Vert};
-use wgpu::{
-    AddressMode, BindingResource, FilterMode, RenderBundle, RenderBundleDescriptor,
-    RenderBundleEncoderDescriptor, Texture, TextureView,
-};
+use assets::formats::mesh::{IndexedMesh, Mesh, Vert};
+use ultraviolet::{Vec2, Vec3};
+use wgpu::{RenderBundle, RenderBundleDescriptor, RenderBundleEncoderDescriptor};
 
 use crate::{
-    context::device,
-    load::CountedBuffer,
-    pipeline::{simple, GBuffer, Vertex3D},
-    transform::{self, Spatial},
-    Transform,
+    context::{device, gbuffer},
+    debug::{DebugBundles, DebugMode, Positions},
+    dynamic::DynamicMesh,
+    load::{CountedBuffer, LoadedTexture},
+    material::{self, Material, PixelMaterial},
+    pipeline::{compact, CompactVertex, GBuffer, Vertex3D},
+    stats::DrawStats,
+    transform::Spatial,
+    Drawable, Transform,
 };
 
 /// I need to create a wrapper type around RenderBundle that also holds references to it's GPU assets
@@ -26,7 +28,13 @@ use crate::{
 /// TODO: Deep dive on when things are freed and how to minimally ensure asset lifetimes
 pub struct PixelMesh {
     bundle: RenderBundle,
+    /// the mesh drawn in debug views
+    debug: (Rc<Arc<CountedBuffer>>, DebugBundles),
     transform: Transform,
+    stats: DrawStats,
+    /// the dynamic mesh this is drawn from, the version the bundle was recorded at and the
+    /// material needed to record it again
+    dynamic: Option<(Rc<DynamicMesh>, u64, PixelMaterial)>,
 }
 
 impl PixelMesh {
@@ -34,68 +42,161 @@ impl PixelMesh {
     pub fn new(
         mesh: Rc<Arc<CountedBuffer>>,
         transform: Transform,
-        tex: Rc<Arc<(Texture, TextureView)>>,
+        tex: Rc<Arc<LoadedTexture>>,
+    ) -> Self {
+        Self::with_material(mesh, transform, PixelMaterial::new(tex))
+    }
+
+    /// Create a new renderable with an emissive texture
+    ///
+    /// See [`PixelMaterial::emissive`]
+    pub fn emissive(
+        mesh: Rc<Arc<CountedBuffer>>,
+        transform: Transform,
+        tex: Rc<Arc<LoadedTexture>>,
+        emissive: Rc<Arc<LoadedTexture>>,
+        intensity: f32,
+    ) -> Self {
+        Self::with_material(
+            mesh,
+            transform,
+            PixelMaterial::new(tex).emissive(emissive, intensity),
+        )
+    }
+
+    /// Create a new renderable drawn with `material`
+    pub fn with_material(
+        mesh: Rc<Arc<CountedBuffer>>,
+        transform: Transform,
+        material: PixelMaterial,
     ) -> Self {
         // create render bundle for this asset
-        let device = device();
-        let mut bundle = device.create_render_bundle_encoder(&RenderBundleEncoderDescriptor {
-            label: None,
-            color_formats: GBuffer::color_formats(),
-            depth_stencil: GBuffer::depth_format(),
-            sample_count: 1,
-            multiview: None,
-        });
+        let bundle = material::record(&mesh, &material, &transform);
+        Self {
+            bundle,
+            debug: (mesh.clone(), DebugBundles::new::<PixelMaterial>()),
+            transform,
+            stats: mesh.stats(),
+            dynamic: None,
+        }
+    }
 
-        // create bind group for uniform buffer
-        let uniform = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: transform::layout(),
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: transform.buffer().as_entire_binding(),
-            }],
-            label: None,
-        });
+    /// Create a new renderable from a [`DynamicMesh`] of [`Vertex3D`]s
+    ///
+    /// Changes to the mesh are picked up in [`Drawable::prepare`]
+    pub fn dynamic(mesh: Rc<DynamicMesh>, transform: Transform, material: PixelMaterial) -> Self {
+        let buffer = mesh.buffer();
+        let version = mesh.version();
+        Self {
+            bundle: material::record(&buffer, &material, &transform),
+            debug: (buffer.clone(), DebugBundles::new::<PixelMaterial>()),
+            transform,
+            stats: buffer.stats(),
+            dynamic: Some((mesh, version, material)),
+        }
+    }
 
-        // create texture bind group
-        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            mag_filter: FilterMode::Nearest,
-            min_filter: FilterMode::Nearest,
-            mipmap_filter: FilterMode::Nearest,
-            address_mode_u: AddressMode::Repeat,
-            address_mode_v: AddressMode::Repeat,
-            ..Default::default()
-        });
+    /// Create a new renderable from a mesh of [`CompactVertex`]s
+    ///
+    /// The mesh should be loaded with
+    /// [`GpuIndexedMesh`](crate::load::GpuIndexedMesh) and [`compact_vertex_buffer`]. This looks
+    /// identical to a mesh created with [`PixelMesh::new`] but uses far less memory.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the mesh does not have an index buffer
+    pub fn compact(
+        mesh: Rc<Arc<CountedBuffer>>,
+        transform: Transform,
+        tex: Rc<Arc<LoadedTexture>>,
+    ) -> Self {
+        Self::compact_with_material(mesh, transform, PixelMaterial::new(tex))
+    }
 
-        let texture_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: None,
-            layout: &*simple::TEX_LAYOUT,
+    /// Create a new renderable from a mesh of [`CompactVertex`]s drawn with `material`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the mesh does not have an index buffer
+    pub fn compact_with_material(
+        mesh: Rc<Arc<CountedBuffer>>,
+        transform: Transform,
+        material: PixelMaterial,
+    ) -> Self {
+        let device = device();
+        let index = mesh
+            .index_buffer()
+            .expect("Compact pixel meshes must have an index buffer");
+
+        let mesh_binding = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Compact Mesh"),
+            layout: &compact::MESH_LAYOUT,
             entries: &[
                 wgpu::BindGroupEntry {
                     binding: 0,
-                    resource: BindingResource::Sampler(&sampler),
+                    resource: mesh.as_entire_binding(),
                 },
                 wgpu::BindGroupEntry {
                     binding: 1,
-                    resource: BindingResource::TextureView(&tex.1),
+                    resource: index.as_entire_binding(),
                 },
             ],
         });
+        let material_binding = material.bind_group(material::layout::<PixelMaterial>());
 
-        // start recording render commands
-        bundle.set_pipeline(&*simple::PIPELINE);
-        bundle.set_bind_group(0, &texture_group, &[]);
-        bundle.set_bind_group(1, &uniform, &[]);
-        bundle.set_vertex_buffer(0, mesh.slice(..));
+        let mut bundle = device.create_render_bundle_encoder(&RenderBundleEncoderDescriptor {
+            label: None,
+            color_formats: GBuffer::color_formats(),
+            depth_stencil: GBuffer::depth_format(),
+            sample_count: gbuffer().sample_count(),
+            multiview: None,
+        });
+        bundle.set_pipeline(&compact::PIPELINE);
+        bundle.set_bind_group(0, material_binding, &[]);
+        bundle.set_bind_group(1, transform.bind_group(), &[]);
+        bundle.set_bind_group(2, &mesh_binding, &[]);
         bundle.draw(0..mesh.len(), 0..1);
-        let bundle = bundle.finish(&RenderBundleDescriptor { label: None });
-        Self { bundle, transform }
+        let bundle = bundle.finish(&RenderBundleDescriptor {
+            label: Some("Compact Pixel Mesh"),
+        });
+
+        // compact vertices start with their position
+        let positions = Positions::packed(size_of::<CompactVertex>() as u64);
+        Self {
+            bundle,
+            debug: (mesh.clone(), DebugBundles::with_positions(Some(positions))),
+            transform,
+            stats: mesh.stats(),
+            dynamic: None,
+        }
     }
 }
 
-impl Borrow<RenderBundle> for PixelMesh {
-    fn borrow(&self) -> &RenderBundle {
+impl Drawable for PixelMesh {
+    fn bundle(&self) -> &RenderBundle {
         &self.bundle
     }
+
+    fn stats(&self) -> DrawStats {
+        self.stats
+    }
+
+    fn prepare(&mut self) {
+        if let Some((dynamic, version, material)) = &mut self.dynamic {
+            if dynamic.version() != *version {
+                *version = dynamic.version();
+                let buffer = dynamic.buffer();
+                self.bundle = material::record(&buffer, material, &self.transform);
+                self.stats = buffer.stats();
+                self.debug = (buffer, DebugBundles::new::<PixelMaterial>());
+            }
+        }
+    }
+
+    fn debug_bundle(&self, mode: DebugMode) -> Option<&RenderBundle> {
+        let (mesh, debug) = &self.debug;
+        debug.get(mode, mesh, &self.transform)
+    }
 }
 
 impl Spatial for PixelMesh {
@@ -140,3 +241,21 @@ pub fn vertex_buffer(mesh: &Mesh<f32>) -> (Vec<u8>, usize) {
     }
     (buffer, verts.len())
 }
+
+/// Generate a compact vertex buffer and index buffer for a given mesh
+///
+/// Use with [`PixelMesh::compact`]
+pub fn compact_vertex_buffer(mesh: &IndexedMesh<f32>) -> (Vec<u8>, Vec<u32>) {
+    let verts: Vec<CompactVertex> = mesh
+        .verts()
+        .map(|v| {
+            let norm = v
+                .norm
+                .map(Vec3::from)
+                .unwrap_or_else(|| Vec3::new(1.0, 1.0, 1.0).normalized());
+            let uv = v.uv.map(Vec2::from).unwrap_or_default();
+            CompactVertex::new(v.pos.into(), norm, uv)
+        })
+        .collect();
+    (bytemuck::cast_slice(&verts).to_vec(), mesh.indices.clone())
+}
@@ -0,0 +1,213 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public License,
+ * v. 2.0. If a copy of the MPL was not distributed with this file, You can
+ * obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+//! Textured quads that face the camera
+use std::{cell::Cell, rc::Rc, sync::Arc};
+
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroup, Buffer, BufferUsages, RenderBundle, RenderBundleDescriptor,
+    RenderBundleEncoderDescriptor, RenderPipeline,
+};
+
+use crate::{
+    context::{device, gbuffer, queue},
+    debug::{DebugMode, ModeBundles},
+    load::LoadedTexture,
+    material::{self, Material, PixelMaterial},
+    pipeline::{sprite, GBuffer},
+    stats::{self, DrawStats},
+    transform::Spatial,
+    Drawable, Transform,
+};
+
+/// How a sprite turns to face the camera
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Facing {
+    /// Always face the camera
+    #[default]
+    Camera,
+    /// Only turn around the Y axis so the sprite stays upright
+    AxisY,
+}
+
+/// A textured quad drawn into the G-buffer that faces the camera
+///
+/// The texture can be a sprite sheet of equally sized frames, the frame drawn is picked with
+/// [`Sprite::set_frame`]. Texels with an alpha below one half are cut out.
+pub struct Sprite {
+    bundle: RenderBundle,
+    debug: ModeBundles,
+    transform: Transform,
+    material: PixelMaterial,
+    uniform: Buffer,
+    /// the uniform bound for the sprite pipelines
+    binding: BindGroup,
+    sheet: (u32, u32),
+    frame: Cell<u32>,
+    size: Cell<(f32, f32)>,
+    facing: Cell<Facing>,
+}
+
+impl Sprite {
+    /// Create a sprite showing the whole texture
+    pub fn new(tex: Rc<Arc<LoadedTexture>>, transform: Transform) -> Self {
+        Self::sheet(tex, transform, 1, 1)
+    }
+
+    /// Create a sprite from a sheet of `columns` by `rows` frames
+    ///
+    /// Frames are numbered left to right then top to bottom. The sprite is one unit tall and as
+    /// wide as a frame's aspect ratio.
+    pub fn sheet(
+        tex: Rc<Arc<LoadedTexture>>,
+        transform: Transform,
+        columns: u32,
+        rows: u32,
+    ) -> Self {
+        let device = device();
+        let sheet = (columns.max(1), rows.max(1));
+
+        let tex_size = tex.0.size();
+        let frame_width = tex_size.width as f32 / sheet.0 as f32;
+        let frame_height = tex_size.height as f32 / sheet.1 as f32;
+        let size = (frame_width / frame_height, 1.0);
+
+        let uniform = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Sprite"),
+            contents: bytemuck::cast_slice(&uniform_data(sheet, 0, size, Facing::Camera)),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let binding = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Sprite"),
+            layout: &sprite::SPRITE_LAYOUT,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform.as_entire_binding(),
+            }],
+        });
+        let material = PixelMaterial::new(tex);
+        let bundle = record(&sprite::PIPELINE, &material, &transform, &binding);
+
+        Self {
+            bundle,
+            debug: ModeBundles::default(),
+            transform,
+            material,
+            uniform,
+            binding,
+            sheet,
+            frame: Cell::new(0),
+            size: Cell::new(size),
+            facing: Cell::new(Facing::Camera),
+        }
+    }
+
+    /// Number of frames in this sprite's sheet
+    pub fn frames(&self) -> u32 {
+        self.sheet.0 * self.sheet.1
+    }
+
+    /// Show a frame of the sprite sheet, wrapping around past the last frame
+    pub fn set_frame(&self, frame: u32) {
+        self.frame.set(frame % self.frames());
+        self.write();
+    }
+
+    /// Set the width and height of the quad before the transform's scale
+    pub fn set_size(&self, width: f32, height: f32) {
+        self.size.set((width, height));
+        self.write();
+    }
+
+    /// Set how the sprite turns to face the camera
+    pub fn set_facing(&self, facing: Facing) {
+        self.facing.set(facing);
+        self.write();
+    }
+
+    fn write(&self) {
+        let data = uniform_data(
+            self.sheet,
+            self.frame.get(),
+            self.size.get(),
+            self.facing.get(),
+        );
+        queue().write_buffer(&self.uniform, 0, bytemuck::cast_slice(&data));
+        stats::record_buffer_write();
+    }
+}
+
+/// Record a render bundle that draws a sprite with `pipeline`
+fn record(
+    pipeline: &RenderPipeline,
+    material: &PixelMaterial,
+    transform: &Transform,
+    binding: &BindGroup,
+) -> RenderBundle {
+    let mut bundle = device().create_render_bundle_encoder(&RenderBundleEncoderDescriptor {
+        label: None,
+        color_formats: GBuffer::color_formats(),
+        depth_stencil: GBuffer::depth_format(),
+        sample_count: gbuffer().sample_count(),
+        multiview: None,
+    });
+    bundle.set_pipeline(pipeline);
+    bundle.set_bind_group(
+        0,
+        material.bind_group(material::layout::<PixelMaterial>()),
+        &[],
+    );
+    bundle.set_bind_group(1, transform.bind_group(), &[]);
+    bundle.set_bind_group(2, binding, &[]);
+    bundle.draw(0..6, 0..1);
+    bundle.finish(&RenderBundleDescriptor {
+        label: Some("Sprite"),
+    })
+}
+
+fn uniform_data(
+    (columns, rows): (u32, u32),
+    frame: u32,
+    (width, height): (f32, f32),
+    facing: Facing,
+) -> [f32; 8] {
+    let frame_size = (1.0 / columns as f32, 1.0 / rows as f32);
+    let x = (frame % columns) as f32 * frame_size.0;
+    let y = (frame / columns) as f32 * frame_size.1;
+    let axis = match facing {
+        Facing::Camera => 0.0,
+        Facing::AxisY => 1.0,
+    };
+    [x, y, frame_size.0, frame_size.1, width, height, axis, 0.0]
+}
+
+impl Drawable for Sprite {
+    fn bundle(&self) -> &RenderBundle {
+        &self.bundle
+    }
+
+    fn stats(&self) -> DrawStats {
+        DrawStats::new(1, 6)
+    }
+
+    fn debug_bundle(&self, mode: DebugMode) -> Option<&RenderBundle> {
+        self.debug.get(mode, || {
+            record(
+                sprite::debug_pipeline(mode),
+                &self.material,
+                &self.transform,
+                &self.binding,
+            )
+        })
+    }
+}
+
+impl Spatial for Sprite {
+    fn transform(&self) -> &Transform {
+        &self.transform
+    }
+}
@@ -0,0 +1,373 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public License,
+ * v. 2.0. If a copy of the MPL was not distributed with this file, You can
+ * obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+//! Many static meshes drawn with GPU culled indirect draws
+//!
+//! Every mesh in a [`StaticBatch`] shares one vertex and one index buffer and every draw stores
+//! its transform in a storage buffer. Before the geometry pass of each view a compute pass culls
+//! the draws against the camera and writes their indirect arguments, then the whole batch is
+//! drawn with a single multi draw. Devices without multi draw support issue one indirect draw
+//! per object instead, which still avoids recording a bundle per object.
+
+use std::{cell::RefCell, fmt, mem::size_of};
+
+use assets::formats::mesh::IndexedMesh;
+use mint::ColumnMatrix4;
+use ultraviolet::Mat4;
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroup, BindGroupDescriptor, BindGroupEntry, Buffer, BufferUsages, CommandEncoder,
+    IndexFormat, RenderPass, ShaderStages,
+};
+
+use crate::{
+    camera,
+    context::{device, queue},
+    draw::mesh::indexed_vertex_buffer,
+    material::{self, Material, StandardMaterial},
+    pipeline::{
+        indirect::{self, ARGS_SIZE, DRAW_SIZE},
+        mesh::MeshVertex,
+    },
+    stats::{self, DrawStats},
+};
+
+/// Number of draws culled by each compute workgroup
+const WORKGROUP_SIZE: u32 = 64;
+
+/// A mesh added to a [`StaticBatchBuilder`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BatchMesh(usize);
+
+/// A draw of a mesh in a [`StaticBatch`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BatchDraw(usize);
+
+/// Where a mesh is stored in the shared buffers and the sphere bounding it
+#[derive(Debug, Clone, Copy)]
+struct MeshRange {
+    first_index: u32,
+    index_count: u32,
+    base_vertex: i32,
+    bounds: [f32; 4],
+}
+
+/// Collects the meshes and draws of a [`StaticBatch`]
+#[derive(Debug, Default)]
+pub struct StaticBatchBuilder {
+    vertices: Vec<u8>,
+    indices: Vec<u32>,
+    meshes: Vec<MeshRange>,
+    draws: Vec<(BatchMesh, Mat4)>,
+}
+
+impl StaticBatchBuilder {
+    /// Create an empty batch
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a mesh to the shared buffers so it can be drawn any number of times
+    pub fn add_mesh(&mut self, mesh: &IndexedMesh<f32>) -> BatchMesh {
+        let (vertices, indices) = indexed_vertex_buffer(mesh);
+        let range = MeshRange {
+            first_index: self.indices.len() as u32,
+            index_count: indices.len() as u32,
+            base_vertex: (self.vertices.len() / size_of::<MeshVertex>()) as i32,
+            bounds: bounds(mesh),
+        };
+        self.vertices.extend_from_slice(&vertices);
+        self.indices.extend_from_slice(&indices);
+        self.meshes.push(range);
+        BatchMesh(self.meshes.len() - 1)
+    }
+
+    /// Draw `mesh` with the model matrix `model`
+    pub fn add_draw(&mut self, mesh: BatchMesh, model: impl Into<ColumnMatrix4<f32>>) -> BatchDraw {
+        self.draws.push((mesh, Mat4::from(model.into())));
+        BatchDraw(self.draws.len() - 1)
+    }
+
+    /// Upload the batch to the GPU
+    pub fn build(self, material: StandardMaterial) -> StaticBatch {
+        let device = device();
+
+        let mut draws = Vec::with_capacity(self.draws.len() * DRAW_SIZE as usize);
+        for (mesh, model) in &self.draws {
+            let range = self.meshes[mesh.0];
+            draws.extend_from_slice(&transform_bytes(*model, *model));
+            draws.extend_from_slice(bytemuck::cast_slice(&range.bounds));
+            draws.extend_from_slice(bytemuck::cast_slice(&[
+                range.first_index,
+                range.index_count,
+                range.base_vertex as u32,
+                0,
+            ]));
+        }
+
+        // buffers can't be empty
+        let init = |label, contents: &[u8], usage| {
+            let padding = [0; ARGS_SIZE as usize];
+            device.create_buffer_init(&BufferInitDescriptor {
+                label: Some(label),
+                contents: if contents.is_empty() {
+                    &padding
+                } else {
+                    contents
+                },
+                usage,
+            })
+        };
+        let vertices = init(
+            "Static Batch Vertices",
+            &self.vertices,
+            BufferUsages::VERTEX,
+        );
+        let indices = init(
+            "Static Batch Indices",
+            bytemuck::cast_slice(&self.indices),
+            BufferUsages::INDEX,
+        );
+        let draw_buffer = init(
+            "Static Batch Draws",
+            &draws,
+            BufferUsages::STORAGE | BufferUsages::COPY_DST,
+        );
+        let args = init(
+            "Static Batch Arguments",
+            &vec![0; self.draws.len() * ARGS_SIZE as usize],
+            BufferUsages::STORAGE | BufferUsages::INDIRECT,
+        );
+        stats::buffers_created(4);
+
+        let cull = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Static Batch Culling"),
+            layout: indirect::cull_layout(),
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: draw_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: args.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: camera::buffer().as_entire_binding(),
+                },
+            ],
+        });
+        let draw_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Static Batch"),
+            layout: indirect::layout(),
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: draw_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: camera::buffer().as_entire_binding(),
+                },
+            ],
+        });
+
+        StaticBatch {
+            vertices,
+            indices,
+            args,
+            cull,
+            draw_group,
+            material,
+            index_count: self
+                .draws
+                .iter()
+                .map(|(mesh, _)| self.meshes[mesh.0].index_count)
+                .sum(),
+            models: RefCell::new(self.draws.iter().map(|(_, model)| *model).collect()),
+            moved: RefCell::default(),
+            moving: RefCell::default(),
+            draws: draw_buffer,
+        }
+    }
+}
+
+/// A set of static meshes culled and drawn on the GPU
+///
+/// Build one with a [`StaticBatchBuilder`] and draw it with
+/// [`Frame::draw_batch`](crate::Frame::draw_batch).
+pub struct StaticBatch {
+    vertices: Buffer,
+    indices: Buffer,
+    draws: Buffer,
+    args: Buffer,
+    cull: BindGroup,
+    draw_group: BindGroup,
+    /// number of indices drawn if nothing is culled
+    index_count: u32,
+    /// current model matrix of each draw
+    models: RefCell<Vec<Mat4>>,
+    /// draws moved since the batch was last drawn
+    moved: RefCell<Vec<usize>>,
+    /// draws moved the time before, their previous model matrix still has to catch up
+    moving: RefCell<Vec<usize>>,
+    material: StandardMaterial,
+}
+
+impl StaticBatch {
+    /// Move a draw
+    ///
+    /// Like [`Transform::update`](crate::Transform::update) this should be called every frame
+    /// while the draw is moving so its motion vectors stay correct. Once a draw stops moving its
+    /// previous model matrix catches up the next frame the batch is drawn.
+    pub fn set_transform(&self, draw: BatchDraw, model: impl Into<ColumnMatrix4<f32>>) {
+        let model = Mat4::from(model.into());
+        let prev = std::mem::replace(&mut self.models.borrow_mut()[draw.0], model);
+        self.write_transform(draw.0, model, prev);
+
+        let mut moved = self.moved.borrow_mut();
+        if !moved.contains(&draw.0) {
+            moved.push(draw.0);
+        }
+    }
+
+    /// Age the previous model matrices by a frame
+    ///
+    /// Draws that moved last frame but not this one get their previous matrix set to their
+    /// current one, so they stop producing motion vectors.
+    pub(crate) fn age(&self) {
+        let moved = self.moved.take();
+        let models = self.models.borrow();
+        for &draw in self.moving.borrow().iter() {
+            if !moved.contains(&draw) {
+                self.write_transform(draw, models[draw], models[draw]);
+            }
+        }
+        *self.moving.borrow_mut() = moved;
+    }
+
+    fn write_transform(&self, draw: usize, model: Mat4, prev: Mat4) {
+        queue().write_buffer(
+            &self.draws,
+            draw as u64 * DRAW_SIZE,
+            &transform_bytes(model, prev),
+        );
+        stats::record_buffer_write();
+    }
+
+    /// Number of draws in this batch
+    pub fn len(&self) -> usize {
+        self.models.borrow().len()
+    }
+
+    /// Check if this batch has no draws
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The draw calls issued for this batch if nothing is culled
+    pub fn stats(&self) -> DrawStats {
+        let draw_calls = if indirect::multi_draw() {
+            1
+        } else {
+            self.len() as u32
+        };
+        DrawStats::new(draw_calls.min(self.len() as u32), self.index_count)
+    }
+
+    /// Cull the draws against the currently bound camera
+    pub(crate) fn cull(&self, encoder: &mut CommandEncoder) {
+        if self.is_empty() {
+            return;
+        }
+        let mut cpass = encoder.begin_compute_pass(&Default::default());
+        cpass.set_pipeline(indirect::cull_pipeline());
+        cpass.set_bind_group(0, &self.cull, &[]);
+        cpass.dispatch_workgroups(
+            (self.len() as u32 + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE,
+            1,
+            1,
+        );
+    }
+
+    /// Draw the batch into the G-buffer using the arguments written by [`StaticBatch::cull`]
+    pub(crate) fn draw<'a>(&'a self, rpass: &mut RenderPass<'a>) {
+        if self.is_empty() {
+            return;
+        }
+        rpass.set_pipeline(indirect::pipeline());
+        rpass.set_bind_group(
+            0,
+            self.material
+                .bind_group(material::layout::<StandardMaterial>()),
+            &[],
+        );
+        rpass.set_bind_group(1, &self.draw_group, &[]);
+        rpass.set_vertex_buffer(0, self.vertices.slice(..));
+        rpass.set_index_buffer(self.indices.slice(..), IndexFormat::Uint32);
+
+        let len = self.len() as u32;
+        if indirect::multi_draw() {
+            // each draw starts at its own instance so no offset is needed
+            rpass.set_push_constants(ShaderStages::VERTEX, 0, bytemuck::bytes_of(&0u32));
+            rpass.multi_draw_indexed_indirect(&self.args, 0, len);
+        } else {
+            for i in 0..len {
+                rpass.set_push_constants(ShaderStages::VERTEX, 0, bytemuck::bytes_of(&i));
+                rpass.draw_indexed_indirect(&self.args, i as u64 * ARGS_SIZE);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for StaticBatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StaticBatch")
+            .field("len", &self.len())
+            .field("index_count", &self.index_count)
+            .finish()
+    }
+}
+
+impl Drop for StaticBatch {
+    fn drop(&mut self) {
+        stats::buffers_dropped(4);
+    }
+}
+
+/// The model, normal and previous model matrix of a draw
+fn transform_bytes(model: Mat4, prev: Mat4) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(64 * 3);
+    buffer.extend_from_slice(model.as_byte_slice());
+    buffer.extend_from_slice(model.inversed().transposed().as_byte_slice());
+    buffer.extend_from_slice(prev.as_byte_slice());
+    buffer
+}
+
+/// Center and radius of a sphere around a mesh
+fn bounds(mesh: &IndexedMesh<f32>) -> [f32; 4] {
+    if mesh.verts.is_empty() {
+        return [0.0; 4];
+    }
+    let (mut min, mut max) = ([f32::INFINITY; 3], [f32::NEG_INFINITY; 3]);
+    for v in &mesh.verts {
+        for (i, x) in [v.x, v.y, v.z].into_iter().enumerate() {
+            min[i] = min[i].min(x);
+            max[i] = max[i].max(x);
+        }
+    }
+    let center = [0, 1, 2].map(|i| (min[i] + max[i]) / 2.0);
+    let radius = mesh
+        .verts
+        .iter()
+        .map(|v| {
+            let d = [v.x - center[0], v.y - center[1], v.z - center[2]];
+            (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt()
+        })
+        .fold(0.0, f32::max);
+    [center[0], center[1], center[2], radius]
+}
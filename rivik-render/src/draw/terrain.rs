@@ -0,0 +1,430 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public License,
+ * v. 2.0. If a copy of the MPL was not distributed with this file, You can
+ * obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+//! Heightmap terrain
+//!
+//! The heightmap is split into square chunks which are drawn with fewer triangles the further
+//! they are from the camera. Each chunk has a skirt hanging down from its edges which hides the
+//! cracks between neighbouring chunks at different levels of detail.
+
+use image::DynamicImage;
+use mint::Vector3;
+use ultraviolet::Vec3;
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    Buffer, BufferUsages, IndexFormat, RenderBundle, RenderBundleDescriptor,
+    RenderBundleEncoderDescriptor, ShaderStages,
+};
+
+use crate::{
+    context::{device, gbuffer},
+    debug::{self, DebugMode, ModeBundles},
+    material::{self, Material, TerrainMaterial},
+    pipeline::{mesh::MeshVertex, GBuffer},
+    stats::{self, DrawStats},
+    transform::Spatial,
+    Drawable, Transform,
+};
+
+/// Describes how a heightmap is turned into a [`Terrain`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TerrainDesc {
+    /// Distance between neighbouring heightmap samples in world units
+    pub spacing: f32,
+    /// Height of a white heightmap sample in world units
+    pub height: f32,
+    /// Number of heightmap cells along each side of a chunk
+    pub chunk_size: u32,
+    /// Number of levels of detail, each one halves the resolution of the last
+    ///
+    /// Levels stop once a chunk is a single quad, so at most `log2(chunk_size) + 1` are built.
+    pub lod_levels: u32,
+    /// Distance from the camera at which chunks drop to the second level of detail
+    ///
+    /// Each following level starts at twice the distance of the previous one.
+    pub lod_distance: f32,
+}
+
+impl Default for TerrainDesc {
+    fn default() -> Self {
+        Self {
+            spacing: 1.0,
+            height: 32.0,
+            chunk_size: 32,
+            lod_levels: 4,
+            lod_distance: 64.0,
+        }
+    }
+}
+
+/// A section of the terrain with its own vertices and an index buffer per level of detail
+struct Chunk {
+    vertices: Buffer,
+    /// index buffers and their lengths, from the most to the least detailed
+    lods: Vec<(Buffer, u32)>,
+    /// center of the chunk in terrain space
+    center: Vec3,
+}
+
+impl Drop for Chunk {
+    fn drop(&mut self) {
+        stats::buffers_dropped(1 + self.lods.len() as u64);
+    }
+}
+
+/// Terrain generated from a heightmap
+///
+/// The terrain starts at the origin and extends along positive x and z, textured with a
+/// [`TerrainMaterial`] stretched over its whole surface. Call [`Terrain::update_lod`] with the
+/// camera position to pick the level of detail of each chunk.
+pub struct Terrain {
+    bundle: RenderBundle,
+    /// bundles drawing the selected levels of detail in debug views
+    debug: ModeBundles,
+    transform: Transform,
+    chunks: Vec<Chunk>,
+    /// selected level of detail for each chunk
+    selected: Vec<usize>,
+    desc: TerrainDesc,
+    heights: Vec<f32>,
+    width: u32,
+    depth: u32,
+    material: TerrainMaterial,
+}
+
+impl Drawable for Terrain {
+    fn bundle(&self) -> &RenderBundle {
+        &self.bundle
+    }
+
+    fn stats(&self) -> DrawStats {
+        let indices = self
+            .chunks
+            .iter()
+            .zip(&self.selected)
+            .map(|(chunk, &lod)| chunk.lods[lod].1)
+            .sum();
+        DrawStats::new(self.chunks.len() as u32, indices)
+    }
+
+    fn debug_bundle(&self, mode: DebugMode) -> Option<&RenderBundle> {
+        self.debug.get(mode, || self.record_debug(mode))
+    }
+}
+
+impl Spatial for Terrain {
+    fn transform(&self) -> &Transform {
+        &self.transform
+    }
+}
+
+impl Terrain {
+    /// Create terrain from the luminance of a heightmap
+    ///
+    /// Heightmaps are usually loaded with [`Img`](assets::formats::img::Img), 16 bit images give
+    /// smoother slopes than 8 bit ones.
+    pub fn new(heightmap: &DynamicImage, desc: TerrainDesc, material: TerrainMaterial) -> Self {
+        let luma = heightmap.to_luma16();
+        let (width, depth) = luma.dimensions();
+        assert!(
+            width >= 2 && depth >= 2,
+            "Heightmaps need at least 2x2 samples"
+        );
+        assert!(desc.chunk_size > 0, "Terrain chunks can't be empty");
+        let heights = luma
+            .pixels()
+            .map(|p| p.0[0] as f32 / u16::MAX as f32 * desc.height)
+            .collect();
+
+        let mut terrain = Self {
+            bundle: device()
+                .create_render_bundle_encoder(&bundle_descriptor())
+                .finish(&RenderBundleDescriptor { label: None }),
+            debug: ModeBundles::default(),
+            transform: Transform::default(),
+            chunks: vec![],
+            selected: vec![],
+            desc,
+            heights,
+            width,
+            depth,
+            material,
+        };
+
+        let size = desc.chunk_size;
+        for z in (0..depth - 1).step_by(size as usize) {
+            for x in (0..width - 1).step_by(size as usize) {
+                let chunk =
+                    terrain.chunk(x, z, (x + size).min(width - 1), (z + size).min(depth - 1));
+                terrain.chunks.push(chunk);
+            }
+        }
+        terrain.selected = vec![0; terrain.chunks.len()];
+        terrain.bundle = terrain.record();
+        terrain
+    }
+
+    /// Pick the level of detail of each chunk from its distance to `camera`
+    ///
+    /// This should be called whenever the camera or terrain moves, the terrain is only
+    /// re-recorded when a chunk changes level.
+    pub fn update_lod(&mut self, camera: impl Into<Vector3<f32>>) {
+        let camera = Vec3::from(camera.into());
+        let model = self.transform.model();
+
+        let mut changed = false;
+        for (chunk, selected) in self.chunks.iter().zip(&mut self.selected) {
+            let distance = (model.transform_point3(chunk.center) - camera).mag();
+            let mut lod = 0;
+            while lod + 1 < chunk.lods.len()
+                && distance > self.desc.lod_distance * 2f32.powi(lod as i32)
+            {
+                lod += 1;
+            }
+            if *selected != lod {
+                *selected = lod;
+                changed = true;
+            }
+        }
+
+        if changed {
+            self.bundle = self.record();
+            self.debug = ModeBundles::default();
+        }
+    }
+
+    /// Height of the terrain surface at `x`, `z` in terrain space
+    ///
+    /// Heights between samples are interpolated, points outside the terrain have no height.
+    pub fn height_at(&self, x: f32, z: f32) -> Option<f32> {
+        let x = x / self.desc.spacing;
+        let z = z / self.desc.spacing;
+        let (max_x, max_z) = ((self.width - 1) as f32, (self.depth - 1) as f32);
+        if !(0.0..=max_x).contains(&x) || !(0.0..=max_z).contains(&z) {
+            return None;
+        }
+
+        // stay inside the last cell on the far edges
+        let x0 = (x.floor() as u32).min(self.width - 2);
+        let z0 = (z.floor() as u32).min(self.depth - 2);
+        let (tx, tz) = (x - x0 as f32, z - z0 as f32);
+
+        let near = lerp(self.sample(x0, z0), self.sample(x0 + 1, z0), tx);
+        let far = lerp(self.sample(x0, z0 + 1), self.sample(x0 + 1, z0 + 1), tx);
+        Some(lerp(near, far, tz))
+    }
+
+    fn sample(&self, x: u32, z: u32) -> f32 {
+        self.heights[(z * self.width + x) as usize]
+    }
+
+    fn vertex(&self, x: u32, z: u32, drop: f32) -> MeshVertex {
+        let spacing = self.desc.spacing;
+
+        // central differences, one sided on the edges of the heightmap
+        let (left, right) = (x.saturating_sub(1), (x + 1).min(self.width - 1));
+        let (back, front) = (z.saturating_sub(1), (z + 1).min(self.depth - 1));
+        let dx = (self.sample(right, z) - self.sample(left, z)) / ((right - left) as f32 * spacing);
+        let dz = (self.sample(x, front) - self.sample(x, back)) / ((front - back) as f32 * spacing);
+        let norm = Vec3::new(-dx, 1.0, -dz).normalized();
+
+        MeshVertex {
+            pos: [
+                x as f32 * spacing,
+                self.sample(x, z) - drop,
+                z as f32 * spacing,
+            ],
+            norm: [norm.x, norm.y, norm.z],
+            uv: [
+                x as f32 / (self.width - 1) as f32,
+                z as f32 / (self.depth - 1) as f32,
+            ],
+        }
+    }
+
+    /// Build the chunk covering the samples from `x0`, `z0` to `x1`, `z1` inclusive
+    fn chunk(&self, x0: u32, z0: u32, x1: u32, z1: u32) -> Chunk {
+        let (nx, nz) = (x1 - x0, z1 - z0);
+        let row = nx + 1;
+        let skirt = self.desc.height / 8.0;
+
+        // the grid followed by the skirts along the back, front, left and right edges
+        let mut vertices = vec![];
+        for z in z0..=z1 {
+            for x in x0..=x1 {
+                vertices.push(self.vertex(x, z, 0.0));
+            }
+        }
+        let grid_len = vertices.len() as u32;
+        vertices.extend((x0..=x1).map(|x| self.vertex(x, z0, skirt)));
+        vertices.extend((x0..=x1).map(|x| self.vertex(x, z1, skirt)));
+        vertices.extend((z0..=z1).map(|z| self.vertex(x0, z, skirt)));
+        vertices.extend((z0..=z1).map(|z| self.vertex(x1, z, skirt)));
+
+        let grid = |x: u32, z: u32| z * row + x;
+        let back = |x: u32| grid_len + x;
+        let front = |x: u32| grid_len + row + x;
+        let left = |z: u32| grid_len + row * 2 + z;
+        let right = |z: u32| grid_len + row * 2 + nz + 1 + z;
+
+        let device = device();
+        let levels = (u32::BITS - self.desc.chunk_size.leading_zeros()).min(self.desc.lod_levels);
+        let lods = (0..levels.max(1))
+            .map(|lod| {
+                let xs = steps(nx, 1 << lod);
+                let zs = steps(nz, 1 << lod);
+
+                let mut indices = vec![];
+                for z in zs.windows(2) {
+                    for x in xs.windows(2) {
+                        quad(
+                            &mut indices,
+                            [
+                                grid(x[0], z[0]),
+                                grid(x[1], z[0]),
+                                grid(x[0], z[1]),
+                                grid(x[1], z[1]),
+                            ],
+                        );
+                    }
+                }
+                for x in xs.windows(2) {
+                    quad(
+                        &mut indices,
+                        [grid(x[0], 0), grid(x[1], 0), back(x[0]), back(x[1])],
+                    );
+                    quad(
+                        &mut indices,
+                        [grid(x[0], nz), grid(x[1], nz), front(x[0]), front(x[1])],
+                    );
+                }
+                for z in zs.windows(2) {
+                    quad(
+                        &mut indices,
+                        [grid(0, z[0]), grid(0, z[1]), left(z[0]), left(z[1])],
+                    );
+                    quad(
+                        &mut indices,
+                        [grid(nx, z[0]), grid(nx, z[1]), right(z[0]), right(z[1])],
+                    );
+                }
+
+                let buffer = device.create_buffer_init(&BufferInitDescriptor {
+                    label: Some("Terrain Indices"),
+                    contents: bytemuck::cast_slice(&indices),
+                    usage: BufferUsages::INDEX | BufferUsages::STORAGE,
+                });
+                (buffer, indices.len() as u32)
+            })
+            .collect::<Vec<_>>();
+
+        let vertices = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Terrain Vertices"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: BufferUsages::VERTEX | BufferUsages::STORAGE,
+        });
+        stats::buffers_created(1 + lods.len() as u64);
+
+        let spacing = self.desc.spacing;
+        let center_height = self.sample((x0 + x1) / 2, (z0 + z1) / 2);
+        Chunk {
+            vertices,
+            lods,
+            center: Vec3::new(
+                (x0 + x1) as f32 / 2.0 * spacing,
+                center_height,
+                (z0 + z1) as f32 / 2.0 * spacing,
+            ),
+        }
+    }
+
+    fn record(&self) -> RenderBundle {
+        let mut bundle = device().create_render_bundle_encoder(&bundle_descriptor());
+        bundle.set_pipeline(material::pipeline::<TerrainMaterial>());
+        bundle.set_bind_group(
+            0,
+            self.material
+                .bind_group(material::layout::<TerrainMaterial>()),
+            &[],
+        );
+        bundle.set_bind_group(1, self.transform.bind_group(), &[]);
+        for (chunk, &lod) in self.chunks.iter().zip(&self.selected) {
+            let (indices, len) = &chunk.lods[lod];
+            bundle.set_vertex_buffer(0, chunk.vertices.slice(..));
+            bundle.set_index_buffer(indices.slice(..), IndexFormat::Uint32);
+            bundle.draw_indexed(0..*len, 0, 0..1);
+        }
+        bundle.finish(&RenderBundleDescriptor {
+            label: Some("Terrain"),
+        })
+    }
+
+    fn record_debug(&self, mode: DebugMode) -> RenderBundle {
+        // chunks are indexed, so views that need barycentrics fetch their vertices from storage
+        let fetch = mode.needs_barycentrics();
+        let storage: Vec<_> = self
+            .chunks
+            .iter()
+            .zip(&self.selected)
+            .filter(|_| fetch)
+            .map(|(chunk, &lod)| debug::storage_group(&chunk.vertices, &chunk.lods[lod].0))
+            .collect();
+
+        let mut bundle = device().create_render_bundle_encoder(&bundle_descriptor());
+        bundle.set_pipeline(debug::pipeline::<TerrainMaterial>(mode, fetch));
+        bundle.set_bind_group(0, self.transform.bind_group(), &[]);
+        if fetch {
+            let positions = debug::positions::<TerrainMaterial>();
+            bundle.set_push_constants(
+                ShaderStages::VERTEX,
+                0,
+                bytemuck::cast_slice(&positions.words()),
+            );
+        }
+        for (i, (chunk, &lod)) in self.chunks.iter().zip(&self.selected).enumerate() {
+            let (indices, len) = &chunk.lods[lod];
+            match storage.get(i) {
+                Some(storage) => {
+                    bundle.set_bind_group(1, storage, &[]);
+                    bundle.draw(0..*len, 0..1);
+                }
+                None => {
+                    bundle.set_vertex_buffer(0, chunk.vertices.slice(..));
+                    bundle.set_index_buffer(indices.slice(..), IndexFormat::Uint32);
+                    bundle.draw_indexed(0..*len, 0, 0..1);
+                }
+            }
+        }
+        bundle.finish(&RenderBundleDescriptor {
+            label: Some("Terrain Debug View"),
+        })
+    }
+}
+
+fn bundle_descriptor() -> RenderBundleEncoderDescriptor<'static> {
+    RenderBundleEncoderDescriptor {
+        label: None,
+        color_formats: GBuffer::color_formats(),
+        depth_stencil: GBuffer::depth_format(),
+        sample_count: gbuffer().sample_count(),
+        multiview: None,
+    }
+}
+
+/// Every `step`th position from 0 to `len`, always ending on `len`
+fn steps(len: u32, step: u32) -> Vec<u32> {
+    let mut steps: Vec<u32> = (0..len).step_by(step as usize).collect();
+    steps.push(len);
+    steps
+}
+
+fn quad(indices: &mut Vec<u32>, [a, b, c, d]: [u32; 4]) {
+    indices.extend_from_slice(&[a, c, b, b, c, d]);
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
@@ -0,0 +1,277 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public License,
+ * v. 2.0. If a copy of the MPL was not distributed with this file, You can
+ * obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+//! Meshes that can be rewritten from the CPU
+//!
+//! A [`DynamicMesh`] owns vertex and index buffers that can be replaced or partially updated at
+//! any time. The buffers grow when more data is written than they can hold, which replaces them
+//! with larger ones. Drawables built from a dynamic mesh, such as [`Mesh::dynamic`] and
+//! [`PixelMesh::dynamic`], notice the change and re-record their bundle in
+//! [`Drawable::prepare`](crate::Drawable::prepare).
+//!
+//! [`Mesh::dynamic`]: crate::draw::Mesh::dynamic
+//! [`PixelMesh::dynamic`]: crate::draw::PixelMesh::dynamic
+
+use std::{
+    cell::{Cell, RefCell},
+    fmt,
+    mem::size_of,
+    rc::Rc,
+    sync::Arc,
+};
+
+use bytemuck::Pod;
+use wgpu::{Buffer, BufferDescriptor, BufferUsages};
+
+use crate::{
+    context::{device, queue},
+    load::CountedBuffer,
+    stats,
+};
+
+/// Vertex and index data that can be rewritten each frame
+pub struct DynamicMesh {
+    buffer: RefCell<Rc<Arc<CountedBuffer>>>,
+    stride: u64,
+    /// bumped whenever the drawn buffers or length change
+    version: Cell<u64>,
+}
+
+impl DynamicMesh {
+    /// Create a mesh drawn directly from its vertices
+    ///
+    /// `V` must match the vertex layout of the drawable this mesh is used with, for example
+    /// [`MeshVertex`](crate::pipeline::mesh::MeshVertex) for a [`Mesh`](crate::draw::Mesh).
+    pub fn new<V: Pod>(vertices: &[V]) -> Self {
+        let mesh = Self::with_buffer::<V>(CountedBuffer::new(
+            vertex_buffer(size_of_slice(vertices)),
+            vertices.len() as u32,
+        ));
+        mesh.write_vertices(0, vertices);
+        mesh
+    }
+
+    /// Create a mesh drawn with a `u32` index buffer
+    pub fn indexed<V: Pod>(vertices: &[V], indices: &[u32]) -> Self {
+        let mesh = Self::with_buffer::<V>(CountedBuffer::indexed(
+            vertex_buffer(size_of_slice(vertices)),
+            index_buffer(size_of_slice(indices)),
+            indices.len() as u32,
+        ));
+        mesh.write_vertices(0, vertices);
+        mesh.write_indices(0, indices);
+        mesh
+    }
+
+    fn with_buffer<V>(buffer: CountedBuffer) -> Self {
+        let stride = size_of::<V>() as u64;
+        assert!(
+            stride % 4 == 0,
+            "Dynamic mesh vertices must be a multiple of 4 bytes"
+        );
+        Self {
+            buffer: RefCell::new(Rc::new(Arc::new(buffer))),
+            stride,
+            version: Cell::new(0),
+        }
+    }
+
+    /// Replace all of the vertices of an unindexed mesh
+    pub fn set<V: Pod>(&self, vertices: &[V]) {
+        self.write_vertices(0, vertices);
+        self.set_len(vertices.len() as u32);
+    }
+
+    /// Replace all of the vertices and indices of an indexed mesh
+    ///
+    /// # Panics
+    ///
+    /// Panics if the mesh was not created with [`DynamicMesh::indexed`]
+    pub fn set_indexed<V: Pod>(&self, vertices: &[V], indices: &[u32]) {
+        self.write_vertices(0, vertices);
+        self.write_indices(0, indices);
+        self.set_len(indices.len() as u32);
+    }
+
+    /// Overwrite vertices starting at the vertex `offset`
+    ///
+    /// Writing past the end of an unindexed mesh extends its draw length.
+    pub fn write_vertices<V: Pod>(&self, offset: u32, vertices: &[V]) {
+        assert_eq!(
+            size_of::<V>() as u64,
+            self.stride,
+            "Vertices must be the same type the mesh was created with"
+        );
+        if vertices.is_empty() {
+            return;
+        }
+
+        let end = offset + vertices.len() as u32;
+        self.reserve(Target::Vertex, end as u64 * self.stride);
+        queue().write_buffer(
+            &self.buffer.borrow(),
+            offset as u64 * self.stride,
+            bytemuck::cast_slice(vertices),
+        );
+        stats::record_buffer_write();
+
+        if self.buffer.borrow().index_buffer().is_none() && end > self.len() {
+            self.set_len(end);
+        }
+    }
+
+    /// Overwrite indices starting at the index `offset`
+    ///
+    /// Writing past the end of the index buffer extends the draw length.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the mesh was not created with [`DynamicMesh::indexed`]
+    pub fn write_indices(&self, offset: u32, indices: &[u32]) {
+        assert!(
+            self.buffer.borrow().index_buffer().is_some(),
+            "Only indexed dynamic meshes have indices"
+        );
+        if indices.is_empty() {
+            return;
+        }
+
+        let end = offset + indices.len() as u32;
+        self.reserve(Target::Index, end as u64 * 4);
+        queue().write_buffer(
+            self.buffer.borrow().index_buffer().unwrap(),
+            offset as u64 * 4,
+            bytemuck::cast_slice(indices),
+        );
+        stats::record_buffer_write();
+
+        if end > self.len() {
+            self.set_len(end);
+        }
+    }
+
+    /// Change the number of vertices, or indices for indexed meshes, that are drawn
+    pub fn set_len(&self, len: u32) {
+        let buffer = self.buffer.borrow();
+        if buffer.len() != len {
+            buffer.set_len(len);
+            self.version.set(self.version.get() + 1);
+        }
+    }
+
+    /// Number of vertices, or indices for indexed meshes, that are drawn
+    pub fn len(&self) -> u32 {
+        self.buffer.borrow().len()
+    }
+
+    /// Check if nothing is drawn
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Incremented whenever drawables need to re-record their bundles
+    pub fn version(&self) -> u64 {
+        self.version.get()
+    }
+
+    /// The buffers currently holding this mesh
+    ///
+    /// These are replaced when the mesh grows, the old buffers live as long as the bundles that
+    /// draw them.
+    pub fn buffer(&self) -> Rc<Arc<CountedBuffer>> {
+        self.buffer.borrow().clone()
+    }
+
+    /// Make sure the `target` buffer can hold `size` bytes
+    ///
+    /// Growing at least doubles the capacity. Both buffers are replaced by copies since they are
+    /// owned by the same [`CountedBuffer`].
+    fn reserve(&self, target: Target, size: u64) {
+        let old = self.buffer();
+        let vertex_size = old.size();
+        let index_size = old.index_buffer().map(Buffer::size);
+        let current = match target {
+            Target::Vertex => vertex_size,
+            Target::Index => index_size.unwrap(),
+        };
+        if size <= current {
+            return;
+        }
+        let size = size.max(current * 2);
+
+        let vertex = vertex_buffer(match target {
+            Target::Vertex => size,
+            Target::Index => vertex_size,
+        });
+        let index = index_size.map(|index_size| {
+            index_buffer(match target {
+                Target::Index => size,
+                Target::Vertex => index_size,
+            })
+        });
+
+        // carry over what has been written so far
+        let mut encoder = device().create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Dynamic Mesh Growth"),
+        });
+        encoder.copy_buffer_to_buffer(&old, 0, &vertex, 0, vertex_size);
+        if let (Some(old), Some(new), Some(size)) = (old.index_buffer(), &index, index_size) {
+            encoder.copy_buffer_to_buffer(old, 0, new, 0, size);
+        }
+        let _ = queue().submit(Some(encoder.finish()));
+
+        let buffer = match index {
+            Some(index) => CountedBuffer::indexed(vertex, index, old.len()),
+            None => CountedBuffer::new(vertex, old.len()),
+        };
+        *self.buffer.borrow_mut() = Rc::new(Arc::new(buffer));
+        self.version.set(self.version.get() + 1);
+    }
+}
+
+/// Which buffer of a dynamic mesh is growing
+enum Target {
+    Vertex,
+    Index,
+}
+
+impl fmt::Debug for DynamicMesh {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DynamicMesh")
+            .field("len", &self.len())
+            .field("version", &self.version.get())
+            .finish()
+    }
+}
+
+fn size_of_slice<T>(slice: &[T]) -> u64 {
+    // buffers can't be empty
+    (size_of::<T>() * slice.len()).max(4) as u64
+}
+
+fn vertex_buffer(size: u64) -> Buffer {
+    device().create_buffer(&BufferDescriptor {
+        label: Some("Dynamic Vertices"),
+        size,
+        usage: BufferUsages::VERTEX
+            | BufferUsages::STORAGE
+            | BufferUsages::COPY_DST
+            | BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    })
+}
+
+fn index_buffer(size: u64) -> Buffer {
+    device().create_buffer(&BufferDescriptor {
+        label: Some("Dynamic Indices"),
+        size,
+        usage: BufferUsages::INDEX
+            | BufferUsages::STORAGE
+            | BufferUsages::COPY_DST
+            | BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    })
+}
@@ -4,8 +4,6 @@
  * obtain one at http://mozilla.org/MPL/2.0/.
  */
 
-use std::borrow::Borrow;
-
 use wgpu::{
     AddressMode, FilterMode, RenderBundle, RenderBundleDescriptor, RenderBundleEncoderDescriptor,
 };
@@ -13,6 +11,8 @@ use wgpu::{
 use crate::{
     context::{device, gbuffer, surface_config},
     pipeline::display,
+    stats::DrawStats,
+    Drawable,
 };
 
 /// Convienience object for drawing a buffer to screen
@@ -76,10 +76,6 @@ impl DisplayFilter {
 
         Self { bundle, hdr }
     }
-
-    pub(crate) fn bundle(&self) -> &RenderBundle {
-        &self.bundle
-    }
 }
 
 impl Default for DisplayFilter {
@@ -88,8 +84,13 @@ impl Default for DisplayFilter {
     }
 }
 
-impl Borrow<RenderBundle> for DisplayFilter {
-    fn borrow(&self) -> &RenderBundle {
+impl Drawable for DisplayFilter {
+    fn bundle(&self) -> &RenderBundle {
         &self.bundle
     }
+
+    fn stats(&self) -> DrawStats {
+        // a single fullscreen draw
+        DrawStats::new(1, 7)
+    }
 }
@@ -0,0 +1,134 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public License,
+ * v. 2.0. If a copy of the MPL was not distributed with this file, You can
+ * obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+use std::{rc::Rc, sync::Arc};
+
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    AddressMode, BindGroupDescriptor, BindGroupEntry, BindingResource, Buffer, BufferUsages,
+    FilterMode, RenderBundle, RenderBundleDescriptor, RenderBundleEncoderDescriptor,
+};
+
+use crate::{
+    context::{device, gbuffer, queue, surface_config},
+    load::LoadedTexture,
+    pipeline::grade,
+    stats::{self, DrawStats},
+    Drawable,
+};
+
+/// Filter that displays the lit image color graded by a pair of lookup tables
+///
+/// The LUTs are 3D textures loaded with [`GpuLut`](crate::load::GpuLut). The output is blended
+/// between the two tables so a scene can cross-fade from one grade to another. The lit image is
+/// tone mapped and looked up in sRGB, as LUTs are authored for display colors. This replaces the
+/// [`DisplayFilter`](super::DisplayFilter).
+pub struct GradeFilter {
+    bundle: RenderBundle,
+    buffer: Buffer,
+    #[allow(dead_code)]
+    luts: [Rc<Arc<LoadedTexture>>; 2],
+}
+
+impl GradeFilter {
+    /// Creates a filter that grades with a single LUT
+    pub fn new(lut: Rc<Arc<LoadedTexture>>) -> Self {
+        Self::blended(Rc::clone(&lut), lut, 0.0)
+    }
+
+    /// Creates a filter that blends between two LUTs
+    ///
+    /// A `blend` of 0 uses only `from` and 1 uses only `to`
+    pub fn blended(from: Rc<Arc<LoadedTexture>>, to: Rc<Arc<LoadedTexture>>, blend: f32) -> Self {
+        let device = device();
+        let gbuffer = gbuffer();
+        let fmt = surface_config().read().unwrap().format;
+
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Color Grade"),
+            contents: bytemuck::cast_slice(&[blend, 0.0, 0.0, 0.0]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Nearest,
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            ..Default::default()
+        });
+
+        let inputs = device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: grade::layout(),
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::Sampler(&sampler),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&gbuffer.hdr_view),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::TextureView(&from.1),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: BindingResource::TextureView(&to.1),
+                },
+                BindGroupEntry {
+                    binding: 4,
+                    resource: buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut bundle = device.create_render_bundle_encoder(&RenderBundleEncoderDescriptor {
+            label: None,
+            color_formats: &[Some(fmt)],
+            depth_stencil: None,
+            sample_count: 1,
+            multiview: None,
+        });
+
+        bundle.set_pipeline(grade::pipeline());
+        bundle.set_bind_group(0, &inputs, &[]);
+        bundle.draw(0..7, 0..1);
+
+        let bundle = bundle.finish(&RenderBundleDescriptor { label: None });
+
+        Self {
+            bundle,
+            buffer,
+            luts: [from, to],
+        }
+    }
+
+    /// Queues a write of how far to blend from the first LUT to the second
+    pub fn set_blend(&self, blend: f32) {
+        queue().write_buffer(
+            &self.buffer,
+            0,
+            bytemuck::cast_slice(&[blend.clamp(0.0, 1.0), 0.0, 0.0, 0.0]),
+        );
+        stats::record_buffer_write();
+    }
+}
+
+impl Drawable for GradeFilter {
+    fn bundle(&self) -> &RenderBundle {
+        &self.bundle
+    }
+
+    fn stats(&self) -> DrawStats {
+        // a single fullscreen draw
+        DrawStats::new(1, 7)
+    }
+}
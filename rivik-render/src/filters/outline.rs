@@ -0,0 +1,131 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public License,
+ * v. 2.0. If a copy of the MPL was not distributed with this file, You can
+ * obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroupDescriptor, BindGroupEntry, Buffer, BufferUsages, RenderBundle,
+    RenderBundleDescriptor, RenderBundleEncoderDescriptor,
+};
+
+use crate::{
+    context::{device, gbuffer, queue, surface_config},
+    pipeline::outline,
+    stats::{self, DrawStats},
+    Drawable,
+};
+
+/// Settings for how outlines are detected and drawn
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OutlineSettings {
+    /// Color of the outline, alpha blends it over the image
+    pub color: [f32; 4],
+    /// Relative change in depth between neighbouring pixels that counts as an edge
+    pub depth_threshold: f32,
+    /// Change in normal between neighbouring pixels that counts as an edge, from 0 to 2
+    pub normal_threshold: f32,
+    /// Distance in pixels to the neighbours that are compared
+    pub thickness: f32,
+}
+
+impl Default for OutlineSettings {
+    fn default() -> Self {
+        Self {
+            color: [0.0, 0.0, 0.0, 1.0],
+            depth_threshold: 0.05,
+            normal_threshold: 0.4,
+            thickness: 1.0,
+        }
+    }
+}
+
+impl OutlineSettings {
+    fn data(&self) -> [f32; 8] {
+        let [r, g, b, a] = self.color;
+        [
+            r,
+            g,
+            b,
+            a,
+            self.depth_threshold,
+            self.normal_threshold,
+            self.thickness,
+            0.0,
+        ]
+    }
+}
+
+/// Filter that draws toon outlines where the G-buffer's depth or normals are discontinuous
+///
+/// The outline is blended over the image so this should be drawn after the filter that displays
+/// the lit image.
+pub struct OutlineFilter {
+    bundle: RenderBundle,
+    buffer: Buffer,
+}
+
+impl OutlineFilter {
+    /// Creates a new outline filter
+    pub fn new(settings: OutlineSettings) -> Self {
+        let device = device();
+        let gbuffer = gbuffer();
+        let fmt = surface_config().read().unwrap().format;
+
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Outline"),
+            contents: bytemuck::cast_slice(&settings.data()),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let uniform = device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: outline::layout(),
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        let mut bundle = device.create_render_bundle_encoder(&RenderBundleEncoderDescriptor {
+            label: None,
+            color_formats: &[Some(fmt)],
+            depth_stencil: None,
+            sample_count: 1,
+            multiview: None,
+        });
+
+        bundle.set_pipeline(outline::pipeline());
+        bundle.set_bind_group(0, &gbuffer.bind_group, &[]);
+        bundle.set_bind_group(1, &uniform, &[]);
+        bundle.draw(0..7, 0..1);
+
+        let bundle = bundle.finish(&RenderBundleDescriptor { label: None });
+
+        Self { bundle, buffer }
+    }
+
+    /// Queues a write of new outline settings
+    pub fn set(&self, settings: OutlineSettings) {
+        queue().write_buffer(&self.buffer, 0, bytemuck::cast_slice(&settings.data()));
+        stats::record_buffer_write();
+    }
+}
+
+impl Default for OutlineFilter {
+    fn default() -> Self {
+        Self::new(OutlineSettings::default())
+    }
+}
+
+impl Drawable for OutlineFilter {
+    fn bundle(&self) -> &RenderBundle {
+        &self.bundle
+    }
+
+    fn stats(&self) -> DrawStats {
+        // a single fullscreen draw
+        DrawStats::new(1, 7)
+    }
+}
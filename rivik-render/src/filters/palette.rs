@@ -0,0 +1,111 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public License,
+ * v. 2.0. If a copy of the MPL was not distributed with this file, You can
+ * obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+use std::{rc::Rc, sync::Arc};
+
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroupDescriptor, BindGroupEntry, BindingResource, Buffer, BufferUsages, RenderBundle,
+    RenderBundleDescriptor, RenderBundleEncoderDescriptor,
+};
+
+use crate::{
+    context::{device, gbuffer, queue, surface_config},
+    load::LoadedTexture,
+    pipeline::palette,
+    stats::{self, DrawStats},
+    Drawable,
+};
+
+/// Filter that displays the lit image quantized to the colors of a palette
+///
+/// Every texel of the palette texture is one color of the palette so it should be small, a
+/// palette PNG loaded with [`GpuTexture`](crate::load::GpuTexture) works well. This replaces the
+/// [`DisplayFilter`](super::DisplayFilter).
+pub struct PaletteFilter {
+    bundle: RenderBundle,
+    buffer: Buffer,
+    #[allow(dead_code)]
+    palette: Rc<Arc<LoadedTexture>>,
+}
+
+impl PaletteFilter {
+    /// Creates a new palette filter
+    ///
+    /// `dither` is the strength of the ordered dithering applied before quantizing, 0 disables
+    /// it
+    pub fn new(palette: Rc<Arc<LoadedTexture>>, dither: f32) -> Self {
+        let device = device();
+        let gbuffer = gbuffer();
+        let fmt = surface_config().read().unwrap().format;
+
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Palette"),
+            contents: bytemuck::cast_slice(&[dither, 0.0, 0.0, 0.0]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let inputs = device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: palette::layout(),
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&gbuffer.hdr_view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&palette.1),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut bundle = device.create_render_bundle_encoder(&RenderBundleEncoderDescriptor {
+            label: None,
+            color_formats: &[Some(fmt)],
+            depth_stencil: None,
+            sample_count: 1,
+            multiview: None,
+        });
+
+        bundle.set_pipeline(palette::pipeline());
+        bundle.set_bind_group(0, &inputs, &[]);
+        bundle.draw(0..7, 0..1);
+
+        let bundle = bundle.finish(&RenderBundleDescriptor { label: None });
+
+        Self {
+            bundle,
+            buffer,
+            palette,
+        }
+    }
+
+    /// Queues a write of the ordered dithering strength
+    pub fn set_dither(&self, dither: f32) {
+        queue().write_buffer(
+            &self.buffer,
+            0,
+            bytemuck::cast_slice(&[dither, 0.0, 0.0, 0.0]),
+        );
+        stats::record_buffer_write();
+    }
+}
+
+impl Drawable for PaletteFilter {
+    fn bundle(&self) -> &RenderBundle {
+        &self.bundle
+    }
+
+    fn stats(&self) -> DrawStats {
+        // a single fullscreen draw
+        DrawStats::new(1, 7)
+    }
+}
@@ -4,16 +4,27 @@
  * obtain one at http://mozilla.org/MPL/2.0/.
  */
 
-use std::borrow::Borrow;
+use std::iter;
 
-use egui::{ClippedPrimitive, TexturesDelta};
+use egui::{epaint::Primitive, ClippedPrimitive, TexturesDelta};
 use snafu::{Backtrace, ResultExt, Snafu};
 use tracing::{debug, debug_span, instrument};
 use wgpu::{Color, CommandEncoder, RenderBundle, SurfaceError, SurfaceTexture, TextureView};
 
 use crate::{
-    context::{device, egui_render, gbuffer, queue, surface, surface_config},
+    antialias,
+    camera::{self, Camera, Viewport},
+    context::{device, egui_render, gbuffer, queue, settings, surface, surface_config},
+    debug::DebugMode,
+    draw::StaticBatch,
     filters::DisplayFilter,
+    graph::{Pass, PassContext, RenderGraph, Resource},
+    lights::{ClusteredLights, Light},
+    particles::ParticleEmitter,
+    ssao,
+    stats::{self, DrawStats},
+    target::RenderTarget,
+    timestamps::FrameTimer,
 };
 
 /// An error constructing a frame
@@ -31,149 +42,353 @@ pub struct Frame<'a> {
     pub(crate) frame_view: TextureView,
     pub(crate) encoder: CommandEncoder,
     geom: Vec<&'a RenderBundle>,
+    batches: Vec<&'a StaticBatch>,
     lights: Vec<&'a RenderBundle>,
+    clustered: Vec<&'a ClusteredLights>,
     filters: Vec<&'a RenderBundle>,
+    particles: Vec<&'a ParticleEmitter>,
+    views: Vec<(&'a Camera, Viewport)>,
+    /// stats of the geometry and lights which are drawn once per view
+    view_stats: Vec<DrawStats>,
+    targets: Vec<Pass<'a>>,
+    compute: Vec<(ComputeStage, Pass<'a>)>,
+    passes: Vec<Pass<'a>>,
     ui: Option<(&'a [ClippedPrimitive], TexturesDelta)>,
+    /// debug view the frame's geometry is drawn with
+    debug: DebugMode,
+}
+
+/// A point in a [`Frame`] where compute work added with [`Frame::compute`] is recorded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComputeStage {
+    /// After render targets and before the geometry pass
+    BeforeGeometry,
+    /// After the G-buffer and ambient occlusion are drawn and before lighting
+    AfterGBuffer,
+    /// After lighting and particles and before custom passes, antialiasing and filters
+    AfterLighting,
+}
+
+impl ComputeStage {
+    /// Declare the resources used at this stage so the pass is ordered correctly
+    fn pass<'a>(self, pass: Pass<'a>) -> Pass<'a> {
+        match self {
+            // passes without resources run as early as they were added
+            ComputeStage::BeforeGeometry => pass,
+            ComputeStage::AfterGBuffer => pass.read(Resource::GBuffer).write(Resource::GBuffer),
+            ComputeStage::AfterLighting => pass
+                .read(Resource::GBuffer)
+                .read(Resource::Hdr)
+                .write(Resource::Hdr),
+        }
+    }
 }
 
 /// An object that can be drawn to a frame
 pub trait Drawable {
     /// Fetch a render bundle that draws this object
     fn bundle(&self) -> &RenderBundle;
+
+    /// The draw calls recorded into this object's render bundle
+    ///
+    /// Only used for [render statistics](crate::stats)
+    fn stats(&self) -> DrawStats {
+        DrawStats::default()
+    }
+
+    /// Bring this object's render bundle up to date before it is drawn
+    ///
+    /// Called once per frame before the object is added to a [`Frame`], after its transform was
+    /// updated. Objects drawing [dynamic meshes](crate::dynamic::DynamicMesh) re-record their
+    /// bundle here.
+    fn prepare(&mut self) {}
+
+    /// Fetch a render bundle that draws this object in a [debug view](crate::debug)
+    ///
+    /// Objects without a debug bundle are drawn with [`Drawable::bundle`] in every debug mode.
+    fn debug_bundle(&self, _mode: DebugMode) -> Option<&RenderBundle> {
+        None
+    }
 }
 
-impl<T> Drawable for T
-where
-    T: Borrow<RenderBundle>,
-{
+impl Drawable for RenderBundle {
     fn bundle(&self) -> &RenderBundle {
-        self.borrow()
+        self
     }
 }
 
 impl<'a> Frame<'a> {
     /// Finalize this frame and draw it to screen
+    ///
+    /// The builtin passes are added to a [`RenderGraph`] along with any passes added with
+    /// [`Frame::add_pass`], which are recorded before antialiasing and the UI. Render targets are
+    /// drawn before anything else.
     #[instrument(skip(self))]
     pub fn present(mut self) {
-        // TODO: Handle camera transform setup
-        {
-            let geom_span = debug_span!("Geometry render pass");
-            let _e = geom_span.enter();
-            let mut rpass = gbuffer().rpass(&mut self.encoder, Some(Color::BLACK));
-            rpass.execute_bundles(self.geom);
-        }
+        // really ugly way of generating a default list of filters but only creating them if there
+        // is an empty filter list.
+        //
+        // This is ugly because we are regenerating the list every frame.
+        // TODO: this should probably be recorded once and statically cached
+        let display = if self.filters.is_empty() {
+            Some(DisplayFilter::default())
+        } else {
+            None
+        };
 
-        {
-            let span = debug_span!("Lighting render pass");
-            let _e = span.enter();
-            let mut rpass = self.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Lighting"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &gbuffer().hdr_view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
-                        store: true,
-                    },
-                })],
-                depth_stencil_attachment: None,
-            });
-
-            rpass.execute_bundles(self.lights);
-        }
+        let filters = if !self.filters.is_empty() {
+            self.filters
+        } else {
+            let display = display.as_ref().unwrap();
+            stats::record_bundle(display.stats());
+            vec![display.bundle()]
+        };
 
-        {
-            let span = debug_span!("Filter render pass");
-            let _e = span.enter();
-            // really ugly way of generating a default list of filters but only creating them if there
-            // is an empty filter list.
-            //
-            // This is ugly because we are regenerating the list every frame.
-            // TODO: this should probably be recorded once and statically cached
-            let display = if self.filters.is_empty() {
-                Some(DisplayFilter::default())
-            } else {
-                None
-            };
-
-            let filters = if !self.filters.is_empty() {
-                self.filters
-            } else {
-                vec![display.as_ref().unwrap().bundle()]
-            };
-
-            {
-                let mut rpass = self.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                    label: Some("Filters"),
-                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                        view: &self.frame_view,
-                        resolve_target: None,
-                        ops: wgpu::Operations {
-                            load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
-                            store: true,
-                        },
-                    })],
-                    depth_stencil_attachment: None,
-                });
+        let mut graph = RenderGraph::new();
+        for pass in self.targets {
+            graph.add_pass(pass);
+        }
 
-                rpass.execute_bundles(filters);
+        let (mut before_geometry, mut after_gbuffer, mut after_lighting) = (vec![], vec![], vec![]);
+        for (stage, pass) in self.compute {
+            let pass = stage.pass(pass);
+            match stage {
+                ComputeStage::BeforeGeometry => before_geometry.push(pass),
+                ComputeStage::AfterGBuffer => after_gbuffer.push(pass),
+                ComputeStage::AfterLighting => after_lighting.push(pass),
             }
         }
+        for pass in before_geometry {
+            graph.add_pass(pass);
+        }
 
-        // render UI pass
-        if let Some((clipped_primitives, textures_delta)) = self.ui {
-            let span = debug_span!("Render UI");
-            let _e = span.enter();
-            let mut renderer = egui_render().write().unwrap();
-            // update textures
-            for (id, image) in textures_delta.set {
-                renderer.update_texture(device(), queue(), id, &image);
+        let views = if self.views.is_empty() {
+            vec![(camera::main(), Viewport::FULL)]
+        } else {
+            self.views
+        };
+        for _ in &views {
+            for stats in &self.view_stats {
+                stats::record_bundle(*stats);
             }
+            // the emission pass
+            stats::record_draw(DrawStats::new(1, 7));
+        }
 
-            let screen_descriptor = {
-                let config = surface_config().read().unwrap();
-                egui_wgpu::renderer::ScreenDescriptor {
-                    size_in_pixels: [config.width, config.height],
-                    pixels_per_point: 1.0,
+        let geom = self.geom;
+        let batches = self.batches;
+        let geom_views = views.clone();
+        graph.add_pass(
+            Pass::new("Geometry", move |ctx| {
+                for (i, (camera, viewport)) in geom_views.into_iter().enumerate() {
+                    let encoder = ctx.encoder();
+                    camera.bind(encoder);
+                    // batches depend on the camera so they are culled once per view
+                    for batch in &batches {
+                        batch.cull(encoder);
+                    }
+                    let clear = if i == 0 { Some(Color::BLACK) } else { None };
+                    let mut rpass = gbuffer().rpass(encoder, clear);
+                    viewport.apply(&mut rpass);
+                    rpass.execute_bundles(geom.iter().copied());
+                    for batch in &batches {
+                        batch.draw(&mut rpass);
+                    }
                 }
-            };
-
-            let _ = renderer.update_buffers(
-                device(),
-                queue(),
-                &mut self.encoder,
-                &clipped_primitives,
-                &screen_descriptor,
-            );
+                gbuffer().resolve(ctx.encoder());
+            })
+            .write(Resource::GBuffer),
+        );
 
-            {
-                let mut rpass = self.encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                        view: &self.frame_view,
-                        resolve_target: None,
-                        ops: wgpu::Operations {
-                            load: wgpu::LoadOp::Load,
-                            store: true,
-                        },
-                    })],
-                    depth_stencil_attachment: None,
-                    label: Some("egui_render"),
-                });
-
-                renderer.render(&mut rpass, &clipped_primitives, &screen_descriptor);
-            }
+        let ao_views = views.clone();
+        graph.add_pass(
+            Pass::new("Ambient Occlusion", move |ctx| {
+                for (i, (camera, viewport)) in ao_views.into_iter().enumerate() {
+                    let encoder = ctx.encoder();
+                    camera.bind(encoder);
+                    ssao::occlusion(encoder, &viewport, i == 0);
+                }
+            })
+            .read(Resource::GBuffer)
+            .write(Resource::GBuffer),
+        );
+
+        for pass in after_gbuffer {
+            graph.add_pass(pass);
+        }
 
-            for id in textures_delta.free {
-                renderer.free_texture(&id);
+        // debug views write their colors as emission, so they are shown unshaded without lights
+        let (lights, clustered) = if self.debug == DebugMode::Off {
+            (self.lights, self.clustered)
+        } else {
+            (vec![], vec![])
+        };
+        let particle_views = views.clone();
+        graph.add_pass(
+            Pass::new("Lighting", move |ctx| {
+                for (i, (camera, viewport)) in views.into_iter().enumerate() {
+                    let encoder = ctx.encoder();
+                    camera.bind(encoder);
+                    // clusters depend on the camera so they are culled once per view
+                    for set in &clustered {
+                        set.cull(encoder);
+                    }
+                    let mut rpass = gbuffer().light_rpass(encoder, i == 0);
+                    viewport.apply(&mut rpass);
+                    rpass.execute_bundles(iter::once(gbuffer().emission()));
+                    rpass.execute_bundles(lights.iter().copied());
+                    rpass.execute_bundles(clustered.iter().map(|set| set.bundle()));
+                }
+            })
+            .read(Resource::GBuffer)
+            .write(Resource::Hdr),
+        );
+
+        if !self.particles.is_empty() {
+            let particles = self.particles;
+            for _ in &particle_views {
+                for emitter in &particles {
+                    stats::record_bundle(emitter.stats());
+                }
             }
+            graph.add_pass(
+                Pass::new("Particles", move |ctx| {
+                    let encoder = ctx.encoder();
+                    {
+                        let mut cpass = encoder.begin_compute_pass(&Default::default());
+                        for emitter in &particles {
+                            emitter.simulate(&mut cpass);
+                        }
+                    }
+                    for (camera, viewport) in particle_views {
+                        camera.bind(encoder);
+                        let mut rpass = gbuffer().forward_rpass(encoder);
+                        viewport.apply(&mut rpass);
+                        rpass.execute_bundles(particles.iter().map(|e| e.bundle()));
+                    }
+                })
+                .read(Resource::GBuffer)
+                .read(Resource::Hdr)
+                .write(Resource::Hdr),
+            );
+        }
+
+        for pass in after_lighting {
+            graph.add_pass(pass);
+        }
+
+        for pass in self.passes {
+            graph.add_pass(pass);
         }
 
-        let span = debug_span!("GPU time");
+        graph.add_pass(
+            Pass::new("Antialiasing", |ctx| antialias::resolve(ctx.encoder()))
+                .read(Resource::GBuffer)
+                .read(Resource::Hdr)
+                .write(Resource::Hdr),
+        );
+
+        graph.add_pass(
+            Pass::new("Filters", move |ctx| {
+                let surface = ctx.surface();
+                let mut rpass = ctx
+                    .encoder()
+                    .begin_render_pass(&wgpu::RenderPassDescriptor {
+                        label: Some("Filters"),
+                        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                            view: surface,
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                                store: true,
+                            },
+                        })],
+                        depth_stencil_attachment: None,
+                    });
+
+                rpass.execute_bundles(filters);
+            })
+            // filters like outlines sample the G-buffer as well as the lit image
+            .read(Resource::GBuffer)
+            .read(Resource::Hdr)
+            .write(Resource::Surface),
+        );
+
+        if let Some((clipped_primitives, textures_delta)) = self.ui {
+            graph.add_pass(
+                Pass::new("UI", move |ctx| {
+                    let surface = ctx.surface();
+                    let encoder = ctx.encoder();
+                    let mut renderer = egui_render().write().unwrap();
+                    // update textures
+                    for (id, image) in textures_delta.set {
+                        renderer.update_texture(device(), queue(), id, &image);
+                        stats::record_texture_upload();
+                    }
+
+                    let screen_descriptor = {
+                        let config = surface_config().read().unwrap();
+                        egui_wgpu::renderer::ScreenDescriptor {
+                            size_in_pixels: [config.width, config.height],
+                            pixels_per_point: 1.0,
+                        }
+                    };
+
+                    let _ = renderer.update_buffers(
+                        device(),
+                        queue(),
+                        encoder,
+                        clipped_primitives,
+                        &screen_descriptor,
+                    );
+
+                    {
+                        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                                view: surface,
+                                resolve_target: None,
+                                ops: wgpu::Operations {
+                                    load: wgpu::LoadOp::Load,
+                                    store: true,
+                                },
+                            })],
+                            depth_stencil_attachment: None,
+                            label: Some("egui_render"),
+                        });
+
+                        renderer.render(&mut rpass, clipped_primitives, &screen_descriptor);
+                    }
+
+                    // egui issues a draw call for every mesh
+                    for prim in clipped_primitives {
+                        if let Primitive::Mesh(mesh) = &prim.primitive {
+                            stats::record_draw(DrawStats::new(1, mesh.indices.len() as u32));
+                        }
+                    }
+
+                    for id in textures_delta.free {
+                        renderer.free_texture(&id);
+                    }
+                })
+                .read(Resource::Surface)
+                .write(Resource::Surface),
+            );
+        }
+
+        let mut timer = FrameTimer::begin();
+        graph.execute_timed(&mut self.encoder, &self.frame_view, timer.as_mut());
+        let timer = timer.map(|t| t.resolve(&mut self.encoder));
+
+        let span = debug_span!("Submit");
         let _e = span.enter();
 
         let _ = queue().submit(Some(self.encoder.finish()));
+        if let Some(timer) = timer {
+            timer.read();
+        }
         debug!("Presenting Frame");
         self.frame.present();
+        stats::end_frame();
     }
 
     /// Try to fetch a new frame
@@ -191,27 +406,118 @@ impl<'a> Frame<'a> {
             frame_view,
             encoder,
             geom: vec![],
+            batches: vec![],
             lights: vec![],
+            clustered: vec![],
             filters: vec![],
+            particles: vec![],
+            views: vec![],
+            view_stats: vec![],
+            targets: vec![],
+            compute: vec![],
+            passes: vec![],
             ui: None,
+            debug: settings().read().unwrap().debug_mode,
         })
     }
 
     /// Draw a geometry object to the internal g-buffer
+    ///
+    /// While a [debug mode](crate::debug) is active the object's debug bundle is drawn instead.
     pub fn draw_geom(&mut self, geom: &'a dyn Drawable) {
-        self.geom.push(geom.bundle());
+        self.view_stats.push(geom.stats());
+        let bundle = match self.debug {
+            DebugMode::Off => None,
+            mode => geom.debug_bundle(mode),
+        };
+        self.geom.push(bundle.unwrap_or_else(|| geom.bundle()));
+    }
+
+    /// Draw a batch of static meshes to the internal g-buffer
+    ///
+    /// The batch is culled against each view before it is drawn. Batches have no debug bundles,
+    /// so they aren't drawn while a [debug mode](crate::debug) is active.
+    ///
+    /// This should be called once per frame, after the batch's draws were moved.
+    pub fn draw_batch(&mut self, batch: &'a StaticBatch) {
+        batch.age();
+        if self.debug != DebugMode::Off {
+            return;
+        }
+        self.view_stats.push(batch.stats());
+        self.batches.push(batch);
     }
 
     /// Add a light to this frame
     pub fn draw_light(&mut self, light: &'a dyn Drawable) {
+        self.view_stats.push(light.stats());
         self.lights.push(light.bundle());
     }
 
+    /// Add a set of clustered point lights to this frame
+    ///
+    /// The lights are culled against each view before the lighting pass.
+    pub fn draw_clustered(&mut self, lights: &'a ClusteredLights) {
+        self.view_stats.push(lights.stats());
+        self.clustered.push(lights);
+    }
+
     /// Add a post-processing filter to this frame
     pub fn draw_filter(&mut self, filter: &'a dyn Drawable) {
+        stats::record_bundle(filter.stats());
         self.filters.push(filter.bundle());
     }
 
+    /// Simulate a particle emitter and draw it over the lit scene
+    ///
+    /// The emitter is only simulated if it was [updated](ParticleEmitter::update) since it was
+    /// last drawn.
+    pub fn draw_particles(&mut self, emitter: &'a ParticleEmitter) {
+        self.particles.push(emitter);
+    }
+
+    /// Draw the frame from `camera` into `viewport`
+    ///
+    /// Every view goes through the geometry and lighting passes, while filters and the UI are
+    /// drawn once over the whole frame. If no views are added the frame is drawn from the
+    /// [main camera](camera::main) over the whole frame.
+    pub fn add_view(&mut self, camera: &'a Camera, viewport: Viewport) {
+        self.views.push((camera, viewport));
+    }
+
+    /// Render a view of the scene into a [`RenderTarget`] before this frame is drawn
+    ///
+    /// [Clustered lights](ClusteredLights) can be passed with the other lights, they are culled
+    /// for the target's camera.
+    pub fn draw_target(
+        &mut self,
+        target: &'a RenderTarget,
+        geom: &[&'a dyn Drawable],
+        lights: &[&'a dyn Light],
+    ) {
+        self.targets.push(target.pass(geom, lights, self.debug));
+    }
+
+    /// Record compute work at a point in this frame
+    ///
+    /// `hook` is called once per frame, not once per view, with the frame's encoder. The
+    /// G-buffer and the HDR image can be fetched from the [`PassContext`], the HDR image can
+    /// also be bound as a storage texture. Work added to the same stage is recorded in the
+    /// order it was added.
+    pub fn compute(
+        &mut self,
+        stage: ComputeStage,
+        name: &'static str,
+        hook: impl FnOnce(&mut PassContext) + 'a,
+    ) {
+        self.compute.push((stage, Pass::new(name, hook)));
+    }
+
+    /// Add a custom pass to this frame's render graph
+    pub fn add_pass(&mut self, pass: Pass<'a>) {
+        self.passes.push(pass);
+    }
+
     /// Draw an EGUI ui to this frame
     pub fn ui(&mut self, clip_prim: &'a [ClippedPrimitive], textures: TexturesDelta) {
         // store these values in self so we can render the UI later
@@ -0,0 +1,321 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public License,
+ * v. 2.0. If a copy of the MPL was not distributed with this file, You can
+ * obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+//! A declarative render graph
+//!
+//! Each [`Pass`] declares the [`Resource`]s it reads and writes and the graph works out the
+//! order to record them in. For every resource, passes that only write it run first, then
+//! passes that read and write it (in the order they were added) and finally passes that only
+//! read it. A pass that loads the existing contents of an attachment should list it as both a
+//! read and a write.
+//!
+//! Transient textures are declared on the graph, allocated from a pool when the graph is
+//! executed and returned to the pool afterwards.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt,
+    sync::Mutex,
+};
+
+use once_cell::sync::Lazy;
+use tracing::debug_span;
+use wgpu::{
+    CommandEncoder, Extent3d, Texture, TextureDescriptor, TextureDimension, TextureFormat,
+    TextureUsages, TextureView,
+};
+
+use crate::{
+    context::{device, gbuffer},
+    pipeline::GBuffer,
+    timestamps::FrameTimer,
+};
+
+/// Transient textures that are not in use by a graph
+static POOL: Lazy<Mutex<Vec<Transient>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+/// An attachment that a pass reads or writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Resource {
+    /// The G-buffer's geometry attachments and depth buffer
+    GBuffer,
+    /// The lit HDR image
+    Hdr,
+    /// The swapchain texture being presented
+    Surface,
+    /// A texture declared with [`RenderGraph::transient`]
+    Transient(&'static str),
+}
+
+/// A render pass in a [`RenderGraph`]
+pub struct Pass<'a> {
+    name: &'static str,
+    reads: Vec<Resource>,
+    writes: Vec<Resource>,
+    exec: Box<dyn FnOnce(&mut PassContext) + 'a>,
+}
+
+impl<'a> Pass<'a> {
+    /// Create a pass that records its commands with `exec`
+    pub fn new(name: &'static str, exec: impl FnOnce(&mut PassContext) + 'a) -> Self {
+        Self {
+            name,
+            reads: vec![],
+            writes: vec![],
+            exec: Box::new(exec),
+        }
+    }
+
+    /// Declare a resource this pass reads
+    pub fn read(mut self, resource: Resource) -> Self {
+        self.reads.push(resource);
+        self
+    }
+
+    /// Declare a resource this pass writes
+    pub fn write(mut self, resource: Resource) -> Self {
+        self.writes.push(resource);
+        self
+    }
+
+    /// The name of this pass
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl fmt::Debug for Pass<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pass")
+            .field("name", &self.name)
+            .field("reads", &self.reads)
+            .field("writes", &self.writes)
+            .finish()
+    }
+}
+
+/// Resources available to a pass while it is recorded
+pub struct PassContext<'a> {
+    encoder: &'a mut CommandEncoder,
+    surface: &'a TextureView,
+    transients: &'a HashMap<&'static str, Transient>,
+}
+
+impl<'a> PassContext<'a> {
+    /// The encoder to record this pass into
+    pub fn encoder(&mut self) -> &mut CommandEncoder {
+        self.encoder
+    }
+
+    /// The swapchain texture being presented
+    pub fn surface(&self) -> &'a TextureView {
+        self.surface
+    }
+
+    /// The lit HDR image
+    pub fn hdr(&self) -> &'a TextureView {
+        &gbuffer().hdr_view
+    }
+
+    /// The G-buffer being drawn to
+    pub fn gbuffer(&self) -> &'static GBuffer {
+        gbuffer()
+    }
+
+    /// Fetch a transient texture
+    ///
+    /// # Panics
+    ///
+    /// Panics if the texture was not declared on the graph
+    pub fn transient(&self, name: &str) -> &'a TextureView {
+        &self
+            .transients
+            .get(name)
+            .unwrap_or_else(|| panic!("Transient texture {name} was not declared"))
+            .view
+    }
+
+    /// Fetch the texture behind a transient
+    ///
+    /// # Panics
+    ///
+    /// Panics if the texture was not declared on the graph
+    pub fn transient_texture(&self, name: &str) -> &'a Texture {
+        &self
+            .transients
+            .get(name)
+            .unwrap_or_else(|| panic!("Transient texture {name} was not declared"))
+            .texture
+    }
+}
+
+struct Transient {
+    format: TextureFormat,
+    size: (u32, u32),
+    texture: Texture,
+    view: TextureView,
+}
+
+impl Transient {
+    fn acquire(format: TextureFormat, size: (u32, u32)) -> Self {
+        let mut pool = POOL.lock().unwrap();
+        if let Some(i) = pool
+            .iter()
+            .position(|t| t.format == format && t.size == size)
+        {
+            return pool.swap_remove(i);
+        }
+
+        let texture = device().create_texture(&TextureDescriptor {
+            label: Some("Transient"),
+            size: Extent3d {
+                width: size.0,
+                height: size.1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::RENDER_ATTACHMENT
+                | TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_SRC
+                | TextureUsages::COPY_DST,
+            view_formats: Default::default(),
+        });
+        let view = texture.create_view(&Default::default());
+        Self {
+            format,
+            size,
+            texture,
+            view,
+        }
+    }
+}
+
+/// A set of passes that are ordered by the resources they use
+#[derive(Debug, Default)]
+pub struct RenderGraph<'a> {
+    passes: Vec<Pass<'a>>,
+    transients: HashMap<&'static str, (TextureFormat, (u32, u32))>,
+}
+
+impl<'a> RenderGraph<'a> {
+    /// Create an empty render graph
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declare a transient texture that passes can use as [`Resource::Transient`]
+    pub fn transient(&mut self, name: &'static str, format: TextureFormat, size: (u32, u32)) {
+        let _ = self.transients.insert(name, (format, size));
+    }
+
+    /// Add a pass to this graph
+    pub fn add_pass(&mut self, pass: Pass<'a>) {
+        self.passes.push(pass);
+    }
+
+    /// The order passes will be recorded in
+    ///
+    /// # Panics
+    ///
+    /// Panics if the passes depend on each other in a cycle
+    pub fn order(&self) -> Vec<usize> {
+        let n = self.passes.len();
+        let mut deps = vec![BTreeSet::new(); n];
+
+        let mut resources: Vec<Resource> = vec![];
+        for pass in &self.passes {
+            for res in pass.reads.iter().chain(&pass.writes) {
+                if !resources.contains(res) {
+                    resources.push(*res);
+                }
+            }
+        }
+
+        for res in resources {
+            let reads = |i: &usize| self.passes[*i].reads.contains(&res);
+            let writes = |i: &usize| self.passes[*i].writes.contains(&res);
+
+            // producers then modifiers run in the order they were added
+            let chain: Vec<usize> = (0..n)
+                .filter(|i| writes(i) && !reads(i))
+                .chain((0..n).filter(|i| writes(i) && reads(i)))
+                .collect();
+            for pair in chain.windows(2) {
+                let _ = deps[pair[1]].insert(pair[0]);
+            }
+
+            // consumers run after the last write
+            if let Some(last) = chain.last() {
+                for i in (0..n).filter(|i| reads(i) && !writes(i)) {
+                    let _ = deps[i].insert(*last);
+                }
+            }
+        }
+
+        // pick the earliest added pass whose dependencies have run
+        let mut done = vec![false; n];
+        let mut order = Vec::with_capacity(n);
+        while order.len() < n {
+            let next = (0..n)
+                .find(|i| !done[*i] && deps[*i].iter().all(|d| done[*d]))
+                .unwrap_or_else(|| {
+                    let stuck: Vec<_> = (0..n)
+                        .filter(|i| !done[*i])
+                        .map(|i| self.passes[i].name)
+                        .collect();
+                    panic!("Render graph has a cycle between passes {stuck:?}")
+                });
+            done[next] = true;
+            order.push(next);
+        }
+        order
+    }
+
+    /// Record every pass into `encoder`
+    pub fn execute(self, encoder: &mut CommandEncoder, surface: &TextureView) {
+        self.execute_timed(encoder, surface, None);
+    }
+
+    /// Record every pass into `encoder`, writing GPU timestamps around each pass
+    pub(crate) fn execute_timed(
+        self,
+        encoder: &mut CommandEncoder,
+        surface: &TextureView,
+        mut timer: Option<&mut FrameTimer>,
+    ) {
+        let order = self.order();
+
+        let transients: HashMap<_, _> = self
+            .transients
+            .iter()
+            .map(|(name, (format, size))| (*name, Transient::acquire(*format, *size)))
+            .collect();
+
+        let mut passes: Vec<_> = self.passes.into_iter().map(Some).collect();
+        let mut ctx = PassContext {
+            encoder,
+            surface,
+            transients: &transients,
+        };
+        for i in order {
+            let pass = passes[i].take().unwrap();
+            let span = debug_span!("Render pass", name = pass.name);
+            let _e = span.enter();
+            if let Some(timer) = timer.as_mut() {
+                timer.start(ctx.encoder, pass.name);
+            }
+            (pass.exec)(&mut ctx);
+            if let Some(timer) = timer.as_mut() {
+                timer.end(ctx.encoder, pass.name);
+            }
+        }
+
+        POOL.lock().unwrap().extend(transients.into_values());
+    }
+}
@@ -14,59 +14,110 @@
 #![deny(unused_imports)]
 #![warn(variant_size_differences)]
 
+pub mod antialias;
+pub mod camera;
 pub mod context;
+pub mod debug;
+pub mod dynamic;
 mod frame;
+pub mod graph;
+pub mod material;
+pub mod particles;
+pub mod preprocess;
+pub mod ssao;
+pub mod stats;
+pub mod target;
+pub mod timestamps;
 pub mod tracing;
 pub mod transform;
 pub use transform::Transform;
 
 /// Contains asset loader functions for fetching GPU assets from disk formats
 pub mod load {
+    mod emitter;
+    mod lut;
     mod mesh;
     mod tex;
 
+    pub use emitter::*;
+    pub use lut::*;
     pub use mesh::*;
     pub use tex::*;
 }
 
 /// Contains render bundle creation methods for drawing geometry
 pub mod draw {
+    mod custom_mesh;
+    mod lod_mesh;
+    mod material_mesh;
     pub mod mesh;
     pub mod pixel_mesh;
     mod skymesh;
+    mod sprite;
+    mod static_batch;
+    mod terrain;
 
+    pub use custom_mesh::CustomMesh;
+    pub use lod_mesh::LodMesh;
+    pub use material_mesh::MaterialMesh;
     pub use mesh::Mesh;
     pub use pixel_mesh::PixelMesh;
     pub use skymesh::SkyMesh;
+    pub use sprite::{Facing, Sprite};
+    pub use static_batch::{BatchDraw, BatchMesh, StaticBatch, StaticBatchBuilder};
+    pub use terrain::{Terrain, TerrainDesc};
 }
 
 /// Containts render bundle creation methods for screen filters
 pub mod filters {
     mod display;
+    mod grade;
+    mod outline;
+    mod palette;
 
     pub use display::DisplayFilter;
+    pub use grade::GradeFilter;
+    pub use outline::{OutlineFilter, OutlineSettings};
+    pub use palette::PaletteFilter;
 }
 
 /// Contains render bundle creation methods for lights
 pub mod lights {
     mod ambient;
+    mod clustered;
+    mod light;
     mod sun;
 
     pub use ambient::AmbientLight;
+    pub use clustered::{ClusteredLights, PointLight};
+    pub use light::{Light, LightId};
     pub use sun::SunLight;
 }
 
 /// Types related to the render pipeline
 pub mod pipeline {
     pub mod ambient;
+    pub mod clustered;
+    pub mod compact;
+    pub mod custom;
     pub mod display;
+    pub mod emission;
     pub mod gbuffer;
+    pub mod grade;
+    pub mod indirect;
     pub mod mesh;
+    pub mod outline;
+    pub mod palette;
+    pub mod particles;
     pub mod simple;
     pub mod sky_box;
+    pub mod sprite;
     pub mod sun;
+    pub mod tonemap;
     pub mod vertex3d;
 
+    pub use compact::CompactVertex;
+    pub use custom::{GeomPipeline, GeomPipelineBuilder, GeomPipelineError};
     pub use gbuffer::GBuffer;
     pub use vertex3d::Vertex3D;
 
@@ -84,31 +135,40 @@ pub mod pipeline {
     };
 }
 
-pub use context::init;
+pub use context::{init, init_with_settings, Settings};
 pub use frame::*;
 
 /// Imports a shader file as a string.
 /// In debug mode this will read the shader from a file at runtime
 ///
 /// In release mode this will embed the shader into the binary at build time
+///
+/// The shader is run through the [`Preprocessor`](preprocess::Preprocessor), any extra
+/// arguments are defined as feature flags before processing.
+/// ```ignore
+/// let source = shader!("../shaders/mesh.wgsl", "EMISSIVE").unwrap();
+/// ```
 #[cfg(debug_assertions)]
 #[macro_export]
 macro_rules! shader {
-    ($path:expr) => {
-        std::fs::read_to_string(format!(
-            "{}/{}",
-            std::path::Path::new(file!()).parent().unwrap().display(),
-            $path
-        ))
-    };
+    ($path:expr $(, $define:expr)* $(,)?) => {{
+        let path = std::path::Path::new(file!()).parent().unwrap().join($path);
+        std::fs::read_to_string(&path).and_then(|source| {
+            $crate::preprocess::Preprocessor::new()
+                .base(path.parent().unwrap())
+                $(.define($define))*
+                .process(&source)
+        })
+    }};
 }
 
 #[cfg(not(debug_assertions))]
 #[allow(missing_docs)]
 #[macro_export]
 macro_rules! shader {
-    ($path:expr) => {{
-        let a: std::io::Result<_> = Ok(include_str!($path));
-        a
-    }};
+    ($path:expr $(, $define:expr)* $(,)?) => {
+        $crate::preprocess::Preprocessor::new()
+            $(.define($define))*
+            .process(include_str!($path))
+    };
 }
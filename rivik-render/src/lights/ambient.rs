@@ -4,13 +4,12 @@
  * obtain one at http://mozilla.org/MPL/2.0/.
  */
 
-use std::borrow::Borrow;
-
 use crate::{
     context::{device, gbuffer, queue},
     pipeline::{ambient, GBuffer},
+    stats::{self, DrawStats},
     transform::Spatial,
-    Transform,
+    Drawable, Transform,
 };
 use ultraviolet::Vec4;
 use wgpu::{
@@ -19,11 +18,14 @@ use wgpu::{
     RenderBundle, RenderBundleDescriptor, RenderBundleEncoderDescriptor,
 };
 
+use super::{Light, LightId};
+
 /// Convienience object for handling the uniform buffer of an ambient light
 pub struct AmbientLight {
     bundle: RenderBundle,
     buffer: Buffer,
     transform: Transform,
+    id: LightId,
 }
 
 impl Spatial for AmbientLight {
@@ -42,30 +44,46 @@ impl AmbientLight {
             contents: Vec4::new(r, g, b, 1.0).as_byte_slice(),
             usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
         });
-        let bundle = ambient_light(&buffer);
+        let bundle = ambient_light(&buffer, gbuffer());
         Self {
             bundle,
             buffer,
             transform: Transform::default(),
+            id: LightId::new(),
         }
     }
 
     /// Queues a write to the internal buffer for this ambient lights color
     pub fn set_color(&self, r: f32, g: f32, b: f32) {
         queue().write_buffer(&self.buffer, 0, Vec4::new(r, g, b, 1.0).as_byte_slice());
+        stats::record_buffer_write();
     }
 }
 
-impl Borrow<RenderBundle> for AmbientLight {
-    fn borrow(&self) -> &RenderBundle {
+impl Drawable for AmbientLight {
+    fn bundle(&self) -> &RenderBundle {
         &self.bundle
     }
+
+    fn stats(&self) -> DrawStats {
+        // a single fullscreen draw
+        DrawStats::new(1, 7)
+    }
+}
+
+impl Light for AmbientLight {
+    fn id(&self) -> LightId {
+        self.id
+    }
+
+    fn record(&self, gbuffer: &GBuffer) -> RenderBundle {
+        ambient_light(&self.buffer, gbuffer)
+    }
 }
 
 /// Generates a renderbundle for an ambient light
-fn ambient_light(uniform: &Buffer) -> RenderBundle {
+fn ambient_light(uniform: &Buffer, gbuffer: &GBuffer) -> RenderBundle {
     let device = device();
-    let gbuffer = gbuffer();
 
     let mut bundle = device.create_render_bundle_encoder(&RenderBundleEncoderDescriptor {
         label: None,
@@ -0,0 +1,248 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public License,
+ * v. 2.0. If a copy of the MPL was not distributed with this file, You can
+ * obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+use std::{cell::Cell, fmt};
+
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, Buffer, BufferDescriptor,
+    BufferUsages, CommandEncoder, RenderBundle, RenderBundleDescriptor,
+    RenderBundleEncoderDescriptor,
+};
+
+use crate::{
+    camera,
+    context::{device, gbuffer, queue},
+    pipeline::{
+        clustered::{self, CLUSTERS, INFO_SIZE, MAX_CLUSTER_LIGHTS, POINT_LIGHT_SIZE},
+        GBuffer,
+    },
+    stats::{self, DrawStats},
+    Drawable,
+};
+
+use super::{Light, LightId};
+
+/// Number of clusters culled by each compute workgroup
+const WORKGROUP_SIZE: u64 = 64;
+
+/// A light that shines in every direction from a point
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PointLight {
+    /// World space position of the light
+    pub position: [f32; 3],
+    /// Distance at which the light has faded out completely
+    pub radius: f32,
+    /// Color of the light
+    pub color: [f32; 3],
+    /// Brightness the color is scaled by
+    pub intensity: f32,
+}
+
+/// A set of point lights shaded in a single pass
+///
+/// Before shading, a compute pass bins the lights into clusters of screen tiles and depth
+/// slices so each pixel only loops over the lights that can reach it. This makes it cheap to
+/// draw hundreds of small lights, where drawing each one as its own [`Light`] would read the
+/// whole G-buffer once per light.
+///
+/// At most 64 lights affect a single cluster, any more are ignored.
+pub struct ClusteredLights {
+    bundle: RenderBundle,
+    lights: Buffer,
+    info: Buffer,
+    #[allow(dead_code)]
+    counts: Buffer,
+    #[allow(dead_code)]
+    indices: Buffer,
+    cull: BindGroup,
+    shade: BindGroup,
+    capacity: usize,
+    len: Cell<usize>,
+    id: LightId,
+}
+
+impl ClusteredLights {
+    /// Create an empty set that can hold up to `capacity` lights
+    pub fn new(capacity: usize) -> Self {
+        let device = device();
+        let capacity = capacity.max(1);
+
+        let storage = |label, size| {
+            device.create_buffer(&BufferDescriptor {
+                label: Some(label),
+                size,
+                usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+        };
+        let lights = storage("Point Lights", capacity as u64 * POINT_LIGHT_SIZE);
+        let counts = storage("Cluster Light Counts", CLUSTERS * 4);
+        let indices = storage("Cluster Lights", CLUSTERS * MAX_CLUSTER_LIGHTS * 4);
+        let info = device.create_buffer(&BufferDescriptor {
+            label: Some("Point Light Count"),
+            size: INFO_SIZE,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        stats::buffers_created(4);
+
+        let bind_group = |layout: &BindGroupLayout| {
+            device.create_bind_group(&BindGroupDescriptor {
+                label: Some("Clustered Lights"),
+                layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: lights.as_entire_binding(),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: info.as_entire_binding(),
+                    },
+                    BindGroupEntry {
+                        binding: 2,
+                        resource: counts.as_entire_binding(),
+                    },
+                    BindGroupEntry {
+                        binding: 3,
+                        resource: indices.as_entire_binding(),
+                    },
+                    BindGroupEntry {
+                        binding: 4,
+                        resource: camera::buffer().as_entire_binding(),
+                    },
+                ],
+            })
+        };
+        let cull = bind_group(clustered::cull_layout());
+        let shade = bind_group(clustered::layout());
+
+        let bundle = shade_bundle(&shade, gbuffer());
+
+        Self {
+            bundle,
+            lights,
+            info,
+            counts,
+            indices,
+            cull,
+            shade,
+            capacity,
+            len: Cell::new(0),
+            id: LightId::new(),
+        }
+    }
+
+    /// Replace the lights in this set
+    ///
+    /// Lights past the set's capacity are ignored.
+    pub fn set(&self, lights: &[PointLight]) {
+        let lights = &lights[..lights.len().min(self.capacity)];
+        let mut data = Vec::with_capacity(lights.len() * 8);
+        for light in lights {
+            let [x, y, z] = light.position;
+            let [r, g, b] = light.color;
+            let i = light.intensity;
+            data.extend_from_slice(&[x, y, z, light.radius, r * i, g * i, b * i, 0.0]);
+        }
+
+        let queue = queue();
+        if !data.is_empty() {
+            queue.write_buffer(&self.lights, 0, bytemuck::cast_slice(&data));
+            stats::record_buffer_write();
+        }
+        queue.write_buffer(
+            &self.info,
+            0,
+            bytemuck::cast_slice(&[lights.len() as u32, 0, 0, 0]),
+        );
+        stats::record_buffer_write();
+        self.len.set(lights.len());
+    }
+
+    /// Number of lights in this set
+    pub fn len(&self) -> usize {
+        self.len.get()
+    }
+
+    /// Check if this set has no lights
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Maximum number of lights this set can hold
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Bin the lights into clusters for the currently bound camera
+    pub(crate) fn cull(&self, encoder: &mut CommandEncoder) {
+        let mut cpass = encoder.begin_compute_pass(&Default::default());
+        cpass.set_pipeline(clustered::cull_pipeline());
+        cpass.set_bind_group(0, &self.cull, &[]);
+        cpass.dispatch_workgroups(
+            ((CLUSTERS + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE) as u32,
+            1,
+            1,
+        );
+    }
+}
+
+impl Light for ClusteredLights {
+    fn id(&self) -> LightId {
+        self.id
+    }
+
+    fn record(&self, gbuffer: &GBuffer) -> RenderBundle {
+        shade_bundle(&self.shade, gbuffer)
+    }
+
+    fn prepare(&self, encoder: &mut CommandEncoder) {
+        self.cull(encoder);
+    }
+}
+
+impl fmt::Debug for ClusteredLights {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ClusteredLights")
+            .field("len", &self.len.get())
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
+
+impl Drop for ClusteredLights {
+    fn drop(&mut self) {
+        stats::buffers_dropped(4);
+    }
+}
+
+impl Drawable for ClusteredLights {
+    fn bundle(&self) -> &RenderBundle {
+        &self.bundle
+    }
+
+    fn stats(&self) -> DrawStats {
+        // a single fullscreen draw
+        DrawStats::new(1, 7)
+    }
+}
+
+/// Record a render bundle that shades `gbuffer` with the lights bound in `shade`
+fn shade_bundle(shade: &BindGroup, gbuffer: &GBuffer) -> RenderBundle {
+    let mut bundle = device().create_render_bundle_encoder(&RenderBundleEncoderDescriptor {
+        label: Some("Clustered Lights"),
+        color_formats: &[Some(GBuffer::hdr_format())],
+        depth_stencil: None,
+        sample_count: 1,
+        multiview: None,
+    });
+    bundle.set_pipeline(clustered::pipeline());
+    bundle.set_bind_group(0, &gbuffer.bind_group, &[]);
+    bundle.set_bind_group(1, shade, &[]);
+    bundle.draw(0..7, 0..1);
+    bundle.finish(&RenderBundleDescriptor { label: None })
+}
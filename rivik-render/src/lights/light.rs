@@ -0,0 +1,39 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public License,
+ * v. 2.0. If a copy of the MPL was not distributed with this file, You can
+ * obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use wgpu::{CommandEncoder, RenderBundle};
+
+use crate::{pipeline::GBuffer, Drawable};
+
+/// Identifies a light so the bundles recorded for it can be reused
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LightId(u64);
+
+impl LightId {
+    /// Create an id no other light has
+    pub(crate) fn new() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// A light that can shade any G-buffer
+///
+/// The bundle returned by [`Drawable::bundle`] lights the frame's G-buffer, [`Light::record`] is
+/// used to light the G-buffer of a [`RenderTarget`](crate::target::RenderTarget). Targets record
+/// a light once and reuse the bundle while the light with the same [`Light::id`] is drawn.
+pub trait Light: Drawable {
+    /// Unique id of this light
+    fn id(&self) -> LightId;
+
+    /// Record a render bundle that lights `gbuffer`
+    fn record(&self, gbuffer: &GBuffer) -> RenderBundle;
+
+    /// Record work that has to be done before the light is drawn from the bound camera
+    fn prepare(&self, _encoder: &mut CommandEncoder) {}
+}
@@ -4,8 +4,6 @@
  * obtain one at http://mozilla.org/MPL/2.0/.
  */
 
-use std::borrow::Borrow;
-
 use mint::Vector3;
 use ultraviolet::Vec3;
 use wgpu::{
@@ -17,15 +15,19 @@ use wgpu::{
 use crate::{
     context::{device, gbuffer, queue},
     pipeline::{sun, GBuffer},
-    transform::{self, Spatial},
-    Transform,
+    stats::{self, DrawStats},
+    transform::Spatial,
+    Drawable, Transform,
 };
 
+use super::{Light, LightId};
+
 /// A Directional light that can be rendered to a frame
 pub struct SunLight {
     bundle: RenderBundle,
     buffer: Buffer,
     transform: Transform,
+    id: LightId,
 }
 
 impl Spatial for SunLight {
@@ -54,73 +56,81 @@ impl SunLight {
             usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
         });
 
-        // create render bundle for this light
-        let gbuffer = gbuffer();
-
-        let mut bundle = device.create_render_bundle_encoder(&RenderBundleEncoderDescriptor {
-            label: None,
-            color_formats: &[Some(GBuffer::hdr_format())],
-            depth_stencil: None,
-            sample_count: 1,
-            multiview: None,
-        });
-
-        let uniform_buffer = device.create_bind_group(&BindGroupDescriptor {
-            label: None,
-            layout: sun::layout(),
-            entries: &[BindGroupEntry {
-                binding: 0,
-                resource: BindingResource::Buffer(BufferBinding {
-                    buffer: &buffer,
-                    offset: 0,
-                    size: None,
-                }),
-            }],
-        });
-
         let transform = Transform::default();
-
-        let t_group = device.create_bind_group(&BindGroupDescriptor {
-            label: None,
-            layout: transform::layout(),
-            entries: &[BindGroupEntry {
-                binding: 0,
-                resource: BindingResource::Buffer(BufferBinding {
-                    buffer: transform.buffer(),
-                    offset: 0,
-                    size: None,
-                }),
-            }],
-        });
-
-        // record draw commands to render bundle
-        bundle.set_pipeline(sun::pipeline());
-        bundle.set_bind_group(0, &gbuffer.bind_group, &[]);
-        bundle.set_bind_group(1, &uniform_buffer, &[]);
-        bundle.set_bind_group(2, &t_group, &[]);
-        bundle.draw(0..7, 0..1);
-
-        let bundle = bundle.finish(&RenderBundleDescriptor { label: None });
+        let bundle = sun_light(&buffer, &transform, gbuffer());
         Self {
             buffer,
             transform,
             bundle,
+            id: LightId::new(),
         }
     }
 
     /// Set the direction of this sun light
     pub fn set_direction(&self, direction: Vec3) {
         queue().write_buffer(&self.buffer, 16, direction.as_byte_slice());
+        stats::record_buffer_write();
     }
 
     /// Set the color of this sun light
     pub fn set_color(&self, color: Vec3) {
         queue().write_buffer(&self.buffer, 0, color.as_byte_slice());
+        stats::record_buffer_write();
     }
 }
 
-impl Borrow<RenderBundle> for SunLight {
-    fn borrow(&self) -> &RenderBundle {
+impl Drawable for SunLight {
+    fn bundle(&self) -> &RenderBundle {
         &self.bundle
     }
+
+    fn stats(&self) -> DrawStats {
+        // a single fullscreen draw
+        DrawStats::new(1, 7)
+    }
+}
+
+impl Light for SunLight {
+    fn id(&self) -> LightId {
+        self.id
+    }
+
+    fn record(&self, gbuffer: &GBuffer) -> RenderBundle {
+        sun_light(&self.buffer, &self.transform, gbuffer)
+    }
+}
+
+/// Generates a renderbundle for a sun light
+fn sun_light(uniform: &Buffer, transform: &Transform, gbuffer: &GBuffer) -> RenderBundle {
+    let device = device();
+
+    let mut bundle = device.create_render_bundle_encoder(&RenderBundleEncoderDescriptor {
+        label: None,
+        color_formats: &[Some(GBuffer::hdr_format())],
+        depth_stencil: None,
+        sample_count: 1,
+        multiview: None,
+    });
+
+    let uniform_buffer = device.create_bind_group(&BindGroupDescriptor {
+        label: None,
+        layout: sun::layout(),
+        entries: &[BindGroupEntry {
+            binding: 0,
+            resource: BindingResource::Buffer(BufferBinding {
+                buffer: uniform,
+                offset: 0,
+                size: None,
+            }),
+        }],
+    });
+
+    // record draw commands to render bundle
+    bundle.set_pipeline(sun::pipeline());
+    bundle.set_bind_group(0, &gbuffer.bind_group, &[]);
+    bundle.set_bind_group(1, &uniform_buffer, &[]);
+    bundle.set_bind_group(2, transform.bind_group(), &[]);
+    bundle.draw(0..7, 0..1);
+
+    bundle.finish(&RenderBundleDescriptor { label: None })
 }
@@ -0,0 +1,220 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public License,
+ * v. 2.0. If a copy of the MPL was not distributed with this file, You can
+ * obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+use std::num::{ParseFloatError, ParseIntError};
+
+use assets::{
+    formats::misc::{ParseTxtError, Txt},
+    Format,
+};
+use snafu::{ensure, Backtrace, OptionExt, ResultExt, Snafu};
+
+use crate::particles::{EmitterDesc, ParticleBlend, MAX_KEYS};
+
+/// An error parsing an emitter description
+#[derive(Debug, Snafu)]
+pub enum EmitterError {
+    /// The file couldn't be read
+    #[snafu(display("Failed to read emitter"))]
+    ReadError {
+        /// The underlying error
+        #[snafu(backtrace)]
+        source: ParseTxtError,
+    },
+    /// A number couldn't be parsed
+    #[snafu(display("Invalid value '{value}' on line {line}"))]
+    InvalidValue {
+        /// Line of the value
+        line: usize,
+        /// The text that couldn't be parsed
+        value: String,
+        /// The underlying error
+        source: ParseFloatError,
+        /// Where the error happened
+        backtrace: Backtrace,
+    },
+    /// The particle count couldn't be parsed
+    #[snafu(display("Invalid particle count '{value}' on line {line}"))]
+    InvalidCount {
+        /// Line of the value
+        line: usize,
+        /// The text that couldn't be parsed
+        value: String,
+        /// The underlying error
+        source: ParseIntError,
+        /// Where the error happened
+        backtrace: Backtrace,
+    },
+    /// A line has too few values
+    #[snafu(display("Expected {expected} values on line {line}"))]
+    MissingValue {
+        /// Line with missing values
+        line: usize,
+        /// Number of values the key takes
+        expected: usize,
+        /// Where the error happened
+        backtrace: Backtrace,
+    },
+    /// A curve has more keys than are supported
+    #[snafu(display("'{key}' has more than {MAX_KEYS} keys on line {line}"))]
+    TooManyKeys {
+        /// Line of the extra key
+        line: usize,
+        /// The curve
+        key: String,
+        /// Where the error happened
+        backtrace: Backtrace,
+    },
+    /// A line starts with an unknown key
+    #[snafu(display("Unknown key '{key}' on line {line}"))]
+    UnknownKey {
+        /// Line of the key
+        line: usize,
+        /// The key
+        key: String,
+        /// Where the error happened
+        backtrace: Backtrace,
+    },
+}
+
+/// File format definition for particle emitter descriptions
+///
+/// Each line is a key followed by its values, `#` starts a comment and missing keys keep their
+/// [default](EmitterDesc::default) values. `size` and `color` add a key to their curve.
+/// ```text
+/// max_particles 2048
+/// rate 200
+/// lifetime 0.5 1.0      # min max seconds
+/// speed 2 5             # min max
+/// spread 30             # degrees
+/// gravity 0 -9.8 0
+/// drag 0.5
+/// size 0 0.05           # t size
+/// size 1 0
+/// color 0 1 0.8 0.3 1   # t r g b a
+/// color 1 1 0.2 0 0
+/// blend additive        # or alpha
+/// ```
+#[derive(Clone, Copy)]
+pub struct EmitterFormat;
+
+fn values<const N: usize>(
+    words: &mut std::str::SplitWhitespace,
+    line: usize,
+) -> Result<[f32; N], EmitterError> {
+    let mut out = [0.0; N];
+    for v in &mut out {
+        let value = words
+            .next()
+            .context(MissingValueSnafu { line, expected: N })?;
+        *v = value.parse().context(InvalidValueSnafu { line, value })?;
+    }
+    Ok(out)
+}
+
+impl Format for EmitterFormat {
+    type Output = EmitterDesc;
+    type Error = EmitterError;
+
+    fn parse(&self, path: &assets::Path) -> Result<Self::Output, Self::Error> {
+        let text = Txt.parse(path).context(ReadSnafu)?;
+
+        let mut desc = EmitterDesc::default();
+        let mut size = vec![];
+        let mut color = vec![];
+        for (i, line) in text.lines().enumerate() {
+            let line_num = i + 1;
+            let line = line.split('#').next().unwrap().trim();
+            let mut words = line.split_whitespace();
+            let key = match words.next() {
+                Some(key) => key,
+                None => continue,
+            };
+
+            match key {
+                "max_particles" => {
+                    let value = words.next().context(MissingValueSnafu {
+                        line: line_num,
+                        expected: 1usize,
+                    })?;
+                    desc.max_particles = value.parse().context(InvalidCountSnafu {
+                        line: line_num,
+                        value,
+                    })?;
+                }
+                "rate" => desc.rate = values::<1>(&mut words, line_num)?[0],
+                "lifetime" => {
+                    let [min, max] = values(&mut words, line_num)?;
+                    desc.lifetime = (min, max);
+                }
+                "speed" => {
+                    let [min, max] = values(&mut words, line_num)?;
+                    desc.speed = (min, max);
+                }
+                "spread" => desc.spread = values::<1>(&mut words, line_num)?[0].to_radians(),
+                "gravity" => desc.gravity = values(&mut words, line_num)?,
+                "drag" => desc.drag = values::<1>(&mut words, line_num)?[0],
+                "size" => {
+                    ensure!(
+                        size.len() < MAX_KEYS,
+                        TooManyKeysSnafu {
+                            line: line_num,
+                            key
+                        }
+                    );
+                    let [t, s] = values(&mut words, line_num)?;
+                    size.push((t, s));
+                }
+                "color" => {
+                    ensure!(
+                        color.len() < MAX_KEYS,
+                        TooManyKeysSnafu {
+                            line: line_num,
+                            key
+                        }
+                    );
+                    let [t, r, g, b, a] = values(&mut words, line_num)?;
+                    color.push((t, [r, g, b, a]));
+                }
+                "blend" => {
+                    desc.blend = match words.next() {
+                        Some("alpha") => ParticleBlend::Alpha,
+                        Some("additive") => ParticleBlend::Additive,
+                        Some(mode) => {
+                            return UnknownKeySnafu {
+                                line: line_num,
+                                key: mode,
+                            }
+                            .fail()
+                        }
+                        None => {
+                            return MissingValueSnafu {
+                                line: line_num,
+                                expected: 1usize,
+                            }
+                            .fail()
+                        }
+                    }
+                }
+                _ => {
+                    return UnknownKeySnafu {
+                        line: line_num,
+                        key,
+                    }
+                    .fail()
+                }
+            }
+        }
+
+        if !size.is_empty() {
+            desc.size = size;
+        }
+        if !color.is_empty() {
+            desc.color = color;
+        }
+        Ok(desc)
+    }
+}
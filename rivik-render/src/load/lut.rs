@@ -0,0 +1,85 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public License,
+ * v. 2.0. If a copy of the MPL was not distributed with this file, You can
+ * obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+use std::num::NonZeroU32;
+
+use assets::{formats::img::Lut, Format};
+use wgpu::{
+    Extent3d, ImageCopyTexture, ImageDataLayout, Origin3d, TextureAspect, TextureDescriptor,
+    TextureDimension, TextureFormat, TextureUsages,
+};
+
+use crate::{
+    context::{device, queue},
+    stats,
+};
+
+use super::LoadedTexture;
+
+/// Load a color lookup table and upload it to the GPU as a 3D texture
+///
+/// Wraps a LUT format such as [`CubeLut`](assets::formats::img::CubeLut) or
+/// [`StripLut`](assets::formats::img::StripLut).
+#[derive(Clone, Copy)]
+pub struct GpuLut<F>(pub F);
+
+impl<F> Format for GpuLut<F>
+where
+    F: Format<Output = Lut>,
+{
+    type Output = LoadedTexture;
+    type Error = F::Error;
+
+    fn parse(&self, r: &assets::Path) -> Result<Self::Output, Self::Error> {
+        let lut = self.0.parse(r)?;
+
+        let size = Extent3d {
+            width: lut.size,
+            height: lut.size,
+            depth_or_array_layers: lut.size,
+        };
+
+        let texture = device().create_texture(&TextureDescriptor {
+            label: Some(&r.to_string()),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D3,
+            format: TextureFormat::Rgba8Unorm,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: Default::default(),
+        });
+        let view = texture.create_view(&Default::default());
+
+        let data: Vec<u8> = lut
+            .data
+            .iter()
+            .flat_map(|c| {
+                let [r, g, b] = c.map(|v| (v.clamp(0.0, 1.0) * 255.0).round() as u8);
+                [r, g, b, 255]
+            })
+            .collect();
+
+        queue().write_texture(
+            ImageCopyTexture {
+                texture: &texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            &data,
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(4 * lut.size),
+                rows_per_image: NonZeroU32::new(lut.size),
+            },
+            size,
+        );
+        stats::record_texture_upload();
+        stats::texture_created();
+        Ok(LoadedTexture(texture, view))
+    }
+}
@@ -6,18 +6,28 @@
 
 //! Loads a mesh file into the GPU
 
-use std::ops::Deref;
+use std::{
+    ops::Deref,
+    sync::atomic::{AtomicU32, Ordering},
+};
 
 use assets::{
-    formats::{mesh::Mesh, FormatError},
+    formats::{
+        mesh::{Indexed, IndexedMesh, Mesh},
+        FormatError,
+    },
     load, AssetLoadError, Format, Path,
 };
+use tracing::debug;
 use wgpu::{
     util::{BufferInitDescriptor, DeviceExt},
-    Buffer, BufferUsages,
+    Buffer, BufferUsages, IndexFormat, RenderBundleEncoder,
 };
 
-use crate::context::device;
+use crate::{
+    context::device,
+    stats::{self, DrawStats},
+};
 
 /// Import format for a Mesh
 ///
@@ -56,22 +66,205 @@ where
     }
 }
 
+/// Import format for an indexed Mesh
+///
+/// Vertices are deduplicated on import and uploaded alongside an index buffer. Both buffers can
+/// also be bound as storage buffers for shaders that fetch their own vertices.
+pub struct GpuIndexedMesh<F, V>(pub F, pub V)
+where
+    F: Format<Output = Mesh<f32>> + Send + Sync,
+    F::Error: FormatError + Send + Sync,
+    V: Fn(&IndexedMesh<f32>) -> (Vec<u8>, Vec<u32>);
+
+impl<F, V> Format for GpuIndexedMesh<F, V>
+where
+    F: Format<Output = Mesh<f32>> + Clone + 'static + Send + Sync,
+    F::Error: FormatError + Send + Sync,
+    V: Fn(&IndexedMesh<f32>) -> (Vec<u8>, Vec<u32>),
+{
+    type Output = CountedBuffer;
+    type Error = AssetLoadError;
+
+    fn parse(&self, path: &Path) -> Result<Self::Output, Self::Error> {
+        // fetch the asset
+        let asset = load(path.to_string(), Indexed(self.0.clone()))?;
+        debug!("Fetching indexed asset: {path}");
+
+        let (vertices, indices) = (self.1)(&asset);
+
+        // upload buffers to GPU
+        let device = device();
+        Ok(CountedBuffer::indexed(
+            device.create_buffer_init(&BufferInitDescriptor {
+                label: Some(&path.to_string()),
+                contents: &vertices,
+                usage: BufferUsages::VERTEX | BufferUsages::STORAGE,
+            }),
+            device.create_buffer_init(&BufferInitDescriptor {
+                label: Some(&path.to_string()),
+                contents: bytemuck::cast_slice(&indices),
+                usage: BufferUsages::INDEX | BufferUsages::STORAGE,
+            }),
+            indices.len() as u32,
+        ))
+    }
+}
+
+/// Import format for a chain of indexed meshes at decreasing levels of detail
+///
+/// Use with [`Lods`](assets::formats::mesh::Lods) to simplify a mesh on import or
+/// [`BakedLods`](assets::formats::mesh::BakedLods) to load levels baked by the asset packer.
+pub struct GpuLodMesh<F, V>(pub F, pub V)
+where
+    F: Format<Output = Vec<IndexedMesh<f32>>> + Send + Sync,
+    F::Error: FormatError + Send + Sync,
+    V: Fn(&IndexedMesh<f32>) -> (Vec<u8>, Vec<u32>);
+
+impl<F, V> Format for GpuLodMesh<F, V>
+where
+    F: Format<Output = Vec<IndexedMesh<f32>>> + Clone + 'static + Send + Sync,
+    F::Error: FormatError + Send + Sync,
+    V: Fn(&IndexedMesh<f32>) -> (Vec<u8>, Vec<u32>),
+{
+    type Output = LodBuffers;
+    type Error = AssetLoadError;
+
+    fn parse(&self, path: &Path) -> Result<Self::Output, Self::Error> {
+        // fetch the asset
+        let lods = load(path.to_string(), self.0.clone())?;
+        debug!("Fetching {} levels of detail: {path}", lods.len());
+
+        // bound the most detailed level, the others are simplified from it
+        let points = lods
+            .first()
+            .map(|mesh| mesh.verts.as_slice())
+            .unwrap_or(&[]);
+        let (min, max) = points.iter().fold(
+            ([f32::INFINITY; 3], [f32::NEG_INFINITY; 3]),
+            |(min, max), p| {
+                (
+                    [min[0].min(p.x), min[1].min(p.y), min[2].min(p.z)],
+                    [max[0].max(p.x), max[1].max(p.y), max[2].max(p.z)],
+                )
+            },
+        );
+        let center = if points.is_empty() {
+            [0.0; 3]
+        } else {
+            [0, 1, 2].map(|i| (min[i] + max[i]) / 2.0)
+        };
+        let radius = points
+            .iter()
+            .map(|p| {
+                let d = [p.x - center[0], p.y - center[1], p.z - center[2]];
+                (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt()
+            })
+            .fold(0.0, f32::max);
+
+        // upload each level to the GPU
+        let device = device();
+        let levels = lods
+            .iter()
+            .map(|mesh| {
+                let (vertices, indices) = (self.1)(mesh);
+                CountedBuffer::indexed(
+                    device.create_buffer_init(&BufferInitDescriptor {
+                        label: Some(&path.to_string()),
+                        contents: &vertices,
+                        usage: BufferUsages::VERTEX | BufferUsages::STORAGE,
+                    }),
+                    device.create_buffer_init(&BufferInitDescriptor {
+                        label: Some(&path.to_string()),
+                        contents: bytemuck::cast_slice(&indices),
+                        usage: BufferUsages::INDEX | BufferUsages::STORAGE,
+                    }),
+                    indices.len() as u32,
+                )
+            })
+            .collect();
+
+        Ok(LodBuffers {
+            levels,
+            center,
+            radius,
+        })
+    }
+}
+
+/// The levels of detail of a mesh on the GPU, from the most to the least detailed
+pub struct LodBuffers {
+    /// Buffers of each level
+    pub levels: Vec<CountedBuffer>,
+    /// Center of the sphere bounding the mesh
+    pub center: [f32; 3],
+    /// Radius of the sphere bounding the mesh
+    pub radius: f32,
+}
+
 /// A GPU buffer with a length
+///
+/// If the buffer has an index buffer the length is the number of indices
 pub struct CountedBuffer {
-    len: u32,
+    len: AtomicU32,
     buffer: Buffer,
+    index: Option<Buffer>,
 }
 
 impl CountedBuffer {
     /// Creates a new `CountedBuffer`
     pub fn new(buf: Buffer, len: u32) -> Self {
-        Self { len, buffer: buf }
+        stats::buffers_created(1);
+        Self {
+            len: AtomicU32::new(len),
+            buffer: buf,
+            index: None,
+        }
+    }
+
+    /// Creates a new `CountedBuffer` that is drawn using a `u32` index buffer
+    pub fn indexed(buf: Buffer, index: Buffer, len: u32) -> Self {
+        stats::buffers_created(2);
+        Self {
+            len: AtomicU32::new(len),
+            buffer: buf,
+            index: Some(index),
+        }
     }
 
     /// Get the length of this buffer
     #[allow(clippy::len_without_is_empty)]
     pub fn len(&self) -> u32 {
-        self.len
+        self.len.load(Ordering::Relaxed)
+    }
+
+    /// Change the number of vertices or indices drawn
+    ///
+    /// Bundles that already draw this buffer keep drawing the old length
+    pub(crate) fn set_len(&self, len: u32) {
+        self.len.store(len, Ordering::Relaxed);
+    }
+
+    /// Get the index buffer of this buffer if it has one
+    pub fn index_buffer(&self) -> Option<&Buffer> {
+        self.index.as_ref()
+    }
+
+    /// The draw call recorded by [`CountedBuffer::draw`]
+    pub fn stats(&self) -> DrawStats {
+        DrawStats::new(1, self.len())
+    }
+
+    /// Record the commands to draw this buffer
+    pub fn draw<'a>(&'a self, bundle: &mut RenderBundleEncoder<'a>) {
+        let len = self.len();
+        bundle.set_vertex_buffer(0, self.buffer.slice(..));
+        match &self.index {
+            Some(index) => {
+                bundle.set_index_buffer(index.slice(..), IndexFormat::Uint32);
+                bundle.draw_indexed(0..len, 0, 0..1);
+            }
+            None => bundle.draw(0..len, 0..1),
+        }
     }
 }
 
@@ -86,5 +279,6 @@ impl Deref for CountedBuffer {
 impl Drop for CountedBuffer {
     fn drop(&mut self) {
         println!("Dropping a GPU vertex buffer");
+        stats::buffers_dropped(1 + self.index.is_some() as u64);
     }
 }
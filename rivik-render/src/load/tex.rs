@@ -16,13 +16,25 @@ use wgpu::{
     TextureUsages, TextureView, TextureViewDescriptor,
 };
 
-use crate::context::{device, queue};
+use crate::{
+    context::{device, queue},
+    stats,
+};
 
 /// Load a texture and upload it to the GPU
 pub struct GpuTexture(pub ImageFormat);
 
+/// A texture uploaded by [`GpuTexture`] and a view of it
+pub struct LoadedTexture(pub Texture, pub TextureView);
+
+impl Drop for LoadedTexture {
+    fn drop(&mut self) {
+        stats::texture_dropped();
+    }
+}
+
 impl Format for GpuTexture {
-    type Output = (Texture, TextureView);
+    type Output = LoadedTexture;
     type Error = ImageParseError;
 
     fn parse(&self, r: &assets::Path) -> Result<Self::Output, Self::Error> {
@@ -77,6 +89,8 @@ impl Format for GpuTexture {
             },
             texture_size,
         );
-        Ok((texture, view))
+        stats::record_texture_upload();
+        stats::texture_created();
+        Ok(LoadedTexture(texture, view))
     }
 }
@@ -0,0 +1,550 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public License,
+ * v. 2.0. If a copy of the MPL was not distributed with this file, You can
+ * obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+//! Materials describe how geometry is shaded into the G-buffer
+//!
+//! A [`Material`] provides a shader, the layout of its vertices and a bind group holding its
+//! textures and uniform parameters. The pipeline for a material is created the first time the
+//! material type is used and is cached by type, so adding a custom surface shader only needs a
+//! new `Material` implementation and a [`MaterialMesh`](crate::draw::MaterialMesh).
+//!
+//! Material shaders have their bind group at group 0 and the object's transform at group 1.
+
+use std::{
+    any::{type_name, TypeId},
+    collections::HashMap,
+    num::NonZeroU64,
+    rc::Rc,
+    sync::{Arc, RwLock},
+};
+
+use once_cell::{sync::Lazy, unsync::OnceCell};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    AddressMode, BindGroup, BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry,
+    BindingResource, BufferUsages, Extent3d, FilterMode, RenderBundle, RenderBundleDescriptor,
+    RenderBundleEncoderDescriptor, RenderPipeline, Sampler, TextureDescriptor, TextureDimension,
+    TextureFormat, TextureUsages, TextureView, VertexBufferLayout,
+};
+
+use crate::{
+    context::{device, gbuffer},
+    load::{CountedBuffer, LoadedTexture},
+    pipeline::{mesh::MeshVertex, GBuffer, Vertex3D},
+    shader, transform, Transform,
+};
+
+static LAYOUTS: Lazy<RwLock<HashMap<TypeId, &'static BindGroupLayout>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+static PIPELINES: Lazy<RwLock<HashMap<TypeId, &'static RenderPipeline>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// Bound in place of the emissive texture of materials that don't glow
+static NO_EMISSION: Lazy<TextureView> = Lazy::new(|| {
+    // new textures are zeroed, which is black
+    device()
+        .create_texture(&TextureDescriptor {
+            label: Some("No Emission"),
+            size: Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8UnormSrgb,
+            usage: TextureUsages::TEXTURE_BINDING,
+            view_formats: Default::default(),
+        })
+        .create_view(&Default::default())
+});
+
+/// Repeating nearest neighbour sampler shared by the builtin materials
+static NEAREST_SAMPLER: Lazy<Sampler> = Lazy::new(|| {
+    device().create_sampler(&wgpu::SamplerDescriptor {
+        mag_filter: FilterMode::Nearest,
+        min_filter: FilterMode::Nearest,
+        mipmap_filter: FilterMode::Nearest,
+        address_mode_u: AddressMode::Repeat,
+        address_mode_v: AddressMode::Repeat,
+        ..Default::default()
+    })
+});
+
+/// Describes the shader and inputs used to draw geometry into the G-buffer
+pub trait Material: 'static {
+    /// WGSL source of this material's shader
+    fn shader() -> String;
+
+    /// Layout of a single vertex in the meshes drawn with this material
+    fn vertex_layout() -> VertexBufferLayout<'static>;
+
+    /// Entries of the bind group holding this material's textures and uniforms
+    fn layout_entries() -> Vec<BindGroupLayoutEntry>;
+
+    /// Fetch the bind group for this material
+    ///
+    /// This is called every time a bundle is recorded with the material, which happens again at
+    /// runtime for dynamic meshes and terrain, so the bind group should be created on the first
+    /// call and reused afterwards.
+    fn bind_group(&self, layout: &BindGroupLayout) -> &BindGroup;
+
+    /// Should geometry drawn with this material write to the depth buffer
+    fn depth_write() -> bool {
+        true
+    }
+
+    /// Can meshes drawn with this material be shown in [debug views](crate::debug)
+    ///
+    /// Debug views read a `vec3<f32>` position from location 0 of the vertex layout
+    fn debug_views() -> bool {
+        true
+    }
+}
+
+/// Fetch the bind group layout of a material type
+pub fn layout<M: Material>() -> &'static BindGroupLayout {
+    let id = TypeId::of::<M>();
+    if let Some(layout) = LAYOUTS.read().unwrap().get(&id).copied() {
+        return layout;
+    }
+
+    let layout = device().create_bind_group_layout(&BindGroupLayoutDescriptor {
+        label: Some(type_name::<M>()),
+        entries: &M::layout_entries(),
+    });
+    // layouts live as long as the renderer, the same as the other pipeline statics
+    let layout: &'static BindGroupLayout = Box::leak(Box::new(layout));
+    *LAYOUTS.write().unwrap().entry(id).or_insert(layout)
+}
+
+/// Fetch the render pipeline of a material type
+pub fn pipeline<M: Material>() -> &'static RenderPipeline {
+    let id = TypeId::of::<M>();
+    if let Some(pipe) = PIPELINES.read().unwrap().get(&id).copied() {
+        return pipe;
+    }
+
+    let shader = M::shader();
+    let bind_groups = [layout::<M>(), transform::layout()];
+    let pipe = if M::depth_write() {
+        GBuffer::geom_pipeline(&shader, &bind_groups, M::vertex_layout())
+    } else {
+        GBuffer::geom_no_depth_pipeline(&shader, &bind_groups, M::vertex_layout())
+    };
+    let pipe: &'static RenderPipeline = Box::leak(Box::new(pipe));
+    *PIPELINES.write().unwrap().entry(id).or_insert(pipe)
+}
+
+/// Record a render bundle that draws `mesh` into the G-buffer using `material`
+pub fn record<M: Material>(
+    mesh: &CountedBuffer,
+    material: &M,
+    transform: &Transform,
+) -> RenderBundle {
+    let device = device();
+    let mut bundle = device.create_render_bundle_encoder(&RenderBundleEncoderDescriptor {
+        label: None,
+        color_formats: GBuffer::color_formats(),
+        depth_stencil: GBuffer::depth_format(),
+        sample_count: gbuffer().sample_count(),
+        multiview: None,
+    });
+
+    // start recording render commands
+    bundle.set_pipeline(pipeline::<M>());
+    bundle.set_bind_group(0, material.bind_group(layout::<M>()), &[]);
+    bundle.set_bind_group(1, transform.bind_group(), &[]);
+    mesh.draw(&mut bundle);
+    bundle.finish(&RenderBundleDescriptor {
+        label: Some(type_name::<M>()),
+    })
+}
+
+/// Layout entries for a material that samples a single texture
+pub fn texture_layout_entries() -> Vec<BindGroupLayoutEntry> {
+    vec![
+        BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::NonFiltering),
+            count: None,
+        },
+        BindGroupLayoutEntry {
+            binding: 1,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        },
+    ]
+}
+
+/// Create a bind group matching [`texture_layout_entries`]
+pub fn texture_bind_group(layout: &BindGroupLayout, tex: &TextureView) -> BindGroup {
+    let device = device();
+
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: None,
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::Sampler(&NEAREST_SAMPLER),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: BindingResource::TextureView(tex),
+            },
+        ],
+    })
+}
+
+/// Layout entries for a material with a diffuse and an emissive texture
+///
+/// Binding 2 is the emissive texture and binding 3 a uniform holding the emissive intensity
+pub fn emissive_layout_entries() -> Vec<BindGroupLayoutEntry> {
+    let mut entries = texture_layout_entries();
+    entries.push(BindGroupLayoutEntry {
+        binding: 2,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count: None,
+    });
+    entries.push(BindGroupLayoutEntry {
+        binding: 3,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: NonZeroU64::new(16),
+        },
+        count: None,
+    });
+    entries
+}
+
+/// Create a bind group matching [`emissive_layout_entries`]
+///
+/// Without an emissive texture nothing is emitted
+pub fn emissive_bind_group(
+    layout: &BindGroupLayout,
+    tex: &TextureView,
+    emissive: Option<&TextureView>,
+    intensity: f32,
+) -> BindGroup {
+    let device = device();
+    let uniform = device.create_buffer_init(&BufferInitDescriptor {
+        label: Some("Emissive Intensity"),
+        contents: bytemuck::cast_slice(&[intensity, 0.0, 0.0, 0.0]),
+        usage: BufferUsages::UNIFORM,
+    });
+
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: None,
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::Sampler(&NEAREST_SAMPLER),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: BindingResource::TextureView(tex),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: BindingResource::TextureView(emissive.unwrap_or(&NO_EMISSION)),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: uniform.as_entire_binding(),
+            },
+        ],
+    })
+}
+
+/// A Phong shaded material with a diffuse texture and an optional emissive texture
+pub struct StandardMaterial {
+    tex: Rc<Arc<LoadedTexture>>,
+    emissive: Option<(Rc<Arc<LoadedTexture>>, f32)>,
+    /// created the first time the material is recorded
+    group: OnceCell<BindGroup>,
+}
+
+impl StandardMaterial {
+    /// Create a new material from a diffuse texture
+    pub fn new(tex: Rc<Arc<LoadedTexture>>) -> Self {
+        Self {
+            tex,
+            emissive: None,
+            group: OnceCell::new(),
+        }
+    }
+
+    /// Make this material glow
+    ///
+    /// The emissive texture is written to the G-buffer's luminance attachment scaled by
+    /// `intensity`, which ambient lights add to the lit color regardless of the diffuse color.
+    /// Intensities above 1 are brighter than any light and will bloom.
+    pub fn emissive(self, tex: Rc<Arc<LoadedTexture>>, intensity: f32) -> Self {
+        Self {
+            emissive: Some((tex, intensity)),
+            group: OnceCell::new(),
+            ..self
+        }
+    }
+}
+
+impl Material for StandardMaterial {
+    fn shader() -> String {
+        shader!("shaders/mesh.wgsl").unwrap()
+    }
+
+    fn vertex_layout() -> VertexBufferLayout<'static> {
+        MeshVertex::LAYOUT
+    }
+
+    fn layout_entries() -> Vec<BindGroupLayoutEntry> {
+        emissive_layout_entries()
+    }
+
+    fn bind_group(&self, layout: &BindGroupLayout) -> &BindGroup {
+        self.group.get_or_init(|| {
+            let (emissive, intensity) = match &self.emissive {
+                Some((tex, intensity)) => (Some(&tex.1), *intensity),
+                None => (None, 0.0),
+            };
+            emissive_bind_group(layout, &self.tex.1, emissive, intensity)
+        })
+    }
+}
+
+/// A material that snaps lighting to the texels of its diffuse texture
+///
+/// Meshes drawn with this material need a [`Vertex3D`] vertex buffer
+pub struct PixelMaterial {
+    tex: Rc<Arc<LoadedTexture>>,
+    emissive: Option<(Rc<Arc<LoadedTexture>>, f32)>,
+    /// created the first time the material is recorded
+    group: OnceCell<BindGroup>,
+}
+
+impl PixelMaterial {
+    /// Create a new material from a diffuse texture
+    pub fn new(tex: Rc<Arc<LoadedTexture>>) -> Self {
+        Self {
+            tex,
+            emissive: None,
+            group: OnceCell::new(),
+        }
+    }
+
+    /// Make this material glow, see [`StandardMaterial::emissive`]
+    pub fn emissive(self, tex: Rc<Arc<LoadedTexture>>, intensity: f32) -> Self {
+        Self {
+            emissive: Some((tex, intensity)),
+            group: OnceCell::new(),
+            ..self
+        }
+    }
+}
+
+impl Material for PixelMaterial {
+    fn shader() -> String {
+        shader!("shaders/simple3d.wgsl").unwrap()
+    }
+
+    fn vertex_layout() -> VertexBufferLayout<'static> {
+        Vertex3D::LAYOUT
+    }
+
+    fn layout_entries() -> Vec<BindGroupLayoutEntry> {
+        emissive_layout_entries()
+    }
+
+    fn bind_group(&self, layout: &BindGroupLayout) -> &BindGroup {
+        self.group.get_or_init(|| {
+            let (emissive, intensity) = match &self.emissive {
+                Some((tex, intensity)) => (Some(&tex.1), *intensity),
+                None => (None, 0.0),
+            };
+            emissive_bind_group(layout, &self.tex.1, emissive, intensity)
+        })
+    }
+}
+
+/// An unlit material drawn infinitely far from the camera
+pub struct SkyMaterial {
+    tex: Rc<Arc<LoadedTexture>>,
+    /// created the first time the material is recorded
+    group: OnceCell<BindGroup>,
+}
+
+impl SkyMaterial {
+    /// Create a new material from a sky texture
+    pub fn new(tex: Rc<Arc<LoadedTexture>>) -> Self {
+        Self {
+            tex,
+            group: OnceCell::new(),
+        }
+    }
+}
+
+impl Material for SkyMaterial {
+    fn shader() -> String {
+        shader!("shaders/skybox.wgsl").unwrap()
+    }
+
+    fn vertex_layout() -> VertexBufferLayout<'static> {
+        MeshVertex::LAYOUT
+    }
+
+    fn layout_entries() -> Vec<BindGroupLayoutEntry> {
+        texture_layout_entries()
+    }
+
+    fn bind_group(&self, layout: &BindGroupLayout) -> &BindGroup {
+        self.group
+            .get_or_init(|| texture_bind_group(layout, &self.tex.1))
+    }
+
+    fn depth_write() -> bool {
+        false
+    }
+}
+
+/// A material blending four tiled textures with a splat map, used by
+/// [`Terrain`](crate::draw::Terrain)
+///
+/// The red, green, blue and alpha channels of the splat map are the weights of each layer,
+/// which are normalized so they don't need to add up to one. Unpainted areas show the first
+/// layer.
+pub struct TerrainMaterial {
+    splat: Rc<Arc<LoadedTexture>>,
+    layers: [Rc<Arc<LoadedTexture>>; 4],
+    tiling: f32,
+    /// created the first time the material is recorded
+    group: OnceCell<BindGroup>,
+}
+
+impl TerrainMaterial {
+    /// Create a terrain material from a splat map and up to four layers
+    ///
+    /// Missing layers repeat the last layer and extra layers are ignored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no layers are given
+    pub fn new(splat: Rc<Arc<LoadedTexture>>, layers: &[Rc<Arc<LoadedTexture>>]) -> Self {
+        let last = layers.last().expect("Terrain needs at least one layer");
+        let layer = |i: usize| layers.get(i).unwrap_or(last).clone();
+        Self {
+            splat,
+            layers: [layer(0), layer(1), layer(2), layer(3)],
+            tiling: 1.0,
+            group: OnceCell::new(),
+        }
+    }
+
+    /// Set how many times the layers repeat across the terrain
+    pub fn tiling(self, tiling: f32) -> Self {
+        Self {
+            tiling,
+            group: OnceCell::new(),
+            ..self
+        }
+    }
+}
+
+impl Material for TerrainMaterial {
+    fn shader() -> String {
+        shader!("shaders/terrain.wgsl").unwrap()
+    }
+
+    fn vertex_layout() -> VertexBufferLayout<'static> {
+        MeshVertex::LAYOUT
+    }
+
+    fn layout_entries() -> Vec<BindGroupLayoutEntry> {
+        let texture = |binding| BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        vec![
+            BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::NonFiltering),
+                count: None,
+            },
+            texture(1),
+            texture(2),
+            texture(3),
+            texture(4),
+            texture(5),
+            BindGroupLayoutEntry {
+                binding: 6,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: NonZeroU64::new(16),
+                },
+                count: None,
+            },
+        ]
+    }
+
+    fn bind_group(&self, layout: &BindGroupLayout) -> &BindGroup {
+        self.group.get_or_init(|| {
+            let device = device();
+            let uniform = device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("Terrain Tiling"),
+                contents: bytemuck::cast_slice(&[self.tiling, 0.0, 0.0, 0.0]),
+                usage: BufferUsages::UNIFORM,
+            });
+
+            let texture = |binding, tex: &Rc<Arc<LoadedTexture>>| wgpu::BindGroupEntry {
+                binding,
+                resource: BindingResource::TextureView(&tex.1),
+            };
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Terrain"),
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::Sampler(&NEAREST_SAMPLER),
+                    },
+                    texture(1, &self.splat),
+                    texture(2, &self.layers[0]),
+                    texture(3, &self.layers[1]),
+                    texture(4, &self.layers[2]),
+                    texture(5, &self.layers[3]),
+                    wgpu::BindGroupEntry {
+                        binding: 6,
+                        resource: uniform.as_entire_binding(),
+                    },
+                ],
+            })
+        })
+    }
+}
@@ -0,0 +1,329 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public License,
+ * v. 2.0. If a copy of the MPL was not distributed with this file, You can
+ * obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+//! GPU particle systems
+//!
+//! A [`ParticleEmitter`] keeps its particles in a GPU buffer. Spawning and simulation run in a
+//! compute shader and the particles are drawn as camera facing billboards into the HDR buffer
+//! after lighting, so they are not lit themselves. Emitters are drawn with
+//! [`Frame::draw_particles`](crate::Frame::draw_particles) and simulated once per frame after
+//! [`ParticleEmitter::update`] is called.
+//!
+//! Emitters are described by an [`EmitterDesc`] which can be loaded with
+//! [`EmitterFormat`](crate::load::EmitterFormat).
+
+use std::{cell::Cell, fmt};
+
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroup, BindGroupDescriptor, BindGroupEntry, Buffer, BufferDescriptor, BufferUsages,
+    ComputePass, RenderBundle, RenderBundleDescriptor, RenderBundleEncoderDescriptor,
+};
+
+use crate::{
+    context::{device, gbuffer, queue},
+    pipeline::{
+        self,
+        particles::{EMITTER_SIZE, PARTICLE_SIZE},
+        GBuffer,
+    },
+    stats::{self, DrawStats},
+    transform::Spatial,
+    Drawable, Transform,
+};
+
+/// Maximum number of keys in a color or size curve
+pub const MAX_KEYS: usize = 4;
+
+/// Number of particles simulated by each compute workgroup
+const WORKGROUP_SIZE: u32 = 64;
+
+/// How particles are blended into the HDR buffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ParticleBlend {
+    /// Particles add light to the scene, for sparks and magic
+    #[default]
+    Additive,
+    /// Particles cover the scene behind them, for smoke
+    Alpha,
+}
+
+/// Description of how an emitter spawns and simulates particles
+///
+/// Curves are lists of `(t, value)` keys sorted by `t`, where `t` runs from 0 when a particle
+/// spawns to 1 when it dies. Only the first [`MAX_KEYS`] keys are used.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmitterDesc {
+    /// Maximum number of particles alive at once
+    pub max_particles: u32,
+    /// Particles spawned per second
+    pub rate: f32,
+    /// Range of particle lifetimes in seconds
+    pub lifetime: (f32, f32),
+    /// Range of initial particle speeds
+    pub speed: (f32, f32),
+    /// Angle in radians of the cone around the emitter's Y axis particles are spawned in
+    pub spread: f32,
+    /// Acceleration applied to every particle
+    pub gravity: [f32; 3],
+    /// Fraction of velocity lost per second
+    pub drag: f32,
+    /// Particle size over its lifetime
+    pub size: Vec<(f32, f32)>,
+    /// Particle color over its lifetime, alpha fades the particle out
+    pub color: Vec<(f32, [f32; 4])>,
+    /// How particles are blended into the scene
+    pub blend: ParticleBlend,
+}
+
+impl Default for EmitterDesc {
+    fn default() -> Self {
+        Self {
+            max_particles: 1024,
+            rate: 64.0,
+            lifetime: (1.0, 2.0),
+            speed: (1.0, 2.0),
+            spread: 0.5,
+            gravity: [0.0, -9.8, 0.0],
+            drag: 0.0,
+            size: vec![(0.0, 0.05)],
+            color: vec![(0.0, [1.0, 1.0, 1.0, 1.0])],
+            blend: ParticleBlend::Additive,
+        }
+    }
+}
+
+impl EmitterDesc {
+    /// Pack this description into the emitter uniform
+    fn uniform(&self, model: &[f32], dt: f32, seed: f32) -> Vec<f32> {
+        let mut data = Vec::with_capacity(EMITTER_SIZE as usize / 4);
+        data.extend_from_slice(model);
+
+        let [x, y, z] = self.gravity;
+        data.extend_from_slice(&[x, y, z, self.drag]);
+        data.extend_from_slice(&[self.lifetime.0, self.lifetime.1, self.speed.0, self.speed.1]);
+        data.extend_from_slice(&[self.spread, dt, seed, self.max_particles as f32]);
+
+        // an empty curve is treated as a single default key
+        let size = if self.size.is_empty() {
+            &[(0.0, 0.05)][..]
+        } else {
+            &self.size[..self.size.len().min(MAX_KEYS)]
+        };
+        let color = if self.color.is_empty() {
+            &[(0.0, [1.0; 4])][..]
+        } else {
+            &self.color[..self.color.len().min(MAX_KEYS)]
+        };
+        data.extend_from_slice(&[color.len() as f32, size.len() as f32, 0.0, 0.0]);
+
+        let mut color_times = [0.0; MAX_KEYS];
+        let mut size_times = [0.0; MAX_KEYS];
+        let mut sizes = [0.0; MAX_KEYS];
+        let mut colors = [0.0; MAX_KEYS * 4];
+        for (i, (t, c)) in color.iter().enumerate() {
+            color_times[i] = *t;
+            colors[i * 4..i * 4 + 4].copy_from_slice(c);
+        }
+        for (i, (t, s)) in size.iter().enumerate() {
+            size_times[i] = *t;
+            sizes[i] = *s;
+        }
+        data.extend_from_slice(&color_times);
+        data.extend_from_slice(&size_times);
+        data.extend_from_slice(&sizes);
+        data.extend_from_slice(&colors);
+        data
+    }
+}
+
+/// A particle system attached to a transform
+pub struct ParticleEmitter {
+    desc: EmitterDesc,
+    transform: Transform,
+    #[allow(dead_code)]
+    particles: Buffer,
+    uniform: Buffer,
+    spawn: Buffer,
+    sim: BindGroup,
+    bundle: RenderBundle,
+    /// fractional particles carried over to the next update
+    spawn_accum: Cell<f32>,
+    bursts: Cell<u32>,
+    time: Cell<f32>,
+    /// set when the emitter has been updated but not simulated
+    pending: Cell<bool>,
+}
+
+impl ParticleEmitter {
+    /// Create an emitter and its particle buffer
+    pub fn new(desc: EmitterDesc) -> Self {
+        let device = device();
+        let transform = Transform::default();
+
+        let particles = device.create_buffer(&BufferDescriptor {
+            label: Some("Particles"),
+            size: desc.max_particles.max(1) as u64 * PARTICLE_SIZE,
+            usage: BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let model = transform.model();
+        let uniform = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Emitter"),
+            contents: bytemuck::cast_slice(&desc.uniform(model.as_slice(), 0.0, 0.0)),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let spawn = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Particle Spawn Count"),
+            contents: bytemuck::bytes_of(&0i32),
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+        });
+        stats::buffers_created(3);
+
+        let sim = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Particle Simulation"),
+            layout: pipeline::particles::sim_layout(),
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: particles.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: uniform.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: spawn.as_entire_binding(),
+                },
+            ],
+        });
+
+        let draw = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Particles"),
+            layout: pipeline::particles::layout(),
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: particles.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: uniform.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut bundle = device.create_render_bundle_encoder(&RenderBundleEncoderDescriptor {
+            label: Some("Particles"),
+            color_formats: &[Some(GBuffer::hdr_format())],
+            depth_stencil: None,
+            sample_count: gbuffer().sample_count(),
+            multiview: None,
+        });
+        bundle.set_pipeline(pipeline::particles::pipeline(desc.blend));
+        bundle.set_bind_group(0, &gbuffer().bind_group, &[]);
+        bundle.set_bind_group(1, transform.bind_group(), &[]);
+        bundle.set_bind_group(2, &draw, &[]);
+        bundle.draw(0..6, 0..desc.max_particles);
+        let bundle = bundle.finish(&RenderBundleDescriptor { label: None });
+
+        Self {
+            desc,
+            transform,
+            particles,
+            uniform,
+            spawn,
+            sim,
+            bundle,
+            spawn_accum: Cell::new(0.0),
+            bursts: Cell::new(0),
+            time: Cell::new(0.0),
+            pending: Cell::new(false),
+        }
+    }
+
+    /// The description this emitter was created from
+    pub fn desc(&self) -> &EmitterDesc {
+        &self.desc
+    }
+
+    /// Spawn `count` extra particles on the next update
+    pub fn burst(&self, count: u32) {
+        self.bursts.set(self.bursts.get() + count);
+    }
+
+    /// Advance this emitter by `dt` seconds when the next frame is drawn
+    ///
+    /// Particles are spawned at the emitter's current transform, so update the transform first.
+    pub fn update(&self, dt: f32) {
+        let spawn = self.spawn_accum.get() + self.desc.rate * dt;
+        self.spawn_accum.set(spawn.fract());
+        let count = spawn as u32 + self.bursts.replace(0);
+
+        let time = self.time.get() + dt;
+        self.time.set(time);
+
+        let model = self.transform.model();
+        let data = self.desc.uniform(model.as_slice(), dt, time);
+        let queue = queue();
+        queue.write_buffer(&self.uniform, 0, bytemuck::cast_slice(&data));
+        queue.write_buffer(
+            &self.spawn,
+            0,
+            bytemuck::bytes_of(&(count.min(self.desc.max_particles) as i32)),
+        );
+        stats::record_buffer_write();
+        stats::record_buffer_write();
+        self.pending.set(true);
+    }
+
+    /// Record the simulation step queued by [`ParticleEmitter::update`]
+    pub(crate) fn simulate<'a>(&'a self, cpass: &mut ComputePass<'a>) {
+        if !self.pending.replace(false) {
+            return;
+        }
+        cpass.set_pipeline(pipeline::particles::sim_pipeline());
+        cpass.set_bind_group(0, &self.sim, &[]);
+        cpass.dispatch_workgroups(
+            (self.desc.max_particles + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE,
+            1,
+            1,
+        );
+    }
+}
+
+impl fmt::Debug for ParticleEmitter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ParticleEmitter")
+            .field("desc", &self.desc)
+            .field("pending", &self.pending.get())
+            .finish()
+    }
+}
+
+impl Drop for ParticleEmitter {
+    fn drop(&mut self) {
+        stats::buffers_dropped(3);
+    }
+}
+
+impl Spatial for ParticleEmitter {
+    fn transform(&self) -> &Transform {
+        &self.transform
+    }
+}
+
+impl Drawable for ParticleEmitter {
+    fn bundle(&self) -> &RenderBundle {
+        &self.bundle
+    }
+
+    fn stats(&self) -> DrawStats {
+        // every particle is an instance of a quad
+        DrawStats::new(1, self.desc.max_particles * 6)
+    }
+}
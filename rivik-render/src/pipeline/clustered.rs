@@ -0,0 +1,156 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public License,
+ * v. 2.0. If a copy of the MPL was not distributed with this file, You can
+ * obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+//! Light culling and shading pipelines for clustered point lights
+
+use std::{borrow::Cow, num::NonZeroU64};
+
+use once_cell::sync::OnceCell;
+use wgpu::{
+    BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType,
+    BufferBindingType, ColorTargetState, ColorWrites, ComputePipeline, ComputePipelineDescriptor,
+    FragmentState, MultisampleState, PipelineLayoutDescriptor, PrimitiveState, RenderPipeline,
+    RenderPipelineDescriptor, ShaderStages, VertexState,
+};
+
+use crate::{
+    camera::CAMERA_SIZE,
+    context::{device, gbuffer},
+    shader,
+};
+
+use super::{GBuffer, LIGHT_BLEND};
+
+/// Number of clusters the view frustum is split into, 16x9 tiles by 24 depth slices
+pub const CLUSTERS: u64 = 16 * 9 * 24;
+/// Maximum number of lights that can affect a single cluster
+pub const MAX_CLUSTER_LIGHTS: u64 = 64;
+/// Size of a single point light in bytes
+pub const POINT_LIGHT_SIZE: u64 = 32;
+/// Size of the light count uniform in bytes
+pub const INFO_SIZE: u64 = 16;
+
+static CULL_PIPE: OnceCell<ComputePipeline> = OnceCell::new();
+static CULL_LAYOUT: OnceCell<BindGroupLayout> = OnceCell::new();
+static SHADE_PIPE: OnceCell<RenderPipeline> = OnceCell::new();
+static SHADE_LAYOUT: OnceCell<BindGroupLayout> = OnceCell::new();
+
+fn entries(visibility: ShaderStages, read_only: bool) -> [BindGroupLayoutEntry; 5] {
+    let entry = |binding, ty, size| BindGroupLayoutEntry {
+        binding,
+        visibility,
+        ty: BindingType::Buffer {
+            ty,
+            has_dynamic_offset: false,
+            min_binding_size: NonZeroU64::new(size),
+        },
+        count: None,
+    };
+    let lights = BufferBindingType::Storage { read_only: true };
+    let clusters = BufferBindingType::Storage { read_only };
+    [
+        entry(0, lights, POINT_LIGHT_SIZE),
+        entry(1, BufferBindingType::Uniform, INFO_SIZE),
+        entry(2, clusters, CLUSTERS * 4),
+        entry(3, clusters, CLUSTERS * MAX_CLUSTER_LIGHTS * 4),
+        entry(4, BufferBindingType::Uniform, CAMERA_SIZE),
+    ]
+}
+
+/// Fetch the layout of the light culling inputs
+///
+/// Binding 0 is the light buffer, 1 the light count, 2 the number of lights in each cluster,
+/// 3 the light indices of each cluster and 4 the camera uniform
+pub fn cull_layout() -> &'static BindGroupLayout {
+    CULL_LAYOUT.get_or_init(|| {
+        device().create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Light Culling"),
+            entries: &entries(ShaderStages::COMPUTE, false),
+        })
+    })
+}
+
+/// Fetch the layout of the clustered shading inputs
+///
+/// The bindings match [`cull_layout`] but the clusters are read only
+pub fn layout() -> &'static BindGroupLayout {
+    SHADE_LAYOUT.get_or_init(|| {
+        device().create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Clustered Lights"),
+            entries: &entries(ShaderStages::FRAGMENT, true),
+        })
+    })
+}
+
+/// Fetch the light culling pipeline
+pub fn cull_pipeline() -> &'static ComputePipeline {
+    CULL_PIPE.get_or_init(|| {
+        let device = device();
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(
+                &shader!("../shaders/clustered.wgsl", "CULL").unwrap(),
+            )),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[cull_layout()],
+            push_constant_ranges: &[],
+        });
+
+        device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("Light Culling"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: "cs_main",
+        })
+    })
+}
+
+/// Fetch the clustered shading pipeline
+pub fn pipeline() -> &'static RenderPipeline {
+    SHADE_PIPE.get_or_init(|| {
+        let device = device();
+        let gbuffer = gbuffer();
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(
+                &shader!("../shaders/clustered.wgsl").unwrap(),
+            )),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&gbuffer.layout, layout()],
+            push_constant_ranges: &[],
+        });
+
+        device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Clustered Lights"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format: GBuffer::hdr_format(),
+                    blend: Some(LIGHT_BLEND),
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            multiview: None,
+        })
+    })
+}
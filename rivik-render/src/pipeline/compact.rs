@@ -0,0 +1,130 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public License,
+ * v. 2.0. If a copy of the MPL was not distributed with this file, You can
+ * obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+//! Pipeline for a pixel mesh with quantized vertices
+//!
+//! [`Vertex3D`](super::Vertex3D) stores the corners of its triangle in every vertex. This
+//! pipeline instead reads deduplicated [`CompactVertex`]s and the index buffer from storage
+//! buffers and reconstructs the corners in the vertex shader, which is 20 bytes per unique
+//! vertex plus 4 bytes per index instead of 104 bytes per index.
+
+use bytemuck::{Pod, Zeroable};
+use once_cell::sync::Lazy;
+use ultraviolet::{Vec2, Vec3};
+use wgpu::{BindGroupLayout, RenderPipeline};
+
+use crate::{
+    context::device,
+    material::{self, PixelMaterial},
+    shader, transform,
+};
+
+use super::GBuffer;
+
+/// Render pipeline for a static pixelated mesh with compact vertices
+pub static PIPELINE: Lazy<RenderPipeline> = Lazy::new(|| {
+    GBuffer::vertexless_geom_pipeline(
+        &shader!("../shaders/compact_pixel.wgsl").unwrap(),
+        &[
+            material::layout::<PixelMaterial>(),
+            transform::layout(),
+            &MESH_LAYOUT,
+        ],
+    )
+});
+
+/// Layout of the storage buffers holding a mesh's vertices and indices
+///
+/// Used by compact meshes and by debug views of indexed meshes.
+pub static MESH_LAYOUT: Lazy<BindGroupLayout> = Lazy::new(|| {
+    let storage = |binding| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::VERTEX,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: true },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    };
+
+    device().create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Compact Mesh"),
+        entries: &[storage(0), storage(1)],
+    })
+});
+
+/// A quantized 3d vertex
+///
+/// The normal is octahedral encoded into two `snorm16`s and the uv is stored as two half floats.
+/// Half floats exactly represent the texel edges of textures up to 2048 pixels wide, so pixel
+/// snapping is unaffected by the quantization.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Zeroable, Pod, Default)]
+pub struct CompactVertex {
+    pos: [f32; 3],
+    norm: u32,
+    uv: u32,
+}
+
+impl CompactVertex {
+    /// Create a new compact vertex
+    pub fn new(pos: Vec3, norm: Vec3, uv: Vec2) -> Self {
+        Self {
+            pos: *pos.as_array(),
+            norm: oct_encode(norm),
+            uv: f16_bits(uv.x) as u32 | (f16_bits(uv.y) as u32) << 16,
+        }
+    }
+}
+
+/// Octahedral encode a normal into two packed `snorm16`s
+fn oct_encode(norm: Vec3) -> u32 {
+    let sign = |v: f32| if v >= 0.0 { 1.0 } else { -1.0 };
+    let n = norm / (norm.x.abs() + norm.y.abs() + norm.z.abs());
+    let (x, y) = if n.z >= 0.0 {
+        (n.x, n.y)
+    } else {
+        ((1.0 - n.y.abs()) * sign(n.x), (1.0 - n.x.abs()) * sign(n.y))
+    };
+
+    let snorm = |v: f32| (v.clamp(-1.0, 1.0) * 32767.0).round() as i16 as u16 as u32;
+    snorm(x) | snorm(y) << 16
+}
+
+/// Convert an `f32` into the bits of the nearest half float
+fn f16_bits(f: f32) -> u16 {
+    let x = f.to_bits();
+    let sign = ((x >> 16) & 0x8000) as u16;
+    let exp = ((x >> 23) & 0xff) as i32;
+    let man = x & 0x7f_ffff;
+
+    // infinity and NaN
+    if exp == 0xff {
+        return sign | 0x7c00 | if man != 0 { 0x200 } else { 0 };
+    }
+
+    let e = exp - 127 + 15;
+    if e >= 0x1f {
+        // too large, round to infinity
+        return sign | 0x7c00;
+    }
+    if e <= 0 {
+        // subnormal half
+        if e < -10 {
+            return sign;
+        }
+        let m = man | 0x80_0000;
+        let shift = (14 - e) as u32;
+        let half = (m >> shift) as u16;
+        let round = ((m >> (shift - 1)) & 1) as u16;
+        return sign | (half + round);
+    }
+
+    // rounding may carry into the exponent, which is still the correct result
+    let half = sign | ((e as u16) << 10) | (man >> 13) as u16;
+    half + ((man >> 12) & 1) as u16
+}
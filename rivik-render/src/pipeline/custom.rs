@@ -0,0 +1,219 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public License,
+ * v. 2.0. If a copy of the MPL was not distributed with this file, You can
+ * obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+//! Custom pipelines drawing geometry into the G-buffer
+//!
+//! A [`GeomPipelineBuilder`] takes a WGSL shader, the layout of its vertices and the layouts of
+//! any bind groups it needs. The object's [`Transform`] is bound at group 0 and the extra bind
+//! groups follow at group 1 onwards, in the order they were added.
+//!
+//! Before the shader is compiled `transform.wgsl` and `gbuffer_targets.wgsl` are included, so a
+//! shader can move its vertices with `clip_position`, `view_position`, `view_normal`,
+//! `current_position` and `previous_position` and return the `GBuffer` struct from its fragment
+//! stage without declaring either. The entry points must be named `vs_main` and `fs_main`.
+//!
+//! ```wgsl
+//! struct VertexOutput {
+//!     @builtin(position) position: vec4<f32>,
+//!     @location(0) view_position: vec4<f32>,
+//!     @location(1) norm: vec4<f32>,
+//!     @location(2) current: vec4<f32>,
+//!     @location(3) previous: vec4<f32>,
+//! }
+//!
+//! @group(1) @binding(0)
+//! var<uniform> tint: vec4<f32>;
+//!
+//! @vertex
+//! fn vs_main(@location(0) pos: vec3<f32>, @location(1) norm: vec3<f32>) -> VertexOutput {
+//!     var out: VertexOutput;
+//!     out.position = clip_position(pos);
+//!     out.view_position = view_position(pos);
+//!     out.norm = view_normal(norm);
+//!     out.current = current_position(pos);
+//!     out.previous = previous_position(pos);
+//!     return out;
+//! }
+//!
+//! @fragment
+//! fn fs_main(in: VertexOutput) -> GBuffer {
+//!     var gbuffer: GBuffer;
+//!     gbuffer.color = tint;
+//!     gbuffer.pos = in.view_position;
+//!     gbuffer.normal = in.norm;
+//!     gbuffer.velocity = motion_vector(in.current, in.previous);
+//!     return gbuffer;
+//! }
+//! ```
+//!
+//! A bind group sampling a single texture can use the layout from
+//! [`material::texture_layout_entries`](crate::material::texture_layout_entries) and be created
+//! with [`material::texture_bind_group`](crate::material::texture_bind_group).
+//!
+//! Meshes are drawn with a pipeline through a [`CustomMesh`](crate::draw::CustomMesh), or a
+//! bundle recorded with [`GeomPipeline::record`] for drawables that manage their own state.
+//! Shaders that only need a single bind group can implement a [`Material`](crate::material)
+//! instead.
+
+use std::io;
+
+use snafu::{Backtrace, ResultExt, Snafu};
+use wgpu::{
+    BindGroup, BindGroupLayout, RenderBundle, RenderBundleDescriptor,
+    RenderBundleEncoderDescriptor, RenderPipeline, VertexBufferLayout,
+};
+
+use crate::{
+    context::{device, gbuffer},
+    debug::Positions,
+    load::CountedBuffer,
+    preprocess::Preprocessor,
+    transform, Transform,
+};
+
+use super::GBuffer;
+
+/// The includes added before a custom shader
+const PRELUDE: &str = "#include \"transform.wgsl\"\n#include \"gbuffer_targets.wgsl\"\n";
+
+/// An error building a custom geometry pipeline
+#[derive(Debug, Snafu)]
+#[snafu(display("Failed to preprocess the geometry shader"))]
+pub struct GeomPipelineError {
+    source: io::Error,
+    backtrace: Backtrace,
+}
+
+/// Builds a [`GeomPipeline`] from a user provided shader
+#[derive(Debug)]
+pub struct GeomPipelineBuilder<'a> {
+    shader: String,
+    vertex: VertexBufferLayout<'a>,
+    bind_groups: Vec<&'a BindGroupLayout>,
+    preprocessor: Preprocessor,
+    depth_write: bool,
+}
+
+impl<'a> GeomPipelineBuilder<'a> {
+    /// Start building a pipeline from WGSL source and the layout of the vertices it draws
+    pub fn new(shader: impl Into<String>, vertex: VertexBufferLayout<'a>) -> Self {
+        Self {
+            shader: shader.into(),
+            vertex,
+            bind_groups: vec![],
+            preprocessor: Preprocessor::new(),
+            depth_write: true,
+        }
+    }
+
+    /// Add a bind group after the transform and the bind groups added before it
+    pub fn bind_group(mut self, layout: &'a BindGroupLayout) -> Self {
+        self.bind_groups.push(layout);
+        self
+    }
+
+    /// Set the preprocessor used to resolve the shader's own includes and defines
+    pub fn preprocessor(self, preprocessor: Preprocessor) -> Self {
+        Self {
+            preprocessor,
+            ..self
+        }
+    }
+
+    /// Set if geometry drawn with this pipeline writes to the depth buffer, defaults to `true`
+    pub fn depth_write(self, depth_write: bool) -> Self {
+        Self {
+            depth_write,
+            ..self
+        }
+    }
+
+    /// Compile the pipeline
+    pub fn build(self) -> Result<GeomPipeline, GeomPipelineError> {
+        let shader = self
+            .preprocessor
+            .define_value("TRANSFORM_GROUP", "0")
+            .process(&format!("{PRELUDE}{}", self.shader))
+            .context(GeomPipelineSnafu)?;
+
+        let positions = Positions::of(&self.vertex);
+        let mut bind_groups = vec![transform::layout()];
+        bind_groups.extend_from_slice(&self.bind_groups);
+        let pipeline = GBuffer::build_geom_pipeline(
+            &shader,
+            &bind_groups,
+            &[self.vertex],
+            self.depth_write,
+            &[],
+        );
+        Ok(GeomPipeline {
+            pipeline,
+            bind_groups: self.bind_groups.len(),
+            positions,
+        })
+    }
+}
+
+/// A pipeline drawing geometry into the G-buffer, built with a [`GeomPipelineBuilder`]
+#[derive(Debug)]
+pub struct GeomPipeline {
+    pipeline: RenderPipeline,
+    /// number of bind groups after the transform
+    bind_groups: usize,
+    /// where debug views find the position of each vertex
+    positions: Option<Positions>,
+}
+
+impl GeomPipeline {
+    /// The underlying render pipeline
+    pub fn pipeline(&self) -> &RenderPipeline {
+        &self.pipeline
+    }
+
+    /// Where debug views find the position of each vertex, `None` if location 0 of the vertex
+    /// layout isn't a `vec3<f32>`
+    pub(crate) fn positions(&self) -> Option<Positions> {
+        self.positions
+    }
+
+    /// Record a render bundle that draws `mesh` with this pipeline
+    ///
+    /// `bind_groups` are bound from group 1 onwards, matching the layouts the pipeline was built
+    /// with.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of bind groups doesn't match the pipeline
+    pub fn record(
+        &self,
+        mesh: &CountedBuffer,
+        transform: &Transform,
+        bind_groups: &[&BindGroup],
+    ) -> RenderBundle {
+        assert_eq!(
+            bind_groups.len(),
+            self.bind_groups,
+            "The pipeline was built with a different number of bind groups"
+        );
+
+        let mut bundle = device().create_render_bundle_encoder(&RenderBundleEncoderDescriptor {
+            label: None,
+            color_formats: GBuffer::color_formats(),
+            depth_stencil: GBuffer::depth_format(),
+            sample_count: gbuffer().sample_count(),
+            multiview: None,
+        });
+        bundle.set_pipeline(&self.pipeline);
+        bundle.set_bind_group(0, transform.bind_group(), &[]);
+        for (i, group) in bind_groups.iter().enumerate() {
+            bundle.set_bind_group(i as u32 + 1, group, &[]);
+        }
+        mesh.draw(&mut bundle);
+        bundle.finish(&RenderBundleDescriptor {
+            label: Some("Custom Geometry"),
+        })
+    }
+}
@@ -0,0 +1,88 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public License,
+ * v. 2.0. If a copy of the MPL was not distributed with this file, You can
+ * obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+//! Emission pipeline
+//!
+//! Adds the luminance attachment of the G-buffer to the HDR buffer. It isn't a light, every
+//! view draws it once before its lights.
+
+use std::borrow::Cow;
+
+use once_cell::sync::OnceCell;
+use wgpu::{
+    ColorTargetState, ColorWrites, RenderBundle, RenderBundleDescriptor,
+    RenderBundleEncoderDescriptor, RenderPipeline,
+};
+
+use crate::{
+    context::{device, gbuffer},
+    shader,
+};
+
+use super::{GBuffer, LIGHT_BLEND};
+
+static EMISSION_PIPE: OnceCell<RenderPipeline> = OnceCell::new();
+
+/// Fetch the emission pipeline
+pub fn pipeline() -> &'static RenderPipeline {
+    EMISSION_PIPE.get_or_init(|| {
+        let device = device();
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Emission"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(
+                &shader!("../shaders/emission.wgsl").unwrap(),
+            )),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&gbuffer().layout],
+            push_constant_ranges: &[],
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Emission"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format: GBuffer::hdr_format(),
+                    blend: Some(LIGHT_BLEND),
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    })
+}
+
+/// Record a render bundle that adds the emission of `gbuffer` to its HDR buffer
+pub(crate) fn record(gbuffer: &GBuffer) -> RenderBundle {
+    let mut bundle = device().create_render_bundle_encoder(&RenderBundleEncoderDescriptor {
+        label: Some("Emission"),
+        color_formats: &[Some(GBuffer::hdr_format())],
+        depth_stencil: None,
+        sample_count: 1,
+        multiview: None,
+    });
+
+    bundle.set_pipeline(pipeline());
+    bundle.set_bind_group(0, &gbuffer.bind_group, &[]);
+    bundle.draw(0..7, 0..1);
+
+    bundle.finish(&RenderBundleDescriptor {
+        label: Some("Emission"),
+    })
+}
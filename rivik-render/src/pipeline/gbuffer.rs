@@ -7,35 +7,71 @@
 //! Utilities for working with the G-buffer
 use std::borrow::Cow;
 
-use crate::context::device;
+use crate::{
+    context::{device, gbuffer},
+    pipeline::emission,
+    shader,
+    stats::{self, DrawStats},
+};
+use once_cell::sync::OnceCell;
 use wgpu::{
     BindGroup, BindGroupLayout, BindGroupLayoutDescriptor, BindingType, BlendState,
     ColorTargetState, ColorWrites, CommandEncoder, DepthStencilState, Extent3d, LoadOp,
-    RenderBundleDepthStencil, RenderPass, RenderPassColorAttachment,
-    RenderPassDepthStencilAttachment, RenderPipeline, SamplerBindingType, SamplerDescriptor,
-    TextureDescriptor, TextureDimension, TextureFormat, TextureUsages, TextureView,
-    TextureViewDescriptor, VertexBufferLayout,
+    PushConstantRange, RenderBundle, RenderBundleDepthStencil, RenderPass,
+    RenderPassColorAttachment, RenderPassDepthStencilAttachment, RenderPipeline,
+    SamplerBindingType, SamplerDescriptor, Texture, TextureDescriptor, TextureDimension,
+    TextureFormat, TextureUsages, TextureView, TextureViewDescriptor, VertexBufferLayout,
 };
 
+/// Pipeline resolving multisampled G-buffers and the layout of its bind group
+static RESOLVE: OnceCell<(BindGroupLayout, RenderPipeline)> = OnceCell::new();
+/// Pipeline copying the lit image into every sample of the multisampled HDR target and the
+/// layout of its bind group
+static HDR_COPY: OnceCell<(BindGroupLayout, RenderPipeline)> = OnceCell::new();
+
 /// The G-buffer
 pub struct GBuffer {
     pub(crate) color_view: TextureView,
     pub(crate) pos_view: TextureView,
     pub(crate) norm_view: TextureView,
     pub(crate) lum_view: TextureView,
+    pub(crate) velocity_view: TextureView,
+    /// ambient occlusion written by the [SSAO](crate::ssao) pass, zero is unoccluded
+    pub(crate) ao_view: TextureView,
     pub(crate) depth_view: TextureView,
     pub(crate) hdr_view: TextureView,
+    pub(crate) hdr_tex: Texture,
     pub(crate) bind_group: BindGroup,
     pub(crate) layout: BindGroupLayout,
+    /// multisampled targets that are resolved into the views above
+    msaa: Option<Msaa>,
+    /// bundle adding the luminance attachment to the HDR buffer, recorded on first use
+    emission: OnceCell<RenderBundle>,
+    sample_count: u32,
+    size: (u32, u32),
+}
+
+/// Multisampled targets geometry is drawn to before being resolved into the G-buffer
+struct Msaa {
+    views: [TextureView; 5],
+    /// the targets and depth buffer bound for the resolve pass
+    bind_group: BindGroup,
+    /// multisampled target forward passes draw into, resolved into the HDR buffer
+    hdr: TextureView,
+    /// the HDR buffer bound to copy it into `hdr`
+    hdr_group: BindGroup,
 }
 
 impl GBuffer {
     /// Creates a new [`GBuffer`].
     ///
+    /// If `sample_count` is greater than 1 geometry is drawn to multisampled targets which are
+    /// resolved with [`GBuffer::resolve`] at the end of the geometry pass.
+    ///
     /// # Panics
     ///
     /// Panics if the renderer device hasn't been initialized
-    pub fn new(width: u32, height: u32) -> Self {
+    pub fn new(width: u32, height: u32, sample_count: u32) -> Self {
         let device = device();
         // create textures
         let dimensions = Extent3d {
@@ -88,6 +124,28 @@ impl GBuffer {
             view_formats: Default::default(),
         });
 
+        let velocity_tex = device.create_texture(&TextureDescriptor {
+            label: Some("Velocity GBuffer"),
+            size: dimensions,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rg16Float,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            view_formats: Default::default(),
+        });
+
+        let ao_tex = device.create_texture(&TextureDescriptor {
+            label: Some("Occlusion GBuffer"),
+            size: dimensions,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: Self::ao_format(),
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            view_formats: Default::default(),
+        });
+
         let hdr_tex = device.create_texture(&TextureDescriptor {
             label: Some("HDR GBuffer"),
             size: dimensions,
@@ -95,7 +153,11 @@ impl GBuffer {
             sample_count: 1,
             dimension: TextureDimension::D2,
             format: TextureFormat::Rgba16Float,
-            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            usage: TextureUsages::RENDER_ATTACHMENT
+                | TextureUsages::TEXTURE_BINDING
+                | TextureUsages::STORAGE_BINDING
+                | TextureUsages::COPY_SRC
+                | TextureUsages::COPY_DST,
             view_formats: Default::default(),
         });
 
@@ -103,7 +165,7 @@ impl GBuffer {
             label: Some("Depth GBuffer"),
             size: dimensions,
             mip_level_count: 1,
-            sample_count: 1,
+            sample_count,
             dimension: TextureDimension::D2,
             format: TextureFormat::Depth24Plus,
             usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
@@ -116,8 +178,27 @@ impl GBuffer {
         let pos_view = pos_tex.create_view(&TextureViewDescriptor::default());
         let norm_view = norm_tex.create_view(&TextureViewDescriptor::default());
         let lum_view = lum_tex.create_view(&TextureViewDescriptor::default());
+        let velocity_view = velocity_tex.create_view(&TextureViewDescriptor::default());
+        let ao_view = ao_tex.create_view(&TextureViewDescriptor::default());
         let hdr_view = hdr_tex.create_view(&TextureViewDescriptor::default());
         let depth_view = depth_tex.create_view(&TextureViewDescriptor::default());
+        let msaa = (sample_count > 1).then(|| {
+            let views = Self::color_formats().map(|format| {
+                device
+                    .create_texture(&TextureDescriptor {
+                        label: Some("Multisampled GBuffer"),
+                        size: dimensions,
+                        mip_level_count: 1,
+                        sample_count,
+                        dimension: TextureDimension::D2,
+                        format: format.unwrap(),
+                        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+                        view_formats: Default::default(),
+                    })
+                    .create_view(&TextureViewDescriptor::default())
+            });
+            Msaa::new(views, &depth_view, &hdr_view, dimensions, sample_count)
+        });
         let sampler = device.create_sampler(&SamplerDescriptor::default());
 
         let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
@@ -144,6 +225,10 @@ impl GBuffer {
                     binding: 4,
                     resource: wgpu::BindingResource::TextureView(&lum_view),
                 },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::TextureView(&ao_view),
+                },
             ],
         });
         Self {
@@ -155,24 +240,99 @@ impl GBuffer {
             depth_view,
             lum_view,
             norm_view,
+            velocity_view,
+            ao_view,
+            hdr_tex,
+            msaa,
+            emission: OnceCell::new(),
+            sample_count,
+            size: (width, height),
         }
     }
 
     /// Formats of the g-buffer's input buffers
-    pub fn color_formats() -> &'static [Option<TextureFormat>] {
+    pub fn color_formats() -> &'static [Option<TextureFormat>; 5] {
         &[
             Some(TextureFormat::Rgba16Float),
             Some(TextureFormat::Rgba16Float),
             Some(TextureFormat::Rgba16Float),
             Some(TextureFormat::Rgba16Float),
+            Some(TextureFormat::Rg16Float),
         ]
     }
 
+    /// The bind group of the G-buffer's attachments, matching `gbuffer.wgsl`
+    ///
+    /// It is visible to fragment and compute shaders.
+    pub fn bind_group(&self) -> &BindGroup {
+        &self.bind_group
+    }
+
+    /// The layout of [`GBuffer::bind_group`]
+    pub fn bind_group_layout(&self) -> &BindGroupLayout {
+        &self.layout
+    }
+
+    /// The diffuse color attachment
+    pub fn color_view(&self) -> &TextureView {
+        &self.color_view
+    }
+
+    /// The view space position attachment
+    pub fn pos_view(&self) -> &TextureView {
+        &self.pos_view
+    }
+
+    /// The view space normal attachment
+    pub fn norm_view(&self) -> &TextureView {
+        &self.norm_view
+    }
+
+    /// The luminance attachment
+    pub fn lum_view(&self) -> &TextureView {
+        &self.lum_view
+    }
+
+    /// The screen space motion attachment
+    pub fn velocity_view(&self) -> &TextureView {
+        &self.velocity_view
+    }
+
+    /// The ambient occlusion attachment, zero is unoccluded
+    pub fn ao_view(&self) -> &TextureView {
+        &self.ao_view
+    }
+
+    /// The depth buffer, which is multisampled when the G-buffer is
+    pub fn depth_view(&self) -> &TextureView {
+        &self.depth_view
+    }
+
+    /// The lit HDR image, which can also be bound as a storage texture
+    pub fn hdr_view(&self) -> &TextureView {
+        &self.hdr_view
+    }
+
+    /// Number of samples per pixel geometry is drawn with
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    /// Size of the g-buffer in pixels
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
     /// The format of the HDR buffer
     pub fn hdr_format() -> TextureFormat {
         TextureFormat::Rgba16Float
     }
 
+    /// The format of the ambient occlusion buffer
+    pub fn ao_format() -> TextureFormat {
+        TextureFormat::R16Float
+    }
+
     /// The depth format used by the g-buffer
     pub fn depth_format() -> Option<RenderBundleDepthStencil> {
         Some(RenderBundleDepthStencil {
@@ -204,6 +364,11 @@ impl GBuffer {
             blend: Some(BlendState::REPLACE),
             write_mask: ColorWrites::ALL,
         }),
+        Some(ColorTargetState {
+            format: TextureFormat::Rg16Float,
+            blend: Some(BlendState::REPLACE),
+            write_mask: ColorWrites::ALL,
+        }),
     ];
 
     /// Create a pipeline for rendering to the g-buffer without setting the depth buffer
@@ -214,48 +379,59 @@ impl GBuffer {
         bind_groups: &[&BindGroupLayout],
         vertex: VertexBufferLayout,
     ) -> RenderPipeline {
-        let shader = device().create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: None,
-            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(shader)),
-        });
-
-        let layout = device().create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: None,
-            bind_group_layouts: bind_groups,
-            push_constant_ranges: &[],
-        });
-
-        device().create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: None,
-            layout: Some(&layout),
-            vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &[vertex],
-            },
-            primitive: Default::default(),
-            depth_stencil: Some(DepthStencilState {
-                format: TextureFormat::Depth24Plus,
-                depth_write_enabled: false,
-                depth_compare: wgpu::CompareFunction::LessEqual,
-                stencil: Default::default(),
-                bias: Default::default(),
-            }),
-            multisample: Default::default(),
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: GBuffer::TARGETS,
-            }),
-            multiview: None,
-        })
+        Self::build_geom_pipeline(shader, bind_groups, &[vertex], false, &[])
     }
 
     /// Create a pipeline for rendering geometry to the g-buffer
+    ///
+    /// See [`GeomPipelineBuilder`](super::GeomPipelineBuilder) for a pipeline with the transform
+    /// and the G-buffer targets declared automatically
     pub fn geom_pipeline(
         shader: &str,
         bind_groups: &[&BindGroupLayout],
         vertex: VertexBufferLayout,
+    ) -> RenderPipeline {
+        Self::build_geom_pipeline(shader, bind_groups, &[vertex], true, &[])
+    }
+
+    /// Create a pipeline for rendering geometry that has no vertex buffers
+    ///
+    /// The vertex shader is expected to fetch its vertices from storage buffers using the
+    /// `vertex_index` builtin
+    pub fn vertexless_geom_pipeline(
+        shader: &str,
+        bind_groups: &[&BindGroupLayout],
+    ) -> RenderPipeline {
+        Self::build_geom_pipeline(shader, bind_groups, &[], true, &[])
+    }
+
+    /// Create a pipeline for rendering geometry with indirect draws
+    ///
+    /// The vertex stage has a `u32` push constant, which is used to pass the index of the draw
+    /// when draws can't be told apart by their first instance
+    pub fn indirect_geom_pipeline(
+        shader: &str,
+        bind_groups: &[&BindGroupLayout],
+        vertex: VertexBufferLayout,
+    ) -> RenderPipeline {
+        Self::build_geom_pipeline(
+            shader,
+            bind_groups,
+            &[vertex],
+            true,
+            &[PushConstantRange {
+                stages: wgpu::ShaderStages::VERTEX,
+                range: 0..4,
+            }],
+        )
+    }
+
+    pub(crate) fn build_geom_pipeline(
+        shader: &str,
+        bind_groups: &[&BindGroupLayout],
+        buffers: &[VertexBufferLayout],
+        depth_write: bool,
+        push_constant_ranges: &[PushConstantRange],
     ) -> RenderPipeline {
         let shader = device().create_shader_module(wgpu::ShaderModuleDescriptor {
             label: None,
@@ -265,7 +441,7 @@ impl GBuffer {
         let layout = device().create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: None,
             bind_group_layouts: bind_groups,
-            push_constant_ranges: &[],
+            push_constant_ranges,
         });
 
         device().create_render_pipeline(&wgpu::RenderPipelineDescriptor {
@@ -274,17 +450,20 @@ impl GBuffer {
             vertex: wgpu::VertexState {
                 module: &shader,
                 entry_point: "vs_main",
-                buffers: &[vertex],
+                buffers,
             },
             primitive: Default::default(),
             depth_stencil: Some(DepthStencilState {
                 format: TextureFormat::Depth24Plus,
-                depth_write_enabled: true,
+                depth_write_enabled: depth_write,
                 depth_compare: wgpu::CompareFunction::LessEqual,
                 stencil: Default::default(),
                 bias: Default::default(),
             }),
-            multisample: Default::default(),
+            multisample: wgpu::MultisampleState {
+                count: gbuffer().sample_count(),
+                ..Default::default()
+            },
             fragment: Some(wgpu::FragmentState {
                 module: &shader,
                 entry_point: "fs_main",
@@ -305,30 +484,37 @@ impl GBuffer {
             LoadOp::Load
         };
 
+        let views = [
+            &self.color_view,
+            &self.pos_view,
+            &self.norm_view,
+            &self.lum_view,
+            &self.velocity_view,
+        ];
+        // draw to the multisampled targets if multisampling, they are resolved separately since
+        // averaging positions and normals would describe surfaces that don't exist
+        let color_attachments: Vec<_> = views
+            .into_iter()
+            .enumerate()
+            .map(|(i, view)| {
+                Some(match &self.msaa {
+                    Some(msaa) => RenderPassColorAttachment {
+                        view: &msaa.views[i],
+                        resolve_target: None,
+                        ops: wgpu::Operations { load, store: true },
+                    },
+                    None => RenderPassColorAttachment {
+                        view,
+                        resolve_target: None,
+                        ops: wgpu::Operations { load, store: true },
+                    },
+                })
+            })
+            .collect();
+
         encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("GBuffer Render pass"),
-            color_attachments: &[
-                Some(RenderPassColorAttachment {
-                    view: &self.color_view,
-                    resolve_target: None,
-                    ops: wgpu::Operations { load, store: true },
-                }),
-                Some(RenderPassColorAttachment {
-                    view: &self.pos_view,
-                    resolve_target: None,
-                    ops: wgpu::Operations { load, store: true },
-                }),
-                Some(RenderPassColorAttachment {
-                    view: &self.norm_view,
-                    resolve_target: None,
-                    ops: wgpu::Operations { load, store: true },
-                }),
-                Some(RenderPassColorAttachment {
-                    view: &self.lum_view,
-                    resolve_target: None,
-                    ops: wgpu::Operations { load, store: true },
-                }),
-            ],
+            color_attachments: &color_attachments,
             depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
                 view: &self.depth_view,
                 depth_ops: Some(wgpu::Operations {
@@ -344,6 +530,120 @@ impl GBuffer {
         })
     }
 
+    /// Resolve the multisampled targets into the G-buffer, does nothing without multisampling
+    ///
+    /// Color and luminance are averaged while positions, normals and velocities are taken from
+    /// the sample closest to the camera, so lights only ever shade surfaces that were drawn.
+    pub(crate) fn resolve(&self, encoder: &mut CommandEncoder) {
+        let Some(msaa) = &self.msaa else {
+            return;
+        };
+        let views = [
+            &self.color_view,
+            &self.pos_view,
+            &self.norm_view,
+            &self.lum_view,
+            &self.velocity_view,
+        ];
+        let color_attachments: Vec<_> = views
+            .into_iter()
+            .map(|view| {
+                Some(RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: LoadOp::Load,
+                        store: true,
+                    },
+                })
+            })
+            .collect();
+
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("GBuffer Resolve"),
+            color_attachments: &color_attachments,
+            depth_stencil_attachment: None,
+        });
+        rpass.set_pipeline(&resolve_pipeline().1);
+        rpass.set_bind_group(0, &msaa.bind_group, &[]);
+        rpass.draw(0..7, 0..1);
+        stats::record_draw(DrawStats::new(1, 7));
+    }
+
+    /// Begin a pass that draws forward geometry, like particles, over the lit HDR buffer
+    ///
+    /// When multisampling the lit image is first copied into every sample of a multisampled
+    /// target, which is drawn to and resolved back into the HDR buffer at the end of the pass, so
+    /// forward geometry gets the same antialiasing as the G-buffer. Pipelines drawn in this pass
+    /// need [`GBuffer::sample_count`] samples.
+    pub(crate) fn forward_rpass<'a>(&'a self, encoder: &'a mut CommandEncoder) -> RenderPass<'a> {
+        let Some(msaa) = &self.msaa else {
+            return self.light_rpass(encoder, false);
+        };
+
+        {
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Forward Copy"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: &msaa.hdr,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: LoadOp::Clear(wgpu::Color::BLACK),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            rpass.set_pipeline(&hdr_copy_pipeline(self.sample_count).1);
+            rpass.set_bind_group(0, &msaa.hdr_group, &[]);
+            rpass.draw(0..7, 0..1);
+            stats::record_draw(DrawStats::new(1, 7));
+        }
+
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Forward"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: &msaa.hdr,
+                resolve_target: Some(&self.hdr_view),
+                ops: wgpu::Operations {
+                    load: LoadOp::Load,
+                    store: false,
+                },
+            })],
+            depth_stencil_attachment: None,
+        })
+    }
+
+    /// Bundle that adds the light emitted by surfaces to the HDR buffer
+    ///
+    /// Drawn once per view in [light passes](GBuffer::light_rpass), independent of the lights.
+    pub(crate) fn emission(&self) -> &RenderBundle {
+        self.emission.get_or_init(|| emission::record(self))
+    }
+
+    /// Begin a pass that draws lights into the HDR buffer
+    pub(crate) fn light_rpass<'a>(
+        &'a self,
+        encoder: &'a mut CommandEncoder,
+        clear: bool,
+    ) -> RenderPass<'a> {
+        let load = if clear {
+            LoadOp::Clear(wgpu::Color::BLACK)
+        } else {
+            LoadOp::Load
+        };
+
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Lighting"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: &self.hdr_view,
+                resolve_target: None,
+                ops: wgpu::Operations { load, store: true },
+            })],
+            depth_stencil_attachment: None,
+        })
+    }
+
     fn layout() -> BindGroupLayoutDescriptor<'static> {
         BindGroupLayoutDescriptor {
             label: Some("GBuffer"),
@@ -351,14 +651,14 @@ impl GBuffer {
                 // sampler
                 wgpu::BindGroupLayoutEntry {
                     binding: 0,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
                     ty: BindingType::Sampler(SamplerBindingType::NonFiltering),
                     count: None,
                 },
                 // color
                 wgpu::BindGroupLayoutEntry {
                     binding: 1,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
                     ty: BindingType::Texture {
                         multisampled: false,
                         sample_type: wgpu::TextureSampleType::Float { filterable: false },
@@ -369,7 +669,7 @@ impl GBuffer {
                 // position
                 wgpu::BindGroupLayoutEntry {
                     binding: 2,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
                     ty: BindingType::Texture {
                         multisampled: false,
                         sample_type: wgpu::TextureSampleType::Float { filterable: false },
@@ -380,7 +680,7 @@ impl GBuffer {
                 // normal
                 wgpu::BindGroupLayoutEntry {
                     binding: 3,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
                     ty: BindingType::Texture {
                         multisampled: false,
                         sample_type: wgpu::TextureSampleType::Float { filterable: false },
@@ -391,7 +691,18 @@ impl GBuffer {
                 // luminance
                 wgpu::BindGroupLayoutEntry {
                     binding: 4,
-                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+                // ambient occlusion
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: wgpu::ShaderStages::FRAGMENT | wgpu::ShaderStages::COMPUTE,
                     ty: BindingType::Texture {
                         multisampled: false,
                         sample_type: wgpu::TextureSampleType::Float { filterable: false },
@@ -403,3 +714,179 @@ impl GBuffer {
         }
     }
 }
+
+impl Msaa {
+    fn new(
+        views: [TextureView; 5],
+        depth_view: &TextureView,
+        hdr_view: &TextureView,
+        size: Extent3d,
+        sample_count: u32,
+    ) -> Self {
+        let device = device();
+        let (layout, _) = resolve_pipeline();
+        let mut entries: Vec<_> = views
+            .iter()
+            .enumerate()
+            .map(|(i, view)| wgpu::BindGroupEntry {
+                binding: i as u32,
+                resource: wgpu::BindingResource::TextureView(view),
+            })
+            .collect();
+        entries.push(wgpu::BindGroupEntry {
+            binding: 5,
+            resource: wgpu::BindingResource::TextureView(depth_view),
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Multisampled GBuffer"),
+            layout,
+            entries: &entries,
+        });
+
+        let hdr = device
+            .create_texture(&TextureDescriptor {
+                label: Some("Multisampled HDR"),
+                size,
+                mip_level_count: 1,
+                sample_count,
+                dimension: TextureDimension::D2,
+                format: GBuffer::hdr_format(),
+                usage: TextureUsages::RENDER_ATTACHMENT,
+                view_formats: Default::default(),
+            })
+            .create_view(&TextureViewDescriptor::default());
+        let hdr_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Multisampled HDR"),
+            layout: &hdr_copy_pipeline(sample_count).0,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(hdr_view),
+            }],
+        });
+
+        Self {
+            views,
+            bind_group,
+            hdr,
+            hdr_group,
+        }
+    }
+}
+
+/// Fetch the pipeline resolving multisampled G-buffers and the layout of its bind group
+fn resolve_pipeline() -> &'static (BindGroupLayout, RenderPipeline) {
+    RESOLVE.get_or_init(|| {
+        let device = device();
+        let texture = |binding, sample_type| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: BindingType::Texture {
+                multisampled: true,
+                sample_type,
+                view_dimension: wgpu::TextureViewDimension::D2,
+            },
+            count: None,
+        };
+        let float = wgpu::TextureSampleType::Float { filterable: false };
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Multisampled GBuffer"),
+            entries: &[
+                texture(0, float),
+                texture(1, float),
+                texture(2, float),
+                texture(3, float),
+                texture(4, float),
+                texture(5, wgpu::TextureSampleType::Depth),
+            ],
+        });
+
+        let source = shader!("../shaders/msaa_resolve.wgsl").unwrap();
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("GBuffer Resolve"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            primitive: Default::default(),
+            depth_stencil: None,
+            multisample: Default::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: GBuffer::TARGETS,
+            }),
+            multiview: None,
+        });
+        (layout, pipeline)
+    })
+}
+
+/// Fetch the pipeline copying the lit image into a multisampled target
+///
+/// The sample count is the same for every G-buffer, so the first one is used.
+fn hdr_copy_pipeline(sample_count: u32) -> &'static (BindGroupLayout, RenderPipeline) {
+    HDR_COPY.get_or_init(|| {
+        let device = device();
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Multisampled HDR"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: BindingType::Texture {
+                    multisampled: false,
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                },
+                count: None,
+            }],
+        });
+
+        let source = shader!("../shaders/hdr_copy.wgsl").unwrap();
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Forward Copy"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            primitive: Default::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                ..Default::default()
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format: GBuffer::hdr_format(),
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+        });
+        (layout, pipeline)
+    })
+}
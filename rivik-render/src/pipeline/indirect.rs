@@ -0,0 +1,147 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public License,
+ * v. 2.0. If a copy of the MPL was not distributed with this file, You can
+ * obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+//! Culling and geometry pipelines for static batches drawn with indirect draws
+
+use std::{borrow::Cow, num::NonZeroU64};
+
+use once_cell::sync::OnceCell;
+use wgpu::{
+    BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType,
+    BufferBindingType, ComputePipeline, ComputePipelineDescriptor, Features,
+    PipelineLayoutDescriptor, RenderPipeline, ShaderStages,
+};
+
+use crate::{
+    camera::CAMERA_SIZE,
+    context::device,
+    material::{self, StandardMaterial},
+    shader,
+};
+
+use super::{mesh::MeshVertex, GBuffer};
+
+/// Size of the data of a single draw in bytes
+pub const DRAW_SIZE: u64 = 64 * 3 + 32;
+/// Size of the arguments of a single indexed indirect draw in bytes
+pub const ARGS_SIZE: u64 = 20;
+
+static CULL_PIPE: OnceCell<ComputePipeline> = OnceCell::new();
+static CULL_LAYOUT: OnceCell<BindGroupLayout> = OnceCell::new();
+static GEOM_PIPE: OnceCell<RenderPipeline> = OnceCell::new();
+static GEOM_LAYOUT: OnceCell<BindGroupLayout> = OnceCell::new();
+
+fn entry(
+    binding: u32,
+    visibility: ShaderStages,
+    ty: BufferBindingType,
+    size: u64,
+) -> BindGroupLayoutEntry {
+    BindGroupLayoutEntry {
+        binding,
+        visibility,
+        ty: BindingType::Buffer {
+            ty,
+            has_dynamic_offset: false,
+            min_binding_size: NonZeroU64::new(size),
+        },
+        count: None,
+    }
+}
+
+/// Check if the device can draw a whole batch with a single multi draw
+///
+/// Otherwise each draw is issued on its own with its index in a push constant.
+pub fn multi_draw() -> bool {
+    device()
+        .features()
+        .contains(Features::MULTI_DRAW_INDIRECT | Features::INDIRECT_FIRST_INSTANCE)
+}
+
+/// Fetch the layout of the culling inputs
+///
+/// Binding 0 is the draw buffer, 1 the indirect arguments and 2 the camera uniform
+pub fn cull_layout() -> &'static BindGroupLayout {
+    CULL_LAYOUT.get_or_init(|| {
+        let draws = BufferBindingType::Storage { read_only: true };
+        let args = BufferBindingType::Storage { read_only: false };
+        device().create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Static Batch Culling"),
+            entries: &[
+                entry(0, ShaderStages::COMPUTE, draws, DRAW_SIZE),
+                entry(1, ShaderStages::COMPUTE, args, ARGS_SIZE),
+                entry(
+                    2,
+                    ShaderStages::COMPUTE,
+                    BufferBindingType::Uniform,
+                    CAMERA_SIZE,
+                ),
+            ],
+        })
+    })
+}
+
+/// Fetch the layout of the draws read by the vertex shader
+///
+/// Binding 0 is the draw buffer and 1 the camera uniform, it takes the place of the transform
+/// in group 1.
+pub fn layout() -> &'static BindGroupLayout {
+    GEOM_LAYOUT.get_or_init(|| {
+        let draws = BufferBindingType::Storage { read_only: true };
+        device().create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Static Batch"),
+            entries: &[
+                entry(0, ShaderStages::VERTEX, draws, DRAW_SIZE),
+                entry(
+                    1,
+                    ShaderStages::VERTEX,
+                    BufferBindingType::Uniform,
+                    CAMERA_SIZE,
+                ),
+            ],
+        })
+    })
+}
+
+/// Fetch the culling pipeline
+pub fn cull_pipeline() -> &'static ComputePipeline {
+    CULL_PIPE.get_or_init(|| {
+        let device = device();
+        let source = if multi_draw() {
+            shader!("../shaders/cull.wgsl", "FIRST_INSTANCE")
+        } else {
+            shader!("../shaders/cull.wgsl")
+        };
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(&source.unwrap())),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[cull_layout()],
+            push_constant_ranges: &[],
+        });
+
+        device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("Static Batch Culling"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: "cs_main",
+        })
+    })
+}
+
+/// Fetch the pipeline drawing static batches with a [`StandardMaterial`]
+pub fn pipeline() -> &'static RenderPipeline {
+    GEOM_PIPE.get_or_init(|| {
+        GBuffer::indirect_geom_pipeline(
+            &shader!("../shaders/mesh.wgsl", "INDIRECT").unwrap(),
+            &[material::layout::<StandardMaterial>(), layout()],
+            MeshVertex::LAYOUT,
+        )
+    })
+}
@@ -0,0 +1,102 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public License,
+ * v. 2.0. If a copy of the MPL was not distributed with this file, You can
+ * obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+//! Outline filter pipeline
+use std::{borrow::Cow, num::NonZeroU64};
+
+use once_cell::sync::OnceCell;
+use wgpu::{
+    BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, BlendState,
+    ColorTargetState, ColorWrites, Device, RenderPipeline, ShaderStages, TextureFormat,
+};
+
+use crate::{
+    context::{device, gbuffer, surface_config},
+    shader,
+};
+
+use super::GBuffer;
+
+static OUTLINE_PIPE: OnceCell<RenderPipeline> = OnceCell::new();
+static OUTLINE_LAYOUT: OnceCell<BindGroupLayout> = OnceCell::new();
+
+/// Fetch the outline settings layout
+pub fn layout() -> &'static BindGroupLayout {
+    if let Some(layout) = OUTLINE_LAYOUT.get() {
+        layout
+    } else {
+        let _ = pipeline();
+        layout()
+    }
+}
+
+/// Fetch the outline pipeline
+pub fn pipeline() -> &'static RenderPipeline {
+    let device = device();
+    let gbuffer = gbuffer();
+    let fmt = surface_config().read().unwrap().format;
+    if let Some(pipe) = OUTLINE_PIPE.get() {
+        pipe
+    } else {
+        OUTLINE_PIPE
+            .try_insert(output_pipeline(device, fmt, gbuffer))
+            .unwrap()
+    }
+}
+
+fn output_pipeline(device: &Device, fmt: TextureFormat, gbuffer: &GBuffer) -> RenderPipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: None,
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(
+            &shader!("../shaders/outline.wgsl").unwrap(),
+        )),
+    });
+
+    let settings_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+        label: Some("Outline"),
+        entries: &[BindGroupLayoutEntry {
+            binding: 0,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: NonZeroU64::new(32),
+            },
+            count: None,
+        }],
+    });
+    let settings_layout = OUTLINE_LAYOUT.try_insert(settings_layout).unwrap();
+
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts: &[&gbuffer.layout, settings_layout],
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Outline"),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[Some(ColorTargetState {
+                format: fmt,
+                // outlines are drawn over the filters before them
+                blend: Some(BlendState::ALPHA_BLENDING),
+                write_mask: ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    })
+}
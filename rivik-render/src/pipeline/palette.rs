@@ -0,0 +1,121 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public License,
+ * v. 2.0. If a copy of the MPL was not distributed with this file, You can
+ * obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+//! Palette quantization filter pipeline
+use std::{borrow::Cow, num::NonZeroU64};
+
+use once_cell::sync::OnceCell;
+use wgpu::{
+    BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType,
+    ColorTargetState, ColorWrites, Device, RenderPipeline, ShaderStages, TextureFormat,
+    TextureSampleType, TextureViewDimension,
+};
+
+use crate::{
+    context::{device, surface_config},
+    shader,
+};
+
+use super::LIGHT_BLEND;
+
+static PALETTE_PIPE: OnceCell<RenderPipeline> = OnceCell::new();
+static PALETTE_LAYOUT: OnceCell<BindGroupLayout> = OnceCell::new();
+
+/// Fetch the palette filter input layout
+pub fn layout() -> &'static BindGroupLayout {
+    if let Some(layout) = PALETTE_LAYOUT.get() {
+        layout
+    } else {
+        let _ = pipeline();
+        layout()
+    }
+}
+
+/// Fetch the palette filter pipeline
+pub fn pipeline() -> &'static RenderPipeline {
+    let device = device();
+    let fmt = surface_config().read().unwrap().format;
+    if let Some(pipe) = PALETTE_PIPE.get() {
+        pipe
+    } else {
+        PALETTE_PIPE
+            .try_insert(output_pipeline(device, fmt))
+            .unwrap()
+    }
+}
+
+fn texture_entry(binding: u32) -> BindGroupLayoutEntry {
+    BindGroupLayoutEntry {
+        binding,
+        visibility: ShaderStages::FRAGMENT,
+        ty: BindingType::Texture {
+            multisampled: false,
+            sample_type: TextureSampleType::Float { filterable: false },
+            view_dimension: TextureViewDimension::D2,
+        },
+        count: None,
+    }
+}
+
+fn output_pipeline(device: &Device, fmt: TextureFormat) -> RenderPipeline {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: None,
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(
+            &shader!("../shaders/palette.wgsl").unwrap(),
+        )),
+    });
+
+    let input_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+        label: Some("Palette"),
+        entries: &[
+            // hdr
+            texture_entry(0),
+            // palette
+            texture_entry(1),
+            // settings
+            BindGroupLayoutEntry {
+                binding: 2,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: NonZeroU64::new(16),
+                },
+                count: None,
+            },
+        ],
+    });
+    let input_layout = PALETTE_LAYOUT.try_insert(input_layout).unwrap();
+
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts: &[input_layout],
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Palette"),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[Some(ColorTargetState {
+                format: fmt,
+                blend: Some(LIGHT_BLEND),
+                write_mask: ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    })
+}
@@ -0,0 +1,181 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public License,
+ * v. 2.0. If a copy of the MPL was not distributed with this file, You can
+ * obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+//! Particle simulation and billboard pipelines
+
+use std::{borrow::Cow, num::NonZeroU64};
+
+use once_cell::sync::OnceCell;
+use wgpu::{
+    BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, BlendState,
+    BufferBindingType, ColorTargetState, ColorWrites, ComputePipeline, ComputePipelineDescriptor,
+    FragmentState, MultisampleState, PipelineLayoutDescriptor, PrimitiveState, RenderPipeline,
+    RenderPipelineDescriptor, ShaderStages, VertexState,
+};
+
+use crate::{
+    context::{device, gbuffer},
+    particles::ParticleBlend,
+    shader, transform,
+};
+
+use super::{GBuffer, LIGHT_BLEND};
+
+/// Size of a single particle in bytes
+pub const PARTICLE_SIZE: u64 = 32;
+/// Size of the emitter uniform in bytes
+pub const EMITTER_SIZE: u64 = 240;
+
+static SIM_PIPE: OnceCell<ComputePipeline> = OnceCell::new();
+static SIM_LAYOUT: OnceCell<BindGroupLayout> = OnceCell::new();
+static ADDITIVE_PIPE: OnceCell<RenderPipeline> = OnceCell::new();
+static ALPHA_PIPE: OnceCell<RenderPipeline> = OnceCell::new();
+static PARTICLE_LAYOUT: OnceCell<BindGroupLayout> = OnceCell::new();
+
+fn buffer_entry(
+    binding: u32,
+    visibility: ShaderStages,
+    ty: BufferBindingType,
+    size: u64,
+) -> BindGroupLayoutEntry {
+    BindGroupLayoutEntry {
+        binding,
+        visibility,
+        ty: BindingType::Buffer {
+            ty,
+            has_dynamic_offset: false,
+            min_binding_size: NonZeroU64::new(size),
+        },
+        count: None,
+    }
+}
+
+/// Fetch the layout of the simulation inputs
+///
+/// Binding 0 is the particle buffer, 1 the emitter uniform and 2 the spawn counter
+pub fn sim_layout() -> &'static BindGroupLayout {
+    SIM_LAYOUT.get_or_init(|| {
+        let storage = BufferBindingType::Storage { read_only: false };
+        device().create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Particle Simulation"),
+            entries: &[
+                buffer_entry(0, ShaderStages::COMPUTE, storage, PARTICLE_SIZE),
+                buffer_entry(
+                    1,
+                    ShaderStages::COMPUTE,
+                    BufferBindingType::Uniform,
+                    EMITTER_SIZE,
+                ),
+                buffer_entry(2, ShaderStages::COMPUTE, storage, 4),
+            ],
+        })
+    })
+}
+
+/// Fetch the layout of the billboard inputs
+///
+/// Binding 0 is the particle buffer and 1 the emitter uniform
+pub fn layout() -> &'static BindGroupLayout {
+    PARTICLE_LAYOUT.get_or_init(|| {
+        device().create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Particles"),
+            entries: &[
+                buffer_entry(
+                    0,
+                    ShaderStages::VERTEX,
+                    BufferBindingType::Storage { read_only: true },
+                    PARTICLE_SIZE,
+                ),
+                buffer_entry(
+                    1,
+                    ShaderStages::VERTEX,
+                    BufferBindingType::Uniform,
+                    EMITTER_SIZE,
+                ),
+            ],
+        })
+    })
+}
+
+/// Fetch the particle simulation pipeline
+pub fn sim_pipeline() -> &'static ComputePipeline {
+    SIM_PIPE.get_or_init(|| {
+        let device = device();
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: None,
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(
+                &shader!("../shaders/particles.wgsl", "SIMULATE").unwrap(),
+            )),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[sim_layout()],
+            push_constant_ranges: &[],
+        });
+
+        device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("Particle Simulation"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: "cs_main",
+        })
+    })
+}
+
+/// Fetch the billboard pipeline for a blend mode
+pub fn pipeline(blend: ParticleBlend) -> &'static RenderPipeline {
+    match blend {
+        ParticleBlend::Additive => ADDITIVE_PIPE.get_or_init(|| create_pipeline(LIGHT_BLEND)),
+        ParticleBlend::Alpha => {
+            ALPHA_PIPE.get_or_init(|| create_pipeline(BlendState::PREMULTIPLIED_ALPHA_BLENDING))
+        }
+    }
+}
+
+fn create_pipeline(blend: BlendState) -> RenderPipeline {
+    let device = device();
+    let gbuffer = gbuffer();
+
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: None,
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(
+            &shader!("../shaders/particles.wgsl").unwrap(),
+        )),
+    });
+
+    let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts: &[&gbuffer.layout, transform::layout(), layout()],
+        push_constant_ranges: &[],
+    });
+
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("Particles"),
+        layout: Some(&pipeline_layout),
+        vertex: VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[],
+        },
+        fragment: Some(FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[Some(ColorTargetState {
+                format: GBuffer::hdr_format(),
+                blend: Some(blend),
+                write_mask: ColorWrites::ALL,
+            })],
+        }),
+        primitive: PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: MultisampleState {
+            count: gbuffer.sample_count(),
+            ..Default::default()
+        },
+        multiview: None,
+    })
+}
@@ -0,0 +1,82 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public License,
+ * v. 2.0. If a copy of the MPL was not distributed with this file, You can
+ * obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+//! Pipeline for camera facing sprites
+//!
+//! Sprites have no vertex buffer, the quad's corners are generated in the vertex shader from the
+//! transform and the sprite's uniform.
+
+use std::num::NonZeroU64;
+
+use once_cell::sync::{Lazy, OnceCell};
+use wgpu::{BindGroupLayout, RenderPipeline};
+
+use crate::{
+    context::device,
+    debug::{self, DebugMode},
+    material::{self, PixelMaterial},
+    shader, transform,
+};
+
+use super::GBuffer;
+
+/// Size of the sprite uniform in bytes
+pub const SPRITE_SIZE: u64 = 32;
+
+/// Render pipeline for a sprite
+pub static PIPELINE: Lazy<RenderPipeline> = Lazy::new(|| {
+    GBuffer::vertexless_geom_pipeline(
+        &shader!("../shaders/sprite.wgsl").unwrap(),
+        &[
+            material::layout::<PixelMaterial>(),
+            transform::layout(),
+            &SPRITE_LAYOUT,
+        ],
+    )
+});
+
+/// Sprite pipelines for each debug mode
+static DEBUG_PIPELINES: [OnceCell<RenderPipeline>; 3] =
+    [OnceCell::new(), OnceCell::new(), OnceCell::new()];
+
+/// Fetch the pipeline drawing sprites in a debug mode
+///
+/// # Panics
+///
+/// Panics if `mode` is [`DebugMode::Off`]
+pub fn debug_pipeline(mode: DebugMode) -> &'static RenderPipeline {
+    DEBUG_PIPELINES[mode.index()].get_or_init(|| {
+        let [view, lines] = debug::defines(mode);
+        let source = shader!("../shaders/sprite.wgsl", "DEBUG", view, lines).unwrap();
+        let layout = device().create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[
+                material::layout::<PixelMaterial>(),
+                transform::layout(),
+                &SPRITE_LAYOUT,
+            ],
+            push_constant_ranges: &[],
+        });
+        debug::build_pipeline(mode, &source, &layout, &[])
+    })
+}
+
+/// Layout of the uniform holding a sprite's frame and size
+pub static SPRITE_LAYOUT: Lazy<BindGroupLayout> = Lazy::new(|| {
+    device().create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("Sprite"),
+        entries: &[wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::VERTEX,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: NonZeroU64::new(SPRITE_SIZE),
+            },
+            count: None,
+        }],
+    })
+});
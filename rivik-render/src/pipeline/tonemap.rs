@@ -0,0 +1,88 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public License,
+ * v. 2.0. If a copy of the MPL was not distributed with this file, You can
+ * obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+//! Tone mapping pipeline for render targets
+//!
+//! Writes the HDR buffer of a G-buffer into an sRGB texture that can be sampled by materials
+//! or shown in EGUI.
+
+use std::borrow::Cow;
+
+use once_cell::sync::OnceCell;
+use wgpu::{
+    BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType,
+    ColorTargetState, ColorWrites, RenderPipeline, ShaderStages, TextureFormat, TextureSampleType,
+    TextureViewDimension,
+};
+
+use crate::{context::device, shader};
+
+/// Format of the tone mapped output
+pub const OUTPUT_FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+
+static TONEMAP_PIPE: OnceCell<RenderPipeline> = OnceCell::new();
+static TONEMAP_LAYOUT: OnceCell<BindGroupLayout> = OnceCell::new();
+
+/// Input layout for tone mapping, binding 0 is the HDR buffer
+pub fn layout() -> &'static BindGroupLayout {
+    TONEMAP_LAYOUT.get_or_init(|| {
+        device().create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Tone Mapping"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Texture {
+                    multisampled: false,
+                    sample_type: TextureSampleType::Float { filterable: false },
+                    view_dimension: TextureViewDimension::D2,
+                },
+                count: None,
+            }],
+        })
+    })
+}
+
+/// Fetch the tone mapping pipeline
+pub fn pipeline() -> &'static RenderPipeline {
+    TONEMAP_PIPE.get_or_init(|| {
+        let device = device();
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Tone Mapping"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(
+                &shader!("../shaders/target.wgsl").unwrap(),
+            )),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[layout()],
+            push_constant_ranges: &[],
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Tone Mapping"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format: OUTPUT_FORMAT,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        })
+    })
+}
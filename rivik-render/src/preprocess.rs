@@ -0,0 +1,225 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public License,
+ * v. 2.0. If a copy of the MPL was not distributed with this file, You can
+ * obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+//! A small preprocessor for WGSL shaders
+//!
+//! The following directives are supported:
+//! - `#include "file.wgsl"` pastes the contents of another shader, each file is included at most
+//!   once
+//! - `#define NAME [value]` defines a feature flag, if a value is given any identifier matching
+//!   `NAME` is replaced with it
+//! - `#ifdef NAME`, `#ifndef NAME`, `#else` and `#endif` conditionally include lines
+//!
+//! Includes are first looked up relative to the including file (when the shader was read from
+//! disk), then in files registered with [`Preprocessor::file`] and finally in the shaders that
+//! are shared by the builtin pipelines (see [`BUILTIN_INCLUDES`]).
+//! This means that in release builds, where shaders are embedded with `include_str!`, the
+//! builtin includes are always available.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Shader files shared between the builtin pipelines
+pub const BUILTIN_INCLUDES: &[(&str, &str)] = &[
+    ("debug_view.wgsl", include_str!("shaders/debug_view.wgsl")),
+    ("draws.wgsl", include_str!("shaders/draws.wgsl")),
+    ("fullscreen.wgsl", include_str!("shaders/fullscreen.wgsl")),
+    ("gbuffer.wgsl", include_str!("shaders/gbuffer.wgsl")),
+    (
+        "gbuffer_targets.wgsl",
+        include_str!("shaders/gbuffer_targets.wgsl"),
+    ),
+    ("pixel.wgsl", include_str!("shaders/pixel.wgsl")),
+    ("tonemap.wgsl", include_str!("shaders/tonemap.wgsl")),
+    ("transform.wgsl", include_str!("shaders/transform.wgsl")),
+];
+
+/// Expands `#include` and `#define` directives in a WGSL shader
+///
+/// This is used by the [`shader!`](crate::shader) macro but can also be used directly
+#[derive(Default, Debug, Clone)]
+pub struct Preprocessor {
+    base: Option<PathBuf>,
+    defines: HashMap<String, String>,
+    files: HashMap<String, String>,
+}
+
+struct State {
+    defines: HashMap<String, String>,
+    included: HashSet<String>,
+    out: String,
+}
+
+impl Preprocessor {
+    /// Creates a preprocessor with no defines
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the directory that includes are resolved relative to
+    pub fn base(self, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            base: Some(dir.into()),
+            ..self
+        }
+    }
+
+    /// Define a feature flag
+    pub fn define(self, name: impl Into<String>) -> Self {
+        self.define_value(name, "")
+    }
+
+    /// Define a name that will be replaced by `value`
+    pub fn define_value(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        let _ = self.defines.insert(name.into(), value.into());
+        self
+    }
+
+    /// Register a virtual file that can be included by name
+    pub fn file(mut self, name: impl Into<String>, source: impl Into<String>) -> Self {
+        let _ = self.files.insert(name.into(), source.into());
+        self
+    }
+
+    /// Run the preprocessor over a shader's source
+    pub fn process(&self, source: &str) -> io::Result<String> {
+        let mut state = State {
+            defines: self.defines.clone(),
+            included: HashSet::new(),
+            out: String::with_capacity(source.len()),
+        };
+        self.expand(source, self.base.as_deref(), &mut state)?;
+        Ok(state.out)
+    }
+
+    fn expand(&self, source: &str, dir: Option<&Path>, state: &mut State) -> io::Result<()> {
+        // each entry records if the enclosing block is active and if this branch is active
+        let mut conds: Vec<(bool, bool)> = vec![];
+        let active = |conds: &[(bool, bool)]| conds.last().map_or(true, |c| c.1);
+
+        for (n, line) in source.lines().enumerate() {
+            let n = n + 1;
+            let trimmed = line.trim();
+            let mut tokens = trimmed.split_whitespace();
+
+            match tokens.next() {
+                Some(directive @ ("#ifdef" | "#ifndef")) => {
+                    let name = tokens
+                        .next()
+                        .ok_or_else(|| invalid(n, format!("{directive} is missing a name")))?;
+                    let defined = state.defines.contains_key(name);
+                    let taken = if directive == "#ifdef" {
+                        defined
+                    } else {
+                        !defined
+                    };
+                    let parent = active(&conds);
+                    conds.push((parent, parent && taken));
+                }
+                Some("#else") => {
+                    let (parent, taken) = conds
+                        .pop()
+                        .ok_or_else(|| invalid(n, "#else without a matching #ifdef"))?;
+                    conds.push((parent, parent && !taken));
+                }
+                Some("#endif") => {
+                    let _ = conds
+                        .pop()
+                        .ok_or_else(|| invalid(n, "#endif without a matching #ifdef"))?;
+                }
+                _ if !active(&conds) => {}
+                Some("#define") => {
+                    let name = tokens
+                        .next()
+                        .ok_or_else(|| invalid(n, "#define is missing a name"))?;
+                    let value = tokens.collect::<Vec<_>>().join(" ");
+                    let _ = state.defines.insert(name.to_string(), value);
+                }
+                Some("#include") => {
+                    let name = trimmed["#include".len()..].trim().trim_matches('"');
+                    self.include(name, dir, state)?;
+                }
+                _ => {
+                    substitute(line, &state.defines, &mut state.out);
+                    state.out.push('\n');
+                }
+            }
+        }
+
+        if conds.is_empty() {
+            Ok(())
+        } else {
+            Err(invalid(source.lines().count(), "unterminated #ifdef"))
+        }
+    }
+
+    fn include(&self, name: &str, dir: Option<&Path>, state: &mut State) -> io::Result<()> {
+        if !state.included.insert(name.to_string()) {
+            return Ok(());
+        }
+
+        // files on disk take precedence so shaders can be edited without rebuilding
+        if let Some(dir) = dir {
+            let path = dir.join(name);
+            if path.is_file() {
+                let source = fs::read_to_string(&path)?;
+                return self.expand(&source, path.parent(), state);
+            }
+        }
+
+        let source = self
+            .files
+            .get(name)
+            .map(String::as_str)
+            .or_else(|| {
+                BUILTIN_INCLUDES
+                    .iter()
+                    .find(|(file, _)| *file == name)
+                    .map(|(_, source)| *source)
+            })
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("could not find shader include \"{name}\""),
+                )
+            })?;
+        self.expand(source, dir, state)
+    }
+}
+
+fn invalid(line: usize, msg: impl AsRef<str>) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("on line {line}: {}", msg.as_ref()),
+    )
+}
+
+/// Replace any identifiers that have a defined value, comments are left untouched
+fn substitute(line: &str, defines: &HashMap<String, String>, out: &mut String) {
+    let (line, comment) = line.split_at(line.find("//").unwrap_or(line.len()));
+    let mut ident = String::new();
+    let flush = |ident: &mut String, out: &mut String| {
+        match defines.get(ident.as_str()) {
+            Some(value) if !value.is_empty() => out.push_str(value),
+            _ => out.push_str(ident),
+        }
+        ident.clear();
+    };
+
+    for c in line.chars() {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            ident.push(c);
+        } else {
+            flush(&mut ident, out);
+            out.push(c);
+        }
+    }
+    flush(&mut ident, out);
+    out.push_str(comment);
+}
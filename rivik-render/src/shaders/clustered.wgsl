@@ -0,0 +1,183 @@
+//  This Source Code Form is subject to the terms of the Mozilla Public License,
+//  v. 2.0. If a copy of the MPL was not distributed with this file, You can
+//  obtain one at http://mozilla.org/MPL/2.0/.
+
+// Clustered point lights
+//
+// The view frustum is split into screen tiles and exponential depth slices. With CULL defined
+// this builds the compute stage that bins lights into the clusters they touch, otherwise it
+// builds a fullscreen pass that shades each pixel with the lights of its cluster.
+
+#include "transform.wgsl"
+
+#define CLUSTER_X 16u
+#define CLUSTER_Y 9u
+#define CLUSTER_Z 24u
+#define CLUSTERS 3456u
+#define MAX_CLUSTER_LIGHTS 64u
+
+struct PointLight {
+    // xyz: world position, w: radius
+    pos: vec4<f32>,
+    // rgb: color scaled by intensity
+    color: vec4<f32>,
+}
+
+struct LightInfo {
+    // x: number of lights
+    count: vec4<u32>,
+}
+
+#ifdef CULL
+@group(0)
+@binding(0)
+var<storage, read> lights: array<PointLight>;
+
+@group(0)
+@binding(1)
+var<uniform> info: LightInfo;
+
+@group(0)
+@binding(2)
+var<storage, read_write> cluster_counts: array<u32>;
+
+@group(0)
+@binding(3)
+var<storage, read_write> cluster_lights: array<u32>;
+
+@group(0)
+@binding(4)
+var<uniform> camera: Camera;
+#else
+#include "fullscreen.wgsl"
+#include "gbuffer.wgsl"
+
+@group(1)
+@binding(0)
+var<storage, read> lights: array<PointLight>;
+
+@group(1)
+@binding(1)
+var<uniform> info: LightInfo;
+
+@group(1)
+@binding(2)
+var<storage, read> cluster_counts: array<u32>;
+
+@group(1)
+@binding(3)
+var<storage, read> cluster_lights: array<u32>;
+
+@group(1)
+@binding(4)
+var<uniform> camera: Camera;
+#endif
+
+// x, y: projection scale, z: near plane, w: far plane
+fn projection() -> vec4<f32> {
+    let proj = camera_projection(camera);
+    let near = proj[3][2] / proj[2][2];
+    // an infinite far plane is clamped so the slices stay usable
+    var far = near * 1000.0;
+    if abs(proj[2][2] + 1.0) > 0.000001 {
+        far = proj[3][2] / (proj[2][2] + 1.0);
+    }
+    return vec4<f32>(proj[0][0], proj[1][1], near, far);
+}
+
+fn slice_depth(slice: u32, near: f32, far: f32) -> f32 {
+    return near * pow(far / near, f32(slice) / f32(CLUSTER_Z));
+}
+
+fn light_view_pos(light: PointLight) -> vec3<f32> {
+    return (camera.view * vec4<f32>(light.pos.xyz, 1.0)).xyz;
+}
+
+#ifdef CULL
+@compute
+@workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let cluster = id.x;
+    if cluster >= CLUSTERS {
+        return;
+    }
+
+    let cx = cluster % CLUSTER_X;
+    let cy = (cluster / CLUSTER_X) % CLUSTER_Y;
+    let cz = cluster / (CLUSTER_X * CLUSTER_Y);
+
+    let proj = projection();
+    let d0 = slice_depth(cz, proj.z, proj.w);
+    let d1 = slice_depth(cz + 1u, proj.z, proj.w);
+
+    // tiles are numbered from the top left of the viewport
+    let x0 = f32(cx) / f32(CLUSTER_X) * 2.0 - 1.0;
+    let x1 = f32(cx + 1u) / f32(CLUSTER_X) * 2.0 - 1.0;
+    let y0 = 1.0 - f32(cy + 1u) / f32(CLUSTER_Y) * 2.0;
+    let y1 = 1.0 - f32(cy) / f32(CLUSTER_Y) * 2.0;
+
+    // bounding box of the cluster in view space
+    let xs = vec4<f32>(x0 * d0, x0 * d1, x1 * d0, x1 * d1) / proj.x;
+    let ys = vec4<f32>(y0 * d0, y0 * d1, y1 * d0, y1 * d1) / proj.y;
+    let box_min = vec3<f32>(min(min(xs.x, xs.y), min(xs.z, xs.w)), min(min(ys.x, ys.y), min(ys.z, ys.w)), -d1);
+    let box_max = vec3<f32>(max(max(xs.x, xs.y), max(xs.z, xs.w)), max(max(ys.x, ys.y), max(ys.z, ys.w)), -d0);
+
+    var count = 0u;
+    for (var i = 0u; i < info.count.x; i = i + 1u) {
+        let light = lights[i];
+        let pos = light_view_pos(light);
+        let offset = clamp(pos, box_min, box_max) - pos;
+        if dot(offset, offset) <= light.pos.w * light.pos.w && count < MAX_CLUSTER_LIGHTS {
+            cluster_lights[cluster * MAX_CLUSTER_LIGHTS + count] = i;
+            count = count + 1u;
+        }
+    }
+    cluster_counts[cluster] = count;
+}
+#else
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let coord = vec2<i32>(in.pos.xy);
+    let pos = textureLoad(g_pos, coord, 0);
+    if pos.w <= 0.0 {
+        return vec4<f32>(0.0);
+    }
+    let norm = textureLoad(g_norm, coord, 0).xyz;
+    let col = textureLoad(g_color, coord, 0);
+    let lum = textureLoad(g_lum, coord, 0);
+
+    // the fullscreen uv covers the viewport, matching the clusters' tiles
+    let proj = projection();
+    let tile = min(vec2<u32>(in.uv * vec2<f32>(f32(CLUSTER_X), f32(CLUSTER_Y))), vec2<u32>(CLUSTER_X - 1u, CLUSTER_Y - 1u));
+    let depth = max(-pos.z, proj.z);
+    let slice = min(u32(log(depth / proj.z) / log(proj.w / proj.z) * f32(CLUSTER_Z)), CLUSTER_Z - 1u);
+    let cluster = tile.x + tile.y * CLUSTER_X + slice * CLUSTER_X * CLUSTER_Y;
+
+    // TODO: Specular strength/intensity should be in the gbuffer
+    let spec_str = 0.5;
+    let shininess = 32.0;
+
+    var light = vec4<f32>(0.0);
+    let count = cluster_counts[cluster];
+    for (var i = 0u; i < count; i = i + 1u) {
+        let point = lights[cluster_lights[cluster * MAX_CLUSTER_LIGHTS + i]];
+        let to_light = light_view_pos(point) - pos.xyz;
+        let dist = length(to_light);
+        let radius = point.pos.w;
+        if dist >= radius {
+            continue;
+        }
+
+        let falloff = 1.0 - (dist * dist) / (radius * radius);
+        let light_dir = to_light / max(dist, 0.0001);
+        let power = max(dot(norm, light_dir), 0.0);
+        let refl_dir = reflect(-light_dir, norm);
+        let spec = spec_str * pow(max(dot(normalize(-pos.xyz), refl_dir), 0.0), shininess);
+        light = light + col * ((power + spec) * falloff * falloff * point.color);
+    }
+
+    // dont light emissive pixels, like the sun
+    let inv_lum = max(vec4<f32>(1.0, 1.0, 1.0, 1.0) - lum, vec4<f32>(0.0));
+    return min(light, inv_lum);
+}
+#endif
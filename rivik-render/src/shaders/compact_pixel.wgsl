@@ -0,0 +1,76 @@
+//  This Source Code Form is subject to the terms of the Mozilla Public License,
+//  v. 2.0. If a copy of the MPL was not distributed with this file, You can
+//  obtain one at http://mozilla.org/MPL/2.0/.
+
+// Pixel mesh shader that reads quantized vertices from storage buffers
+//
+// There are no vertex buffers, the mesh is drawn with one invocation per index and the corners
+// of each triangle are fetched from the index buffer
+
+#define TRANSFORM_GROUP 1
+#include "transform.wgsl"
+#include "pixel.wgsl"
+
+struct CompactVertex {
+    x: f32,
+    y: f32,
+    z: f32,
+    // octahedral encoded normal as two snorm16s
+    norm: u32,
+    // uv as two f16s
+    uv: u32,
+}
+
+@group(2)
+@binding(0)
+var<storage, read> vertices: array<CompactVertex>;
+
+@group(2)
+@binding(1)
+var<storage, read> indices: array<u32>;
+
+fn oct_decode(packed: u32) -> vec3<f32> {
+    let e = unpack2x16snorm(packed);
+    var n = vec3<f32>(e.x, e.y, 1.0 - abs(e.x) - abs(e.y));
+    if n.z < 0.0 {
+        let s = select(vec2<f32>(-1.0), vec2<f32>(1.0), n.xy >= vec2<f32>(0.0));
+        n = vec3<f32>((1.0 - abs(n.yx)) * s, n.z);
+    }
+    return normalize(n);
+}
+
+fn vert_pos(v: CompactVertex) -> vec3<f32> {
+    return vec3<f32>(v.x, v.y, v.z);
+}
+
+fn vert_uv(v: CompactVertex) -> vec2<f32> {
+    let uv = unpack2x16float(v.uv);
+    return vec2<f32>(uv.x, 1.0 - uv.y);
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let first = index - index % 3u;
+    let a = vertices[indices[first]];
+    let b = vertices[indices[first + 1u]];
+    let c = vertices[indices[first + 2u]];
+    let v = vertices[indices[index]];
+
+    var out: VertexOutput;
+    out.tex_coord = vert_uv(v);
+    out.position = clip_position(vert_pos(v));
+    out.norm = view_normal(oct_decode(v.norm));
+    out.uv_a = vert_uv(a);
+    out.uv_b = vert_uv(b);
+    out.uv_c = vert_uv(c);
+    out.pos_a = view_position(vert_pos(a));
+    out.pos_b = view_position(vert_pos(b));
+    out.pos_c = view_position(vert_pos(c));
+    out.norm_a = view_normal(oct_decode(a.norm)).xyz;
+    out.norm_b = view_normal(oct_decode(b.norm)).xyz;
+    out.norm_c = view_normal(oct_decode(c.norm)).xyz;
+    out.current = current_position(vert_pos(v));
+    out.previous = previous_position(vert_pos(v));
+
+    return out;
+}
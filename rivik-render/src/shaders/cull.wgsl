@@ -0,0 +1,82 @@
+//  This Source Code Form is subject to the terms of the Mozilla Public License,
+//  v. 2.0. If a copy of the MPL was not distributed with this file, You can
+//  obtain one at http://mozilla.org/MPL/2.0/.
+
+// Frustum culling of a static batch
+//
+// Writes the indirect arguments of every draw, hidden draws get no instances. With
+// FIRST_INSTANCE defined each draw starts at the instance matching its index so the vertex
+// shader can find its transform, otherwise the index is passed as a push constant.
+
+#include "transform.wgsl"
+#include "draws.wgsl"
+
+struct DrawIndexedIndirect {
+    index_count: u32,
+    instance_count: u32,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+}
+
+@group(0)
+@binding(0)
+var<storage, read> draws: array<Draw>;
+
+@group(0)
+@binding(1)
+var<storage, read_write> args: array<DrawIndexedIndirect>;
+
+@group(0)
+@binding(2)
+var<uniform> camera: Camera;
+
+// check if a world space sphere is at least partly inside the frustum of a view projection
+fn visible(view_proj: mat4x4<f32>, center: vec3<f32>, radius: f32) -> bool {
+    let rows = transpose(view_proj);
+    var planes = array<vec4<f32>, 6>(
+        rows[3] + rows[0],
+        rows[3] - rows[0],
+        rows[3] + rows[1],
+        rows[3] - rows[1],
+        rows[2],
+        rows[3] - rows[2],
+    );
+    for (var i = 0; i < 6; i = i + 1) {
+        let plane = planes[i];
+        if dot(plane.xyz, center) + plane.w < -radius * length(plane.xyz) {
+            return false;
+        }
+    }
+    return true;
+}
+
+@compute
+@workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    if i >= arrayLength(&args) {
+        return;
+    }
+
+    let draw = draws[i];
+    let model = draw.transform.model;
+    let center = (model * vec4<f32>(draw.bounds.xyz, 1.0)).xyz;
+    let scale = max(length(model[0].xyz), max(length(model[1].xyz), length(model[2].xyz)));
+
+    var out: DrawIndexedIndirect;
+    out.index_count = draw.index_count;
+    out.instance_count = select(
+        0u,
+        1u,
+        visible(camera.unjittered_view_proj, center, draw.bounds.w * scale),
+    );
+    out.first_index = draw.first_index;
+    out.base_vertex = draw.base_vertex;
+#ifdef FIRST_INSTANCE
+    out.first_instance = i;
+#else
+    out.first_instance = 0u;
+#endif
+    args[i] = out;
+}
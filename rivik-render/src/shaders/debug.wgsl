@@ -0,0 +1,60 @@
+//  This Source Code Form is subject to the terms of the Mozilla Public License,
+//  v. 2.0. If a copy of the MPL was not distributed with this file, You can
+//  obtain one at http://mozilla.org/MPL/2.0/.
+
+// Debug views drawn in place of a mesh's material
+//
+// The view is picked with the defines of debug_view.wgsl. Only the position at location 0 of the
+// mesh's vertices is read.
+// Barycentric coordinates are generated from the vertex index, so they only match the triangles
+// of meshes without an index buffer. Define INDEXED to draw indexed meshes without their index
+// buffer by fetching the vertices from storage buffers instead.
+
+#define TRANSFORM_GROUP 0
+#include "transform.wgsl"
+#include "gbuffer_targets.wgsl"
+#include "debug_view.wgsl"
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) view_position: vec4<f32>,
+    @location(1) bary: vec3<f32>,
+}
+
+#ifdef INDEXED
+// the mesh's vertices as words, and its indices
+@group(1) @binding(0)
+var<storage, read> vertices: array<f32>;
+@group(1) @binding(1)
+var<storage, read> indices: array<u32>;
+
+struct Layout {
+    // words between vertices
+    stride: u32,
+    // words from the start of a vertex to its position
+    offset: u32,
+}
+var<push_constant> layout: Layout;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let start = indices[index] * layout.stride + layout.offset;
+    let position = vec3<f32>(vertices[start], vertices[start + 1u], vertices[start + 2u]);
+#else
+@vertex
+fn vs_main(
+    @location(0) position: vec3<f32>,
+    @builtin(vertex_index) index: u32,
+) -> VertexOutput {
+#endif
+    var out: VertexOutput;
+    out.position = clip_position(position);
+    out.view_position = view_position(position);
+    out.bary = corner_bary(index);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> GBuffer {
+    return debug_view(in.bary, in.view_position);
+}
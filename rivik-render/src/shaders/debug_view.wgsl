@@ -0,0 +1,55 @@
+//  This Source Code Form is subject to the terms of the Mozilla Public License,
+//  v. 2.0. If a copy of the MPL was not distributed with this file, You can
+//  obtain one at http://mozilla.org/MPL/2.0/.
+
+// Colors of the debug views, shared by every shader that can be drawn in one
+//
+// Define WIREFRAME, OVERDRAW or DENSITY to pick the view and LINES when the wireframe is drawn
+// with a line polygon mode. Needs the `GBuffer` struct from gbuffer_targets.wgsl.
+
+// barycentric coordinates of a vertex of a triangle list, from its vertex index
+fn corner_bary(index: u32) -> vec3<f32> {
+    let corner = index % 3u;
+    return vec3<f32>(
+        select(0.0, 1.0, corner == 0u),
+        select(0.0, 1.0, corner == 1u),
+        select(0.0, 1.0, corner == 2u),
+    );
+}
+
+// the G-buffer written by a fragment in the debug view
+fn debug_view(bary: vec3<f32>, view_position: vec4<f32>) -> GBuffer {
+    var gbuffer: GBuffer;
+    var color: vec4<f32>;
+
+#ifdef WIREFRAME
+#ifndef LINES
+    // distance to the closest edge in pixels
+    let edge = bary / fwidth(bary);
+    if min(min(edge.x, edge.y), edge.z) > 1.0 {
+        discard;
+    }
+#endif
+    color = vec4<f32>(0.2, 1.0, 0.4, 1.0);
+#endif
+
+#ifdef OVERDRAW
+    // blended additively, so each layer of geometry brightens the pixel
+    color = vec4<f32>(0.1, 0.04, 0.01, 1.0);
+#endif
+
+#ifdef DENSITY
+    // barycentrics change faster across smaller triangles, shade from blue for large triangles
+    // to red for triangles only a few pixels wide
+    let t = clamp(length(fwidth(bary)) * 4.0, 0.0, 1.0);
+    color = vec4<f32>(t, 1.0 - abs(t * 2.0 - 1.0), 1.0 - t, 1.0);
+#endif
+
+    // debug views are unlit, emitting the color shows it as is
+    gbuffer.color = vec4<f32>(0.0);
+    gbuffer.lum = color;
+    gbuffer.pos = view_position;
+    gbuffer.normal = vec4<f32>(0.0);
+    gbuffer.velocity = vec4<f32>(0.0);
+    return gbuffer;
+}
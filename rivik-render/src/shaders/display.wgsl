@@ -2,22 +2,7 @@
 //  v. 2.0. If a copy of the MPL was not distributed with this file, You can
 //  obtain one at http://mozilla.org/MPL/2.0/.
 
-struct VertexOutput{
-    @builtin(position) pos: vec4<f32>,
-    @location(0) uv: vec2<f32>,
-}
-
-@vertex
-fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> VertexOutput {
-    var out: VertexOutput;
-    let i = i32(in_vertex_index) + 1;
-    let x = f32(1 - (i & 2));
-    let y = f32(1 - ((i & 1) * 2));
-
-    out.pos = vec4<f32>(x, y, 0.0, 1.0);
-    out.uv = vec2<f32>((x+1.0)/2.0, 1.0-(y+1.0)/2.0);
-    return out;
-}
+#include "fullscreen.wgsl"
 
 @group(0)
 @binding(0)
@@ -0,0 +1,17 @@
+//  This Source Code Form is subject to the terms of the Mozilla Public License,
+//  v. 2.0. If a copy of the MPL was not distributed with this file, You can
+//  obtain one at http://mozilla.org/MPL/2.0/.
+
+// Per-draw data of a static batch
+//
+// Requires transform.wgsl for the layout of the transform
+
+struct Draw {
+    transform: Transform,
+    // xyz: center of the bounding sphere in model space, w: radius
+    bounds: vec4<f32>,
+    first_index: u32,
+    index_count: u32,
+    base_vertex: i32,
+    _pad: u32,
+}
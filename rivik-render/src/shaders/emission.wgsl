@@ -0,0 +1,13 @@
+//  This Source Code Form is subject to the terms of the Mozilla Public License,
+//  v. 2.0. If a copy of the MPL was not distributed with this file, You can
+//  obtain one at http://mozilla.org/MPL/2.0/.
+
+// Adds the light given off by surfaces, drawn once per view regardless of the scene's lights
+
+#include "fullscreen.wgsl"
+#include "gbuffer.wgsl"
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(g_lum, samplr, gbuffer_uv(in.pos));
+}
@@ -0,0 +1,33 @@
+//  This Source Code Form is subject to the terms of the Mozilla Public License,
+//  v. 2.0. If a copy of the MPL was not distributed with this file, You can
+//  obtain one at http://mozilla.org/MPL/2.0/.
+
+// Helpers for drawing a triangle strip that covers the entire screen
+//
+// Define CUSTOM_VERTEX before including this file to provide your own vertex stage
+
+fn fullscreen_pos(in_vertex_index: u32) -> vec4<f32> {
+    let i = i32(in_vertex_index) + 1;
+    let x = f32(1 - (i & 2));
+    let y = f32(1 - ((i & 1) * 2));
+    return vec4<f32>(x, y, 0.0, 1.0);
+}
+
+fn fullscreen_uv(pos: vec4<f32>) -> vec2<f32> {
+    return vec2<f32>((pos.x+1.0)/2.0, 1.0-(pos.y+1.0)/2.0);
+}
+
+#ifndef CUSTOM_VERTEX
+struct VertexOutput{
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> VertexOutput {
+    var out: VertexOutput;
+    out.pos = fullscreen_pos(in_vertex_index);
+    out.uv = fullscreen_uv(out.pos);
+    return out;
+}
+#endif
@@ -0,0 +1,58 @@
+//  This Source Code Form is subject to the terms of the Mozilla Public License,
+//  v. 2.0. If a copy of the MPL was not distributed with this file, You can
+//  obtain one at http://mozilla.org/MPL/2.0/.
+
+// Fast approximate antialiasing
+//
+// Blurs along edges found from the luma of neighbouring pixels
+
+#include "fullscreen.wgsl"
+
+#define REDUCE_MIN 0.0078125
+#define REDUCE_MUL 0.125
+#define SPAN_MAX 8.0
+
+@group(0)
+@binding(0)
+var samplr: sampler;
+
+@group(0)
+@binding(1)
+var hdr: texture_2d<f32>;
+
+fn luma(col: vec4<f32>) -> f32 {
+    return dot(min(col.rgb, vec3<f32>(1.0)), vec3<f32>(0.299, 0.587, 0.114));
+}
+
+fn fetch(uv: vec2<f32>) -> vec4<f32> {
+    return textureSampleLevel(hdr, samplr, uv, 0.0);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let px = 1.0 / vec2<f32>(textureDimensions(hdr));
+    let uv = in.uv;
+
+    let col = fetch(uv);
+    let l_m = luma(col);
+    let l_nw = luma(fetch(uv + vec2<f32>(-1.0, -1.0) * px));
+    let l_ne = luma(fetch(uv + vec2<f32>(1.0, -1.0) * px));
+    let l_sw = luma(fetch(uv + vec2<f32>(-1.0, 1.0) * px));
+    let l_se = luma(fetch(uv + vec2<f32>(1.0, 1.0) * px));
+    let l_min = min(l_m, min(min(l_nw, l_ne), min(l_sw, l_se)));
+    let l_max = max(l_m, max(max(l_nw, l_ne), max(l_sw, l_se)));
+
+    // direction of the edge
+    var dir = vec2<f32>(-((l_nw + l_ne) - (l_sw + l_se)), (l_nw + l_sw) - (l_ne + l_se));
+    let reduce = max((l_nw + l_ne + l_sw + l_se) * 0.25 * REDUCE_MUL, REDUCE_MIN);
+    let rcp_min = 1.0 / (min(abs(dir.x), abs(dir.y)) + reduce);
+    dir = clamp(dir * rcp_min, vec2<f32>(-SPAN_MAX), vec2<f32>(SPAN_MAX)) * px;
+
+    let a = 0.5 * (fetch(uv + dir * (1.0 / 3.0 - 0.5)) + fetch(uv + dir * (2.0 / 3.0 - 0.5)));
+    let b = a * 0.5 + 0.25 * (fetch(uv - dir * 0.5) + fetch(uv + dir * 0.5));
+    let l_b = luma(b);
+    if l_b < l_min || l_b > l_max {
+        return vec4<f32>(a.rgb, col.a);
+    }
+    return vec4<f32>(b.rgb, col.a);
+}
@@ -0,0 +1,38 @@
+//  This Source Code Form is subject to the terms of the Mozilla Public License,
+//  v. 2.0. If a copy of the MPL was not distributed with this file, You can
+//  obtain one at http://mozilla.org/MPL/2.0/.
+
+// G-buffer attachments as they are bound for lighting passes
+
+@group(0)
+@binding(0)
+var samplr: sampler;
+
+@group(0)
+@binding(1)
+var g_color: texture_2d<f32>;
+
+@group(0)
+@binding(2)
+var g_pos: texture_2d<f32>;
+
+@group(0)
+@binding(3)
+var g_norm: texture_2d<f32>;
+
+@group(0)
+@binding(4)
+var g_lum: texture_2d<f32>;
+
+// ambient occlusion in the red channel, zero where nothing is occluded
+@group(0)
+@binding(5)
+var g_ao: texture_2d<f32>;
+
+// uv of the G-buffer texel under a fragment
+//
+// The fullscreen uv only covers the viewport being drawn, so lights drawn into part of the frame
+// look up the G-buffer with the fragment's position instead
+fn gbuffer_uv(frag_pos: vec4<f32>) -> vec2<f32> {
+    return frag_pos.xy / vec2<f32>(textureDimensions(g_color));
+}
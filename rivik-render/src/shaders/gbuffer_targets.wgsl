@@ -0,0 +1,19 @@
+//  This Source Code Form is subject to the terms of the Mozilla Public License,
+//  v. 2.0. If a copy of the MPL was not distributed with this file, You can
+//  obtain one at http://mozilla.org/MPL/2.0/.
+
+// Render targets written by geometry passes
+
+struct GBuffer {
+    @location(0)
+    color: vec4<f32>,
+    @location(1)
+    pos: vec4<f32>,
+    @location(2)
+    normal: vec4<f32>,
+    @location(3)
+    lum: vec4<f32>,
+    // screen space motion since the last frame in uv units
+    @location(4)
+    velocity: vec4<f32>,
+}
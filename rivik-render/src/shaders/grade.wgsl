@@ -0,0 +1,53 @@
+//  This Source Code Form is subject to the terms of the Mozilla Public License,
+//  v. 2.0. If a copy of the MPL was not distributed with this file, You can
+//  obtain one at http://mozilla.org/MPL/2.0/.
+
+// Color grades the displayed image by blending between two 3D lookup tables
+//
+// LUTs map sRGB colors to sRGB colors, so the lit image is tone mapped and encoded before the
+// lookup and the graded color is decoded again for the sRGB surface.
+
+#include "fullscreen.wgsl"
+#include "tonemap.wgsl"
+
+struct Grade {
+    // x: blend from the first LUT to the second
+    params: vec4<f32>,
+}
+
+@group(0)
+@binding(0)
+var samplr: sampler;
+
+@group(0)
+@binding(1)
+var hdr: texture_2d<f32>;
+
+@group(0)
+@binding(2)
+var lut_from: texture_3d<f32>;
+
+@group(0)
+@binding(3)
+var lut_to: texture_3d<f32>;
+
+@group(0)
+@binding(4)
+var<uniform> grade: Grade;
+
+fn lookup(lut: texture_3d<f32>, color: vec3<f32>) -> vec3<f32> {
+    // sample between the centers of the first and last texels
+    let size = vec3<f32>(textureDimensions(lut));
+    let uvw = color * ((size - 1.0) / size) + 0.5 / size;
+    return textureSampleLevel(lut, samplr, uvw, 0.0).rgb;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(hdr));
+    let col = textureLoad(hdr, vec2<i32>(in.uv * size), 0);
+    let color = to_srgb(tonemap(col.rgb));
+
+    let graded = mix(lookup(lut_from, color), lookup(lut_to, color), grade.params.x);
+    return vec4<f32>(from_srgb(graded), col.a);
+}
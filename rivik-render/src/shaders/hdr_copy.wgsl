@@ -0,0 +1,16 @@
+//  This Source Code Form is subject to the terms of the Mozilla Public License,
+//  v. 2.0. If a copy of the MPL was not distributed with this file, You can
+//  obtain one at http://mozilla.org/MPL/2.0/.
+
+// Copies the lit image into every sample of a multisampled target, so forward geometry can be
+// drawn over it and resolved back without changing the pixels it doesn't cover
+
+#include "fullscreen.wgsl"
+
+@group(0) @binding(0)
+var hdr: texture_2d<f32>;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureLoad(hdr, vec2<i32>(in.pos.xy), 0);
+}
@@ -7,42 +7,71 @@ struct VertexOutput {
     @location(1) norm: vec4<f32>,
     @builtin(position) position: vec4<f32>,
     @location(2) view_position: vec4<f32>,
+    @location(3) current: vec4<f32>,
+    @location(4) previous: vec4<f32>,
 }
 
-struct Locals {
-    mvp: mat4x4<f32>,
-    mv: mat4x4<f32>,
-    mv_norm: mat4x4<f32>,
-}
+#ifdef INDIRECT
+// drawn as part of a static batch, the transform is fetched from the batch's draws
+#include "transform.wgsl"
+#include "draws.wgsl"
 
 @group(1)
 @binding(0)
-var<uniform> r_locals: Locals;
+var<storage, read> draws: array<Draw>;
+
+@group(1)
+@binding(1)
+var<uniform> camera: Camera;
+
+struct DrawParams {
+    // index of the draw when it can't be found from the instance index
+    draw: u32,
+}
+
+var<push_constant> params: DrawParams;
 
 @vertex
 fn vs_main(
     @location(0) position: vec3<f32>,
     @location(1) norm: vec3<f32>,
     @location(2) tex_coord: vec2<f32>,
+    @builtin(instance_index) instance: u32,
 ) -> VertexOutput {
+    let transform = draws[params.draw + instance].transform;
+    let pos = vec4<f32>(position, 1.0);
+
     var out: VertexOutput;
     out.tex_coord = vec2<f32>(tex_coord.x, 1.0 - tex_coord.y);
-    out.position = r_locals.mvp * vec4<f32>(position.xyz, 1.0);
-    out.view_position = r_locals.mv * vec4<f32>(position.xyz, 1.0);
-    out.norm = r_locals.mv_norm* vec4<f32>(norm.xyz, 0.0);
+    out.position = camera.view_proj * transform.model * pos;
+    out.view_position = camera.view * transform.model * pos;
+    out.norm = camera.view * transform.model_norm * vec4<f32>(norm, 0.0);
+    out.current = camera.unjittered_view_proj * transform.model * pos;
+    out.previous = camera.prev_view_proj * transform.prev_model * pos;
     return out;
 }
+#else
+#define TRANSFORM_GROUP 1
+#include "transform.wgsl"
 
-struct GBuffer {
-    @location(0)
-    color: vec4<f32>,
-    @location(1)
-    pos: vec4<f32>,
-    @location(2)
-    normal: vec4<f32>,
-    @location(3)
-    lum: vec4<f32>,
+@vertex
+fn vs_main(
+    @location(0) position: vec3<f32>,
+    @location(1) norm: vec3<f32>,
+    @location(2) tex_coord: vec2<f32>,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.tex_coord = vec2<f32>(tex_coord.x, 1.0 - tex_coord.y);
+    out.position = clip_position(position);
+    out.view_position = view_position(position);
+    out.norm = view_normal(norm);
+    out.current = current_position(position);
+    out.previous = previous_position(position);
+    return out;
 }
+#endif
+
+#include "gbuffer_targets.wgsl"
 
 @group(0)
 @binding(0)
@@ -52,13 +81,34 @@ var samplr: sampler;
 @binding(1)
 var g_diffuse: texture_2d<f32>;
 
+@group(0)
+@binding(2)
+var g_emissive: texture_2d<f32>;
+
+struct Emissive {
+    // x: intensity the emissive texture is scaled by
+    intensity: vec4<f32>,
+}
+
+@group(0)
+@binding(3)
+var<uniform> emissive: Emissive;
+
+// light given off by the surface, written to the luminance attachment
+fn emission(uv: vec2<f32>) -> vec4<f32> {
+    let glow = textureSample(g_emissive, samplr, uv).rgb * emissive.intensity.x;
+    return vec4<f32>(glow, 0.0);
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> GBuffer {
     var gbuffer: GBuffer;
 
     gbuffer.color = textureSample(g_diffuse, samplr, in.tex_coord);
+    gbuffer.lum = emission(in.tex_coord);
     gbuffer.pos = in.view_position;
     gbuffer.normal = in.norm;
+    gbuffer.velocity = motion_vector(in.current, in.previous);
 
     return gbuffer;
 }
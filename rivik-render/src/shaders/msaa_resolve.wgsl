@@ -0,0 +1,53 @@
+//  This Source Code Form is subject to the terms of the Mozilla Public License,
+//  v. 2.0. If a copy of the MPL was not distributed with this file, You can
+//  obtain one at http://mozilla.org/MPL/2.0/.
+
+// Resolves the multisampled G-buffer
+//
+// Color and luminance are averaged to smooth the edges of geometry. Positions, normals and
+// velocities can't be averaged without describing a surface that doesn't exist, so they are
+// taken from the sample closest to the camera.
+
+#include "fullscreen.wgsl"
+#include "gbuffer_targets.wgsl"
+
+@group(0) @binding(0)
+var m_color: texture_multisampled_2d<f32>;
+@group(0) @binding(1)
+var m_pos: texture_multisampled_2d<f32>;
+@group(0) @binding(2)
+var m_normal: texture_multisampled_2d<f32>;
+@group(0) @binding(3)
+var m_lum: texture_multisampled_2d<f32>;
+@group(0) @binding(4)
+var m_velocity: texture_multisampled_2d<f32>;
+@group(0) @binding(5)
+var m_depth: texture_depth_multisampled_2d;
+
+@fragment
+fn fs_main(in: VertexOutput) -> GBuffer {
+    let coord = vec2<i32>(in.pos.xy);
+    let samples = i32(textureNumSamples(m_color));
+
+    var color = vec4<f32>(0.0);
+    var lum = vec4<f32>(0.0);
+    var nearest = 0;
+    var depth = 1.0;
+    for (var i = 0; i < samples; i++) {
+        color += textureLoad(m_color, coord, i);
+        lum += textureLoad(m_lum, coord, i);
+        let d = textureLoad(m_depth, coord, i);
+        if d < depth {
+            depth = d;
+            nearest = i;
+        }
+    }
+
+    var gbuffer: GBuffer;
+    gbuffer.color = color / f32(samples);
+    gbuffer.lum = lum / f32(samples);
+    gbuffer.pos = textureLoad(m_pos, coord, nearest);
+    gbuffer.normal = textureLoad(m_normal, coord, nearest);
+    gbuffer.velocity = textureLoad(m_velocity, coord, nearest);
+    return gbuffer;
+}
@@ -0,0 +1,71 @@
+//  This Source Code Form is subject to the terms of the Mozilla Public License,
+//  v. 2.0. If a copy of the MPL was not distributed with this file, You can
+//  obtain one at http://mozilla.org/MPL/2.0/.
+
+// Draws outlines where the G-buffer depth or normals are discontinuous
+//
+// The outline color is blended over the filters drawn before this one
+
+#include "fullscreen.wgsl"
+#include "gbuffer.wgsl"
+
+struct Outline {
+    color: vec4<f32>,
+    // x: depth threshold, y: normal threshold, z: thickness in G-buffer pixels
+    params: vec4<f32>,
+}
+
+@group(1)
+@binding(0)
+var<uniform> outline: Outline;
+
+struct Sample {
+    depth: f32,
+    norm: vec3<f32>,
+    geometry: bool,
+}
+
+fn load_sample(coord: vec2<i32>) -> Sample {
+    let size = vec2<i32>(textureDimensions(g_pos));
+    let c = clamp(coord, vec2<i32>(0), size - vec2<i32>(1));
+    let pos = textureLoad(g_pos, c, 0);
+
+    var s: Sample;
+    s.depth = -pos.z;
+    s.norm = textureLoad(g_norm, c, 0).xyz;
+    // nothing was drawn to pixels with an empty position
+    s.geometry = pos.w > 0.0;
+    return s;
+}
+
+fn is_edge(center: Sample, other: Sample) -> bool {
+    if center.geometry != other.geometry {
+        return true;
+    }
+    if !center.geometry {
+        return false;
+    }
+
+    let depth = abs(center.depth - other.depth) / max(center.depth, 0.001);
+    let norm = 1.0 - dot(normalize(center.norm), normalize(other.norm));
+    return depth > outline.params.x || norm > outline.params.y;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(g_pos));
+    let coord = vec2<i32>(in.uv * size);
+    let offset = max(i32(outline.params.z), 1);
+
+    let center = load_sample(coord);
+    var edge = false;
+    edge = edge || is_edge(center, load_sample(coord + vec2<i32>(offset, 0)));
+    edge = edge || is_edge(center, load_sample(coord - vec2<i32>(offset, 0)));
+    edge = edge || is_edge(center, load_sample(coord + vec2<i32>(0, offset)));
+    edge = edge || is_edge(center, load_sample(coord - vec2<i32>(0, offset)));
+
+    if !edge {
+        discard;
+    }
+    return outline.color;
+}
@@ -0,0 +1,69 @@
+//  This Source Code Form is subject to the terms of the Mozilla Public License,
+//  v. 2.0. If a copy of the MPL was not distributed with this file, You can
+//  obtain one at http://mozilla.org/MPL/2.0/.
+
+// Maps the lit image to the closest color in a palette texture
+//
+// Every texel of the palette texture is a color of the palette. Colors are compared in gamma
+// space so the distances are closer to how different they look.
+
+#include "fullscreen.wgsl"
+
+struct Palette {
+    // x: ordered dithering strength, 0 disables dithering
+    params: vec4<f32>,
+}
+
+@group(0)
+@binding(0)
+var hdr: texture_2d<f32>;
+
+@group(0)
+@binding(1)
+var palette: texture_2d<f32>;
+
+@group(0)
+@binding(2)
+var<uniform> settings: Palette;
+
+fn bayer(coord: vec2<u32>) -> f32 {
+    var thresholds = array<f32, 16>(
+        0.0, 8.0, 2.0, 10.0,
+        12.0, 4.0, 14.0, 6.0,
+        3.0, 11.0, 1.0, 9.0,
+        15.0, 7.0, 13.0, 5.0,
+    );
+    let i = (coord.y % 4u) * 4u + coord.x % 4u;
+    return (thresholds[i] + 0.5) / 16.0 - 0.5;
+}
+
+fn to_gamma(color: vec3<f32>) -> vec3<f32> {
+    return pow(clamp(color, vec3<f32>(0.0), vec3<f32>(1.0)), vec3<f32>(1.0 / 2.2));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(hdr));
+    let coord = vec2<u32>(in.uv * size);
+    var color = to_gamma(textureLoad(hdr, vec2<i32>(coord), 0).rgb);
+
+    // nudge the color by a threshold pattern so gradients dither between palette colors
+    color = color + vec3<f32>(bayer(coord) * settings.params.x);
+
+    let palette_size = vec2<i32>(textureDimensions(palette));
+    var best = vec3<f32>(0.0);
+    var best_dist = 1000.0;
+    for (var y = 0; y < palette_size.y; y = y + 1) {
+        for (var x = 0; x < palette_size.x; x = x + 1) {
+            let entry = textureLoad(palette, vec2<i32>(x, y), 0).rgb;
+            let diff = to_gamma(entry) - color;
+            let dist = dot(diff, diff);
+            if dist < best_dist {
+                best_dist = dist;
+                best = entry;
+            }
+        }
+    }
+
+    return vec4<f32>(best, 1.0);
+}
@@ -0,0 +1,206 @@
+//  This Source Code Form is subject to the terms of the Mozilla Public License,
+//  v. 2.0. If a copy of the MPL was not distributed with this file, You can
+//  obtain one at http://mozilla.org/MPL/2.0/.
+
+// Simulates particles and draws them as camera facing billboards into the HDR buffer
+//
+// Define SIMULATE to build the compute stage, otherwise the billboard stages are built
+
+struct Particle {
+    // xyz: world position, w: age in seconds
+    pos: vec4<f32>,
+    // xyz: world velocity, w: lifetime in seconds
+    vel: vec4<f32>,
+}
+
+struct Emitter {
+    model: mat4x4<f32>,
+    // xyz: gravity, w: drag
+    gravity: vec4<f32>,
+    // x: min lifetime, y: max lifetime, z: min speed, w: max speed
+    spawn: vec4<f32>,
+    // x: spread angle, y: timestep, z: random seed, w: max particles
+    params: vec4<f32>,
+    // x: color keys, y: size keys
+    keys: vec4<f32>,
+    color_times: vec4<f32>,
+    size_times: vec4<f32>,
+    sizes: vec4<f32>,
+    colors: array<vec4<f32>, 4>,
+}
+
+fn alive(p: Particle) -> bool {
+    return p.pos.w < p.vel.w;
+}
+
+#ifdef SIMULATE
+@group(0)
+@binding(0)
+var<storage, read_write> particles: array<Particle>;
+
+@group(0)
+@binding(1)
+var<uniform> emitter: Emitter;
+
+// particles left to spawn this step
+@group(0)
+@binding(2)
+var<storage, read_write> spawn_count: atomic<i32>;
+
+fn hash(x: u32) -> u32 {
+    var v = x * 747796405u + 2891336453u;
+    v = ((v >> ((v >> 28u) + 4u)) ^ v) * 277803737u;
+    return (v >> 22u) ^ v;
+}
+
+fn random(seed: ptr<function, u32>) -> f32 {
+    *seed = hash(*seed);
+    return f32(*seed) / 4294967295.0;
+}
+
+fn spawn_particle(index: u32) -> Particle {
+    var seed = hash(index ^ bitcast<u32>(emitter.params.z));
+
+    // pick a direction in a cone around the emitter's up axis
+    let cos_theta = mix(cos(emitter.params.x), 1.0, random(&seed));
+    let sin_theta = sqrt(max(1.0 - cos_theta * cos_theta, 0.0));
+    let phi = random(&seed) * 6.2831853;
+    let local = vec3<f32>(sin_theta * cos(phi), cos_theta, sin_theta * sin(phi));
+    let dir = normalize((emitter.model * vec4<f32>(local, 0.0)).xyz);
+
+    let speed = mix(emitter.spawn.z, emitter.spawn.w, random(&seed));
+    let lifetime = mix(emitter.spawn.x, emitter.spawn.y, random(&seed));
+
+    var p: Particle;
+    p.pos = vec4<f32>((emitter.model * vec4<f32>(0.0, 0.0, 0.0, 1.0)).xyz, 0.0);
+    p.vel = vec4<f32>(dir * speed, lifetime);
+    return p;
+}
+
+@compute
+@workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    if i >= arrayLength(&particles) {
+        return;
+    }
+
+    var p = particles[i];
+    let dt = emitter.params.y;
+    if !alive(p) {
+        // dead particles are reused until this step's spawn count runs out
+        if atomicSub(&spawn_count, 1) <= 0 {
+            return;
+        }
+        p = spawn_particle(i);
+    } else {
+        let drag = max(1.0 - emitter.gravity.w * dt, 0.0);
+        let vel = (p.vel.xyz + emitter.gravity.xyz * dt) * drag;
+        p.vel = vec4<f32>(vel, p.vel.w);
+        p.pos = vec4<f32>(p.pos.xyz + vel * dt, p.pos.w + dt);
+    }
+    particles[i] = p;
+}
+#else
+#define TRANSFORM_GROUP 1
+#include "transform.wgsl"
+#include "gbuffer.wgsl"
+
+@group(2)
+@binding(0)
+var<storage, read> particles: array<Particle>;
+
+@group(2)
+@binding(1)
+var<uniform> emitter: Emitter;
+
+struct VertexOutput {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    @location(1) uv: vec2<f32>,
+    // distance from the camera
+    @location(2) depth: f32,
+}
+
+// index of the last key at or before `t` and how far it is to the next key
+fn key_position(times: vec4<f32>, count: u32, t: f32) -> vec2<f32> {
+    var i = 0u;
+    for (var k = 1u; k < count; k = k + 1u) {
+        if t >= times[k] {
+            i = k;
+        }
+    }
+    if i + 1u >= count {
+        return vec2<f32>(f32(i), 0.0);
+    }
+    let span = max(times[i + 1u] - times[i], 0.0001);
+    return vec2<f32>(f32(i), clamp((t - times[i]) / span, 0.0, 1.0));
+}
+
+fn particle_size(t: f32) -> f32 {
+    let count = u32(emitter.keys.y);
+    let key = key_position(emitter.size_times, count, t);
+    let i = u32(key.x);
+    let next = min(i + 1u, count - 1u);
+    return mix(emitter.sizes[i], emitter.sizes[next], key.y);
+}
+
+fn particle_color(t: f32) -> vec4<f32> {
+    let count = u32(emitter.keys.x);
+    let key = key_position(emitter.color_times, count, t);
+    let i = u32(key.x);
+    let next = min(i + 1u, count - 1u);
+    return mix(emitter.colors[i], emitter.colors[next], key.y);
+}
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex: u32,
+    @builtin(instance_index) instance: u32,
+) -> VertexOutput {
+    var out: VertexOutput;
+    let p = particles[instance];
+    if !alive(p) {
+        // outside of the clip volume so the quad is culled
+        out.pos = vec4<f32>(2.0, 2.0, 2.0, 1.0);
+        return out;
+    }
+
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    let corner = corners[vertex];
+
+    let t = p.pos.w / max(p.vel.w, 0.0001);
+    let size = particle_size(t);
+
+    // the rows of the view matrix are the camera's axes in world space
+    let right = vec3<f32>(camera.view[0].x, camera.view[1].x, camera.view[2].x);
+    let up = vec3<f32>(camera.view[0].y, camera.view[1].y, camera.view[2].y);
+    let world = vec4<f32>(p.pos.xyz + (right * corner.x + up * corner.y) * size, 1.0);
+
+    out.pos = camera.view_proj * world;
+    out.color = particle_color(t);
+    out.uv = corner;
+    out.depth = -(camera.view * world).z;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // test against the scene by hand since the depth buffer may be multisampled
+    let pos = textureLoad(g_pos, vec2<i32>(in.pos.xy), 0);
+    if pos.w > 0.0 && -pos.z < in.depth {
+        discard;
+    }
+
+    let falloff = clamp(1.0 - length(in.uv), 0.0, 1.0);
+    let alpha = in.color.a * falloff;
+    return vec4<f32>(in.color.rgb * alpha, alpha);
+}
+#endif
@@ -0,0 +1,91 @@
+//  This Source Code Form is subject to the terms of the Mozilla Public License,
+//  v. 2.0. If a copy of the MPL was not distributed with this file, You can
+//  obtain one at http://mozilla.org/MPL/2.0/.
+
+// Fragment stage shared by the pixel mesh shaders
+//
+// Snaps the G-buffer position and normal to the texels of the diffuse texture using the
+// corners of the triangle being shaded
+
+#include "gbuffer_targets.wgsl"
+
+struct VertexOutput {
+    @location(0) tex_coord: vec2<f32>,
+    @location(1) norm: vec4<f32>,
+    @builtin(position) position: vec4<f32>,
+    @location(2) uv_a: vec2<f32>,
+    @location(3) uv_b: vec2<f32>,
+    @location(4) uv_c: vec2<f32>,
+    @location(5) pos_a: vec4<f32>,
+    @location(6) pos_b: vec4<f32>,
+    @location(7) pos_c: vec4<f32>,
+    @location(8) norm_a: vec3<f32>,
+    @location(9) norm_b: vec3<f32>,
+    @location(10) norm_c: vec3<f32>,
+    @location(11) current: vec4<f32>,
+    @location(12) previous: vec4<f32>,
+
+}
+
+@group(0)
+@binding(0)
+var samplr: sampler;
+
+@group(0)
+@binding(1)
+var g_diffuse: texture_2d<f32>;
+
+@group(0)
+@binding(2)
+var g_emissive: texture_2d<f32>;
+
+struct Emissive {
+    // x: intensity the emissive texture is scaled by
+    intensity: vec4<f32>,
+}
+
+@group(0)
+@binding(3)
+var<uniform> emissive: Emissive;
+
+// light given off by the surface, written to the luminance attachment
+fn emission(uv: vec2<f32>) -> vec4<f32> {
+    let glow = textureSample(g_emissive, samplr, uv).rgb * emissive.intensity.x;
+    return vec4<f32>(glow, 0.0);
+}
+
+fn bary(a: vec2<f32>, b: vec2<f32>, c: vec2<f32>, uv: vec2<f32>) -> vec3<f32> {
+    let denom = (b.y-c.y)*(a.x-c.x)+(c.x-b.x)*(a.y-c.y);
+    let denom2 =(c.y-a.y)*(b.x-c.x)+(a.x-c.x)*(b.y-c.y);
+    let x = ((b.y-c.y)*(uv.x-c.x)+(c.x-b.x)*(uv.y-c.y)) / denom;
+    let y = ((c.y-a.y)*(uv.x-c.x)+(a.x-c.x)*(uv.y-c.y)) / denom2;
+    let z = 1.0 - x - y;
+    return vec3<f32>(x, y, z);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> GBuffer {
+    var gbuffer: GBuffer;
+
+    gbuffer.color = textureSample(g_diffuse, samplr, in.tex_coord);
+    gbuffer.lum = emission(in.tex_coord);
+    //gbuffer.color = vec4<f32>(in.tex_coord, 0.0, 1.0);
+    //gbuffer.color = vec4<f32>(1.0, 1.0, 1.0, 0.0);
+    //gbuffer.color = in.norm;
+    //gbuffer.pos = in.position;
+    let dim = vec2<f32>(textureDimensions(g_diffuse));
+    var f_uv = in.tex_coord;
+    f_uv = floor(f_uv * dim) / dim;
+    let bary_coord = bary(in.uv_a, in.uv_b, in.uv_c, f_uv);
+    let f_pos = in.pos_a.xyz * bary_coord.x + in.pos_b.xyz * bary_coord.y + in.pos_c.xyz * bary_coord.z;
+
+    gbuffer.pos = vec4<f32>(f_pos, 1.0);
+
+    let norm = in.norm_a * bary_coord.x + in.norm_b * bary_coord.y + in.norm_c *
+    bary_coord.z;
+
+    gbuffer.normal = vec4<f32>(norm, 0.0);
+    gbuffer.velocity = motion_vector(in.current, in.previous);
+
+    return gbuffer;
+}
@@ -8,15 +8,9 @@ struct VertexOutput {
     @builtin(position) position: vec4<f32>,
 }
 
-struct Locals {
-    mvp: mat4x4<f32>,
-    mv: mat4x4<f32>,
-    mv_norm: mat4x4<f32>,
-}
-
-@group(1)
-@binding(0)
-var<uniform> r_locals: Locals;
+#define TRANSFORM_GROUP 1
+#include "transform.wgsl"
+#include "gbuffer_targets.wgsl"
 
 @vertex
 fn vs_main(
@@ -26,7 +20,7 @@ fn vs_main(
 ) -> VertexOutput {
     var out: VertexOutput;
     out.tex_coord = vec2<f32>(tex_coord.x, 1.0 - tex_coord.y);
-    var mvp = r_locals.mvp;
+    var mvp = camera.view_proj * transform.model;
 
     // remove translation component so that we render relative to camera
     mvp[3] = vec4<f32>(0.0, 0.0, 0.0, 1.0);
@@ -39,17 +33,6 @@ fn vs_main(
     return out;
 }
 
-struct GBuffer {
-    @location(0)
-    color: vec4<f32>,
-    @location(1)
-    pos: vec4<f32>,
-    @location(2)
-    normal: vec4<f32>,
-    @location(3)
-    lum: vec4<f32>,
-}
-
 @group(0)
 @binding(0)
 var samplr: sampler;
@@ -62,10 +45,11 @@ var g_diffuse: texture_2d<f32>;
 fn fs_main(in: VertexOutput) -> GBuffer {
     var gbuffer: GBuffer;
 
-    gbuffer.color = textureSample(g_diffuse, samplr, in.tex_coord);
+    // the sky is unlit, so it only emits its color
+    gbuffer.color = vec4<f32>(0.0);
     gbuffer.pos = in.position;
     gbuffer.normal = in.norm;
-    gbuffer.lum = vec4<f32>(1.0, 1.0, 1.0, 1.0);
+    gbuffer.lum = textureSample(g_diffuse, samplr, in.tex_coord);
 
     return gbuffer;
 }
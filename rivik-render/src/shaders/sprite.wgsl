@@ -0,0 +1,107 @@
+//  This Source Code Form is subject to the terms of the Mozilla Public License,
+//  v. 2.0. If a copy of the MPL was not distributed with this file, You can
+//  obtain one at http://mozilla.org/MPL/2.0/.
+
+// A textured quad that turns to face the camera
+//
+// The quad is centered on the transform's origin and cut out where the texture's alpha is below
+// one half so sprites can be drawn with the rest of the opaque geometry. Define DEBUG and the
+// defines of debug_view.wgsl to draw the sprite in a debug view.
+
+#define TRANSFORM_GROUP 1
+#include "transform.wgsl"
+#include "gbuffer_targets.wgsl"
+#ifdef DEBUG
+#include "debug_view.wgsl"
+#endif
+
+struct Sprite {
+    // xy: uv of the frame's corner, zw: uv size of the frame
+    frame: vec4<f32>,
+    // xy: size in local units, z: 1 to only turn around the Y axis
+    params: vec4<f32>,
+}
+
+@group(0)
+@binding(0)
+var samplr: sampler;
+
+@group(0)
+@binding(1)
+var g_diffuse: texture_2d<f32>;
+
+@group(2)
+@binding(0)
+var<uniform> sprite: Sprite;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) tex_coord: vec2<f32>,
+    @location(1) view_position: vec4<f32>,
+    @location(2) norm: vec4<f32>,
+    @location(3) current: vec4<f32>,
+    @location(4) previous: vec4<f32>,
+#ifdef DEBUG
+    @location(5) bary: vec3<f32>,
+#endif
+}
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex: u32) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-0.5, -0.5),
+        vec2<f32>(0.5, -0.5),
+        vec2<f32>(0.5, 0.5),
+        vec2<f32>(-0.5, -0.5),
+        vec2<f32>(0.5, 0.5),
+        vec2<f32>(-0.5, 0.5),
+    );
+    let corner = corners[vertex];
+
+    // the rows of the view matrix are the camera's axes in world space
+    var right = vec3<f32>(camera.view[0].x, camera.view[1].x, camera.view[2].x);
+    var up = vec3<f32>(camera.view[0].y, camera.view[1].y, camera.view[2].y);
+    if sprite.params.z > 0.5 {
+        up = vec3<f32>(0.0, 1.0, 0.0);
+        right = normalize(vec3<f32>(right.x, 0.0, right.z));
+    }
+    let norm = cross(right, up);
+
+    // keep the scale of the transform but not its rotation
+    let scale = vec2<f32>(length(transform.model[0].xyz), length(transform.model[1].xyz));
+    let offset = (right * corner.x * scale.x * sprite.params.x)
+        + (up * corner.y * scale.y * sprite.params.y);
+    let world = vec4<f32>(transform.model[3].xyz + offset, 1.0);
+    let prev_world = vec4<f32>(transform.prev_model[3].xyz + offset, 1.0);
+
+    var out: VertexOutput;
+    out.position = camera.view_proj * world;
+    out.tex_coord = sprite.frame.xy + vec2<f32>(corner.x + 0.5, 0.5 - corner.y) * sprite.frame.zw;
+    out.view_position = camera.view * world;
+    out.norm = camera.view * vec4<f32>(norm, 0.0);
+    out.current = camera.unjittered_view_proj * world;
+    out.previous = camera.prev_view_proj * prev_world;
+#ifdef DEBUG
+    out.bary = corner_bary(vertex);
+#endif
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> GBuffer {
+    let color = textureSample(g_diffuse, samplr, in.tex_coord);
+    if color.a < 0.5 {
+        discard;
+    }
+
+#ifdef DEBUG
+    return debug_view(in.bary, vec4<f32>(in.view_position.xyz, 1.0));
+#else
+    var gbuffer: GBuffer;
+    gbuffer.color = color;
+    gbuffer.pos = vec4<f32>(in.view_position.xyz, 1.0);
+    gbuffer.normal = vec4<f32>(normalize(in.norm.xyz), 0.0);
+    gbuffer.velocity = motion_vector(in.current, in.previous);
+    return gbuffer;
+#endif
+}
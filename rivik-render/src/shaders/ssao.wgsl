@@ -0,0 +1,112 @@
+//  This Source Code Form is subject to the terms of the Mozilla Public License,
+//  v. 2.0. If a copy of the MPL was not distributed with this file, You can
+//  obtain one at http://mozilla.org/MPL/2.0/.
+
+// Screen space ambient occlusion
+//
+// Without BLUR defined this estimates how much of the hemisphere around each pixel's normal is
+// blocked by nearby geometry, with BLUR defined it smooths the noisy estimate into the G-buffer
+
+#include "fullscreen.wgsl"
+#include "transform.wgsl"
+
+struct Params {
+    // x, y, width, height of the viewport in pixels
+    viewport: vec4<f32>,
+    radius: f32,
+    strength: f32,
+    samples: f32,
+    _pad: f32,
+}
+
+var<push_constant> params: Params;
+
+// keep texel lookups inside the viewport being drawn
+fn clamp_texel(texel: vec2<i32>) -> vec2<i32> {
+    let lo = vec2<i32>(params.viewport.xy);
+    let hi = lo + vec2<i32>(params.viewport.zw) - vec2<i32>(1, 1);
+    return clamp(texel, lo, hi);
+}
+
+#ifdef BLUR
+@group(0)
+@binding(0)
+var raw_ao: texture_2d<f32>;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // the noise pattern repeats every 4 pixels so a 4x4 box removes it
+    let coord = vec2<i32>(in.pos.xy);
+    var sum = 0.0;
+    for (var x = -2; x < 2; x = x + 1) {
+        for (var y = -2; y < 2; y = y + 1) {
+            sum = sum + textureLoad(raw_ao, clamp_texel(coord + vec2<i32>(x, y)), 0).r;
+        }
+    }
+    return vec4<f32>(sum / 16.0, 0.0, 0.0, 0.0);
+}
+#else
+@group(0)
+@binding(0)
+var g_pos: texture_2d<f32>;
+
+@group(0)
+@binding(1)
+var g_norm: texture_2d<f32>;
+
+@group(1)
+@binding(0)
+var<uniform> camera: Camera;
+
+// random angle that repeats every 4x4 pixels
+fn noise(coord: vec2<i32>) -> f32 {
+    let i = (coord.x & 3) + (coord.y & 3) * 4;
+    return f32(i) / 16.0 * 6.283185;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let coord = vec2<i32>(in.pos.xy);
+    let pos = textureLoad(g_pos, coord, 0);
+    if pos.w <= 0.0 {
+        return vec4<f32>(0.0);
+    }
+    let norm = normalize(textureLoad(g_norm, coord, 0).xyz);
+
+    // basis around the normal rotated by the noise so neighbouring pixels use different samples
+    let angle = noise(coord);
+    let rand = vec3<f32>(cos(angle), sin(angle), 0.0);
+    let tangent = normalize(rand - norm * dot(rand, norm) + vec3<f32>(0.0, 0.0, 0.0001));
+    let bitangent = cross(norm, tangent);
+
+    let proj = camera_projection(camera);
+    let samples = max(u32(params.samples), 1u);
+    var occlusion = 0.0;
+    for (var i = 0u; i < samples; i = i + 1u) {
+        // points spiral out from the normal over the hemisphere, denser close to the pixel
+        let t = (f32(i) + 0.5) / f32(samples);
+        let phi = f32(i) * 2.399963;
+        let r = sqrt(1.0 - t * t);
+        let dir = tangent * (cos(phi) * r) + bitangent * (sin(phi) * r) + norm * t;
+        let scale = mix(0.1, 1.0, t * t);
+        let sample_pos = pos.xyz + dir * (params.radius * scale);
+
+        let clip = proj * vec4<f32>(sample_pos, 1.0);
+        let uv = clip.xy / clip.w * vec2<f32>(0.5, -0.5) + 0.5;
+        let texel = clamp_texel(vec2<i32>(params.viewport.xy + uv * params.viewport.zw));
+        let scene = textureLoad(g_pos, texel, 0);
+        if scene.w <= 0.0 {
+            continue;
+        }
+
+        // ignore geometry far in front of the pixel so silhouettes don't darken the background
+        let range = smoothstep(0.0, 1.0, params.radius / abs(pos.z - scene.z));
+        if scene.z >= sample_pos.z + 0.025 {
+            occlusion = occlusion + range;
+        }
+    }
+
+    let ao = clamp(occlusion / f32(samples) * params.strength, 0.0, 1.0);
+    return vec4<f32>(ao, 0.0, 0.0, 0.0);
+}
+#endif
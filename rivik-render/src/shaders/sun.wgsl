@@ -2,6 +2,12 @@
 //  v. 2.0. If a copy of the MPL was not distributed with this file, You can
 //  obtain one at http://mozilla.org/MPL/2.0/.
 
+#define CUSTOM_VERTEX
+#define TRANSFORM_GROUP 2
+#include "fullscreen.wgsl"
+#include "transform.wgsl"
+#include "gbuffer.wgsl"
+
 struct VertexOutput{
     @builtin(position) pos: vec4<f32>,
     @location(0) uv: vec2<f32>,
@@ -18,62 +24,27 @@ struct LightData{
 @binding(0)
 var<uniform> light_data: LightData;
 
-
-struct Transform {
-    mvp: mat4x4<f32>,
-    mv: mat4x4<f32>,
-    mv_norm: mat4x4<f32>,
-}
-
-@group(2)
-@binding(0)
-var<uniform> transform: Transform;
-
 @vertex
 fn vs_main(@builtin(vertex_index) in_vertex_index: u32) -> VertexOutput {
     var out: VertexOutput;
-    let i = i32(in_vertex_index) + 1;
-    let x = f32(1 - (i & 2));
-    let y = f32(1 - ((i & 1) * 2));
-
-    out.pos = vec4<f32>(x, y, 0.0, 1.0);
-    out.uv = vec2<f32>((x+1.0)/2.0, 1.0-(y+1.0)/2.0);
+    out.pos = fullscreen_pos(in_vertex_index);
+    out.uv = fullscreen_uv(out.pos);
     out.color = light_data.color;
-    out.dir = transform.mv * vec4<f32>(light_data.direction.xyz, 0.0);
+    out.dir = camera.view * transform.model * vec4<f32>(light_data.direction.xyz, 0.0);
     return out;
 }
 
-@group(0)
-@binding(0)
-var samplr: sampler;
-
-@group(0)
-@binding(1)
-var g_color: texture_2d<f32>;
-
-@group(0)
-@binding(2)
-var g_pos: texture_2d<f32>;
-
-@group(0)
-@binding(3)
-var g_norm: texture_2d<f32>;
-
-@group(0)
-@binding(4)
-var g_lum: texture_2d<f32>;
-
-
 fn sq_len(v: vec3<f32>) -> f32 {
     return v.x * v.x + v.y * v.y;
 }
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    let norm = textureSample(g_norm, samplr, in.uv).xyz;
-    let col = textureSample(g_color, samplr, in.uv);
-    let pos = textureSample(g_pos, samplr, in.uv).xyz;
-    let lum = textureSample(g_lum, samplr, in.uv);
+    let uv = gbuffer_uv(in.pos);
+    let norm = textureSample(g_norm, samplr, uv).xyz;
+    let col = textureSample(g_color, samplr, uv);
+    let pos = textureSample(g_pos, samplr, uv).xyz;
+    let lum = textureSample(g_lum, samplr, uv);
 
     let light_dir = normalize(in.dir.xyz);
     var power = max(dot(norm, light_dir), 0.0);
@@ -91,9 +62,10 @@ fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     // let towardsLight = dot(norm, normalize(light_dir));
     // let lightIntensity = step(0., towardsLight);
 
-    // dont do light computation if there is lum; (it will be done in ambient)
+    // dont do light computation if there is lum; (it is added by the emission pass)
     let light = col * ((power + spec) * in.color);
-    let inv_lum = vec4<f32>(1.0, 1.0, 1.0, 1.0) - lum;
+    // emissive surfaces can be brighter than 1, so keep this from going negative
+    let inv_lum = max(vec4<f32>(1.0, 1.0, 1.0, 1.0) - lum, vec4<f32>(0.0));
 
     return min(light, inv_lum);
 }
@@ -0,0 +1,56 @@
+//  This Source Code Form is subject to the terms of the Mozilla Public License,
+//  v. 2.0. If a copy of the MPL was not distributed with this file, You can
+//  obtain one at http://mozilla.org/MPL/2.0/.
+
+// Temporal antialiasing resolve
+//
+// Reprojects the previous frame using the G-buffer's motion vectors and clamps it to the
+// neighbourhood of the current pixel to avoid ghosting
+
+#include "fullscreen.wgsl"
+
+@group(0)
+@binding(0)
+var samplr: sampler;
+
+@group(0)
+@binding(1)
+var hdr: texture_2d<f32>;
+
+@group(0)
+@binding(2)
+var history: texture_2d<f32>;
+
+@group(0)
+@binding(3)
+var velocity: texture_2d<f32>;
+
+// how much of the current frame is blended into the history
+var<push_constant> blend: f32;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let dims = vec2<i32>(textureDimensions(hdr));
+    let texel = vec2<i32>(in.pos.xy);
+    let current = textureLoad(hdr, texel, 0);
+
+    // bounds of the colors around this pixel
+    var lo = current;
+    var hi = current;
+    for (var y = -1; y <= 1; y = y + 1) {
+        for (var x = -1; x <= 1; x = x + 1) {
+            let coord = clamp(texel + vec2<i32>(x, y), vec2<i32>(0), dims - 1);
+            let neighbour = textureLoad(hdr, coord, 0);
+            lo = min(lo, neighbour);
+            hi = max(hi, neighbour);
+        }
+    }
+
+    let prev_uv = in.uv - textureLoad(velocity, texel, 0).xy;
+    if any(prev_uv < vec2<f32>(0.0)) || any(prev_uv > vec2<f32>(1.0)) {
+        return current;
+    }
+
+    let prev = clamp(textureSampleLevel(history, samplr, prev_uv, 0.0), lo, hi);
+    return mix(prev, current, blend);
+}
@@ -0,0 +1,17 @@
+//  This Source Code Form is subject to the terms of the Mozilla Public License,
+//  v. 2.0. If a copy of the MPL was not distributed with this file, You can
+//  obtain one at http://mozilla.org/MPL/2.0/.
+
+// Tone maps the lit image of a render target into its displayable texture
+
+#include "fullscreen.wgsl"
+#include "tonemap.wgsl"
+
+@group(0) @binding(0)
+var hdr: texture_2d<f32>;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let col = textureLoad(hdr, vec2<i32>(in.pos.xy), 0);
+    return vec4<f32>(tonemap(col.rgb), 1.0);
+}
@@ -0,0 +1,93 @@
+//  This Source Code Form is subject to the terms of the Mozilla Public License,
+//  v. 2.0. If a copy of the MPL was not distributed with this file, You can
+//  obtain one at http://mozilla.org/MPL/2.0/.
+
+// Heightmap terrain blending four textures with a splat map
+
+struct VertexOutput {
+    // uv across the whole terrain
+    @location(0) tex_coord: vec2<f32>,
+    @location(1) norm: vec4<f32>,
+    @builtin(position) position: vec4<f32>,
+    @location(2) view_position: vec4<f32>,
+    @location(3) current: vec4<f32>,
+    @location(4) previous: vec4<f32>,
+}
+
+#define TRANSFORM_GROUP 1
+#include "transform.wgsl"
+#include "gbuffer_targets.wgsl"
+
+@vertex
+fn vs_main(
+    @location(0) position: vec3<f32>,
+    @location(1) norm: vec3<f32>,
+    @location(2) tex_coord: vec2<f32>,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.tex_coord = tex_coord;
+    out.position = clip_position(position);
+    out.view_position = view_position(position);
+    out.norm = view_normal(norm);
+    out.current = current_position(position);
+    out.previous = previous_position(position);
+    return out;
+}
+
+struct TerrainData {
+    // x: number of times the layers repeat across the terrain
+    tiling: vec4<f32>,
+}
+
+@group(0)
+@binding(0)
+var samplr: sampler;
+
+// rgba weights of the four layers
+@group(0)
+@binding(1)
+var splat: texture_2d<f32>;
+
+@group(0)
+@binding(2)
+var layer_0: texture_2d<f32>;
+
+@group(0)
+@binding(3)
+var layer_1: texture_2d<f32>;
+
+@group(0)
+@binding(4)
+var layer_2: texture_2d<f32>;
+
+@group(0)
+@binding(5)
+var layer_3: texture_2d<f32>;
+
+@group(0)
+@binding(6)
+var<uniform> terrain: TerrainData;
+
+@fragment
+fn fs_main(in: VertexOutput) -> GBuffer {
+    var gbuffer: GBuffer;
+
+    let weights = textureSample(splat, samplr, in.tex_coord);
+    // unpainted areas show the first layer
+    let total = weights.r + weights.g + weights.b + weights.a;
+    var w = vec4<f32>(1.0, 0.0, 0.0, 0.0);
+    if total > 0.0001 {
+        w = weights / total;
+    }
+
+    let uv = in.tex_coord * terrain.tiling.x;
+    gbuffer.color = textureSample(layer_0, samplr, uv) * w.r
+        + textureSample(layer_1, samplr, uv) * w.g
+        + textureSample(layer_2, samplr, uv) * w.b
+        + textureSample(layer_3, samplr, uv) * w.a;
+    gbuffer.pos = in.view_position;
+    gbuffer.normal = vec4<f32>(normalize(in.norm.xyz), 0.0);
+    gbuffer.velocity = motion_vector(in.current, in.previous);
+
+    return gbuffer;
+}
@@ -0,0 +1,78 @@
+//  This Source Code Form is subject to the terms of the Mozilla Public License,
+//  v. 2.0. If a copy of the MPL was not distributed with this file, You can
+//  obtain one at http://mozilla.org/MPL/2.0/.
+
+// Layout of a transform buffer and the camera it is drawn from
+//
+// Define TRANSFORM_GROUP as the bind group index to declare the `transform` and `camera`
+// uniforms along with helpers for moving vertices into view and clip space
+
+struct Transform {
+    model: mat4x4<f32>,
+    model_norm: mat4x4<f32>,
+    prev_model: mat4x4<f32>,
+}
+
+struct Camera {
+    // jittered when using temporal antialiasing
+    view_proj: mat4x4<f32>,
+    view: mat4x4<f32>,
+    prev_view_proj: mat4x4<f32>,
+    unjittered_view_proj: mat4x4<f32>,
+    jitter: vec4<f32>,
+}
+
+#ifdef TRANSFORM_GROUP
+@group(TRANSFORM_GROUP)
+@binding(0)
+var<uniform> transform: Transform;
+
+@group(TRANSFORM_GROUP)
+@binding(1)
+var<uniform> camera: Camera;
+
+fn clip_position(pos: vec3<f32>) -> vec4<f32> {
+    return camera.view_proj * transform.model * vec4<f32>(pos, 1.0);
+}
+
+fn view_position(pos: vec3<f32>) -> vec4<f32> {
+    return camera.view * transform.model * vec4<f32>(pos, 1.0);
+}
+
+fn view_normal(norm: vec3<f32>) -> vec4<f32> {
+    return camera.view * transform.model_norm * vec4<f32>(norm, 0.0);
+}
+
+// unjittered clip position of this frame, used for motion vectors
+fn current_position(pos: vec3<f32>) -> vec4<f32> {
+    return camera.unjittered_view_proj * transform.model * vec4<f32>(pos, 1.0);
+}
+
+// unjittered clip position of the previous frame, used for motion vectors
+fn previous_position(pos: vec3<f32>) -> vec4<f32> {
+    return camera.prev_view_proj * transform.prev_model * vec4<f32>(pos, 1.0);
+}
+#endif
+
+// inverse of a view matrix without scale
+fn inverse_rigid(m: mat4x4<f32>) -> mat4x4<f32> {
+    let r = transpose(mat3x3<f32>(m[0].xyz, m[1].xyz, m[2].xyz));
+    let t = -(r * m[3].xyz);
+    return mat4x4<f32>(
+        vec4<f32>(r[0], 0.0),
+        vec4<f32>(r[1], 0.0),
+        vec4<f32>(r[2], 0.0),
+        vec4<f32>(t, 1.0),
+    );
+}
+
+// the unjittered projection matrix of a camera
+fn camera_projection(cam: Camera) -> mat4x4<f32> {
+    return cam.unjittered_view_proj * inverse_rigid(cam.view);
+}
+
+// screen space motion in uv units between two clip space positions
+fn motion_vector(current: vec4<f32>, previous: vec4<f32>) -> vec4<f32> {
+    let motion = (current.xy / current.w - previous.xy / previous.w) * vec2<f32>(0.5, -0.5);
+    return vec4<f32>(motion, 0.0, 0.0);
+}
@@ -0,0 +1,293 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public License,
+ * v. 2.0. If a copy of the MPL was not distributed with this file, You can
+ * obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+//! Screen space ambient occlusion
+//!
+//! Occlusion is estimated from the positions and normals in the G-buffer after the geometry
+//! pass, blurred and stored in the G-buffer where [`AmbientLight`](crate::lights::AmbientLight)
+//! darkens corners and creases with it. It is enabled with
+//! [`set_ambient_occlusion`](crate::context::set_ambient_occlusion).
+
+use std::{
+    borrow::Cow,
+    num::NonZeroU64,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use once_cell::sync::OnceCell;
+use wgpu::{
+    BindGroup, BindGroupLayout, BindGroupLayoutEntry, BindingType, ColorTargetState, ColorWrites,
+    CommandEncoder, Extent3d, LoadOp, PushConstantRange, RenderPipeline, ShaderStages,
+    TextureDescriptor, TextureDimension, TextureSampleType, TextureUsages, TextureView,
+    TextureViewDimension,
+};
+
+use crate::{
+    camera::{self, Viewport, CAMERA_SIZE},
+    context::{device, gbuffer, settings},
+    pipeline::GBuffer,
+    shader,
+    stats::{self, DrawStats},
+};
+
+/// Settings of the ambient occlusion pass
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ssao {
+    /// How dark fully occluded areas get, 1 removes all ambient light from them
+    pub strength: f32,
+    /// Distance in world units around each pixel that is checked for occluders
+    pub radius: f32,
+    /// Number of samples taken for each pixel, more samples are slower but less noisy
+    pub samples: u32,
+}
+
+impl Default for Ssao {
+    fn default() -> Self {
+        Self {
+            strength: 1.0,
+            radius: 0.5,
+            samples: 16,
+        }
+    }
+}
+
+impl Ssao {
+    fn push_constants(&self, viewport: &Viewport) -> [f32; 8] {
+        let (x, y, w, h) = viewport.pixels();
+        [
+            x as f32,
+            y as f32,
+            w as f32,
+            h as f32,
+            self.radius,
+            self.strength,
+            self.samples as f32,
+            0.0,
+        ]
+    }
+}
+
+static OCCLUSION: OnceCell<Occlusion> = OnceCell::new();
+
+struct Occlusion {
+    raw_view: TextureView,
+    gbuffer_group: BindGroup,
+    camera_group: BindGroup,
+    raw_group: BindGroup,
+    occlusion: RenderPipeline,
+    blur: RenderPipeline,
+    /// is the G-buffer holding occlusion that should be cleared when disabled
+    written: AtomicBool,
+}
+
+/// Compute ambient occlusion for the view drawn into `viewport`
+///
+/// The occlusion buffer is cleared first when `clear` is set, the camera should already be bound
+pub(crate) fn occlusion(encoder: &mut CommandEncoder, viewport: &Viewport, clear: bool) {
+    let ssao = match settings().read().unwrap().ambient_occlusion {
+        Some(ssao) => ssao,
+        None => {
+            // leave no stale occlusion behind when disabled
+            if let Some(occlusion) = OCCLUSION.get() {
+                if clear && occlusion.written.swap(false, Ordering::Relaxed) {
+                    let _ = target(encoder, &gbuffer().ao_view, true);
+                }
+            }
+            return;
+        }
+    };
+
+    let occlusion = OCCLUSION.get_or_init(Occlusion::new);
+    occlusion.written.store(true, Ordering::Relaxed);
+    let push = ssao.push_constants(viewport);
+    {
+        let mut rpass = target(encoder, &occlusion.raw_view, clear);
+        viewport.apply(&mut rpass);
+        rpass.set_pipeline(&occlusion.occlusion);
+        rpass.set_push_constants(ShaderStages::FRAGMENT, 0, bytemuck::cast_slice(&push));
+        rpass.set_bind_group(0, &occlusion.gbuffer_group, &[]);
+        rpass.set_bind_group(1, &occlusion.camera_group, &[]);
+        rpass.draw(0..7, 0..1);
+        stats::record_draw(DrawStats::new(1, 7));
+    }
+    {
+        let mut rpass = target(encoder, &gbuffer().ao_view, clear);
+        viewport.apply(&mut rpass);
+        rpass.set_pipeline(&occlusion.blur);
+        rpass.set_push_constants(ShaderStages::FRAGMENT, 0, bytemuck::cast_slice(&push));
+        rpass.set_bind_group(0, &occlusion.raw_group, &[]);
+        rpass.draw(0..7, 0..1);
+        stats::record_draw(DrawStats::new(1, 7));
+    }
+}
+
+fn target<'a>(
+    encoder: &'a mut CommandEncoder,
+    view: &'a TextureView,
+    clear: bool,
+) -> wgpu::RenderPass<'a> {
+    let load = if clear {
+        LoadOp::Clear(wgpu::Color::BLACK)
+    } else {
+        LoadOp::Load
+    };
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: Some("Ambient Occlusion"),
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view,
+            resolve_target: None,
+            ops: wgpu::Operations { load, store: true },
+        })],
+        depth_stencil_attachment: None,
+    })
+}
+
+impl Occlusion {
+    fn new() -> Self {
+        let device = device();
+        let gbuffer = gbuffer();
+        let (width, height) = gbuffer.size();
+
+        let raw_view = device
+            .create_texture(&TextureDescriptor {
+                label: Some("Raw Ambient Occlusion"),
+                size: Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: GBuffer::ao_format(),
+                usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+                view_formats: Default::default(),
+            })
+            .create_view(&Default::default());
+
+        let gbuffer_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Ambient Occlusion"),
+            entries: &[texture_entry(0), texture_entry(1)],
+        });
+        let camera_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Ambient Occlusion Camera"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: NonZeroU64::new(CAMERA_SIZE),
+                },
+                count: None,
+            }],
+        });
+        let raw_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Ambient Occlusion Blur"),
+            entries: &[texture_entry(0)],
+        });
+
+        let gbuffer_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Ambient Occlusion"),
+            layout: &gbuffer_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&gbuffer.pos_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&gbuffer.norm_view),
+                },
+            ],
+        });
+        let camera_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Ambient Occlusion Camera"),
+            layout: &camera_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera::buffer().as_entire_binding(),
+            }],
+        });
+        let raw_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Ambient Occlusion Blur"),
+            layout: &raw_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&raw_view),
+            }],
+        });
+
+        Self {
+            occlusion: pipeline(
+                &shader!("shaders/ssao.wgsl").unwrap(),
+                &[&gbuffer_layout, &camera_layout],
+            ),
+            blur: pipeline(
+                &shader!("shaders/ssao.wgsl", "BLUR").unwrap(),
+                &[&raw_layout],
+            ),
+            raw_view,
+            gbuffer_group,
+            camera_group,
+            raw_group,
+            written: AtomicBool::new(false),
+        }
+    }
+}
+
+fn texture_entry(binding: u32) -> BindGroupLayoutEntry {
+    BindGroupLayoutEntry {
+        binding,
+        visibility: ShaderStages::FRAGMENT,
+        ty: BindingType::Texture {
+            multisampled: false,
+            sample_type: TextureSampleType::Float { filterable: false },
+            view_dimension: TextureViewDimension::D2,
+        },
+        count: None,
+    }
+}
+
+fn pipeline(source: &str, layouts: &[&BindGroupLayout]) -> RenderPipeline {
+    let device = device();
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: None,
+        source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(source)),
+    });
+
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: None,
+        bind_group_layouts: layouts,
+        push_constant_ranges: &[PushConstantRange {
+            stages: ShaderStages::FRAGMENT,
+            range: 0..32,
+        }],
+    });
+
+    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+        label: Some("Ambient Occlusion"),
+        layout: Some(&pipeline_layout),
+        vertex: wgpu::VertexState {
+            module: &shader,
+            entry_point: "vs_main",
+            buffers: &[],
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: &shader,
+            entry_point: "fs_main",
+            targets: &[Some(ColorTargetState {
+                format: GBuffer::ao_format(),
+                blend: None,
+                write_mask: ColorWrites::ALL,
+            })],
+        }),
+        primitive: wgpu::PrimitiveState::default(),
+        depth_stencil: None,
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+    })
+}
@@ -0,0 +1,151 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public License,
+ * v. 2.0. If a copy of the MPL was not distributed with this file, You can
+ * obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+//! Per-frame render statistics
+//!
+//! Counters are gathered while a [`Frame`](crate::Frame) is built and snapshotted when it is
+//! presented. Draw calls and vertices come from [`Drawable::stats`](crate::Drawable::stats) since
+//! the contents of a render bundle can't be inspected. Live buffers and textures only include
+//! assets created through the [`load`](crate::load) formats.
+
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    RwLock,
+};
+
+use egui::{Grid, Ui};
+
+static DRAW_CALLS: AtomicU64 = AtomicU64::new(0);
+static BUNDLES: AtomicU64 = AtomicU64::new(0);
+static VERTICES: AtomicU64 = AtomicU64::new(0);
+static BUFFER_WRITES: AtomicU64 = AtomicU64::new(0);
+static TEXTURE_UPLOADS: AtomicU64 = AtomicU64::new(0);
+static LIVE_BUFFERS: AtomicU64 = AtomicU64::new(0);
+static LIVE_TEXTURES: AtomicU64 = AtomicU64::new(0);
+
+static LAST_FRAME: RwLock<RenderStats> = RwLock::new(RenderStats {
+    draw_calls: 0,
+    bundles: 0,
+    vertices: 0,
+    buffer_writes: 0,
+    texture_uploads: 0,
+    live_buffers: 0,
+    live_textures: 0,
+});
+
+/// The work recorded into a render bundle
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DrawStats {
+    /// Number of draw calls
+    pub draw_calls: u32,
+    /// Number of vertices submitted by the draw calls
+    pub vertices: u32,
+}
+
+impl DrawStats {
+    /// Create stats for a bundle
+    pub fn new(draw_calls: u32, vertices: u32) -> Self {
+        Self {
+            draw_calls,
+            vertices,
+        }
+    }
+}
+
+/// Counters for a single frame
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RenderStats {
+    /// Draw calls issued
+    pub draw_calls: u64,
+    /// Render bundles executed
+    pub bundles: u64,
+    /// Vertices submitted
+    pub vertices: u64,
+    /// Writes queued to GPU buffers
+    pub buffer_writes: u64,
+    /// Textures uploaded to the GPU
+    pub texture_uploads: u64,
+    /// GPU buffers that are currently alive
+    pub live_buffers: u64,
+    /// GPU textures that are currently alive
+    pub live_textures: u64,
+}
+
+/// Fetch the counters of the last presented frame
+pub fn last_frame() -> RenderStats {
+    *LAST_FRAME.read().unwrap()
+}
+
+/// Count draw calls that were recorded outside of a render bundle
+pub fn record_draw(stats: DrawStats) {
+    let _ = DRAW_CALLS.fetch_add(stats.draw_calls as u64, Ordering::Relaxed);
+    let _ = VERTICES.fetch_add(stats.vertices as u64, Ordering::Relaxed);
+}
+
+/// Count a render bundle being executed
+pub fn record_bundle(stats: DrawStats) {
+    let _ = BUNDLES.fetch_add(1, Ordering::Relaxed);
+    record_draw(stats);
+}
+
+/// Count a write to a GPU buffer
+pub fn record_buffer_write() {
+    let _ = BUFFER_WRITES.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Count a texture upload
+pub fn record_texture_upload() {
+    let _ = TEXTURE_UPLOADS.fetch_add(1, Ordering::Relaxed);
+}
+
+pub(crate) fn buffers_created(count: u64) {
+    let _ = LIVE_BUFFERS.fetch_add(count, Ordering::Relaxed);
+}
+
+pub(crate) fn buffers_dropped(count: u64) {
+    let _ = LIVE_BUFFERS.fetch_sub(count, Ordering::Relaxed);
+}
+
+pub(crate) fn texture_created() {
+    let _ = LIVE_TEXTURES.fetch_add(1, Ordering::Relaxed);
+}
+
+pub(crate) fn texture_dropped() {
+    let _ = LIVE_TEXTURES.fetch_sub(1, Ordering::Relaxed);
+}
+
+/// Snapshot this frame's counters and reset them for the next frame
+pub(crate) fn end_frame() {
+    *LAST_FRAME.write().unwrap() = RenderStats {
+        draw_calls: DRAW_CALLS.swap(0, Ordering::Relaxed),
+        bundles: BUNDLES.swap(0, Ordering::Relaxed),
+        vertices: VERTICES.swap(0, Ordering::Relaxed),
+        buffer_writes: BUFFER_WRITES.swap(0, Ordering::Relaxed),
+        texture_uploads: TEXTURE_UPLOADS.swap(0, Ordering::Relaxed),
+        live_buffers: LIVE_BUFFERS.load(Ordering::Relaxed),
+        live_textures: LIVE_TEXTURES.load(Ordering::Relaxed),
+    };
+}
+
+/// Display a frame's counters in an EGUI grid
+pub fn display_stats(ui: &mut Ui, stats: &RenderStats) {
+    let _ = Grid::new("Render Stats").striped(true).show(ui, |ui| {
+        let rows = [
+            ("Draw calls", stats.draw_calls),
+            ("Render bundles", stats.bundles),
+            ("Vertices", stats.vertices),
+            ("Buffer writes", stats.buffer_writes),
+            ("Texture uploads", stats.texture_uploads),
+            ("Live buffers", stats.live_buffers),
+            ("Live textures", stats.live_textures),
+        ];
+        for (name, value) in rows {
+            let _ = ui.label(name);
+            let _ = ui.label(value.to_string());
+            ui.end_row();
+        }
+    });
+}
@@ -0,0 +1,254 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public License,
+ * v. 2.0. If a copy of the MPL was not distributed with this file, You can
+ * obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+//! Offscreen render targets
+//!
+//! A [`RenderTarget`] draws a second view of the scene from its own [`Camera`] into a texture
+//! which can be used by a material or shown in EGUI. Targets are drawn with
+//! [`Frame::draw_target`](crate::Frame::draw_target) and are rendered before the frame's own
+//! geometry. The lit image is tone mapped into an sRGB texture, so it is ready to be displayed.
+//!
+//! A target either owns a G-buffer of its own size or shares the frame's G-buffer. Sharing
+//! saves memory but the target must be the same size as the frame's G-buffer.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    iter, mem,
+    rc::Rc,
+    sync::Arc,
+};
+
+use egui::TextureId;
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindingResource, Color, Extent3d, FilterMode,
+    LoadOp, Operations, RenderBundle, RenderPassColorAttachment, RenderPassDescriptor,
+    TextureDescriptor, TextureDimension, TextureUsages, TextureView,
+};
+
+use crate::{
+    camera::Camera,
+    context::{device, egui_render, gbuffer},
+    debug::DebugMode,
+    graph::{Pass, Resource},
+    lights::{Light, LightId},
+    load::LoadedTexture,
+    pipeline::{tonemap, GBuffer},
+    stats::{self, DrawStats},
+    Drawable,
+};
+
+/// A texture that a view of the scene is rendered into
+pub struct RenderTarget {
+    gbuffer: Option<GBuffer>,
+    texture: Rc<Arc<LoadedTexture>>,
+    /// the HDR buffer of the G-buffer this target is drawn with, bound for tone mapping
+    tonemap: BindGroup,
+    /// bundles lighting our own G-buffer, kept for the lights drawn last time
+    lights: RefCell<HashMap<LightId, Rc<RenderBundle>>>,
+    camera: Camera,
+    size: (u32, u32),
+    egui: Cell<Option<TextureId>>,
+}
+
+impl RenderTarget {
+    /// Create a render target with its own G-buffer
+    pub fn new(width: u32, height: u32) -> Self {
+        let sample_count = gbuffer().sample_count();
+        Self::build(
+            Some(GBuffer::new(width, height, sample_count)),
+            (width, height),
+        )
+    }
+
+    /// Create a render target that is drawn using the frame's G-buffer
+    pub fn shared() -> Self {
+        Self::build(None, gbuffer().size())
+    }
+
+    fn build(own: Option<GBuffer>, (width, height): (u32, u32)) -> Self {
+        let texture = device().create_texture(&TextureDescriptor {
+            label: Some("Render Target"),
+            size: Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: tonemap::OUTPUT_FORMAT,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::RENDER_ATTACHMENT,
+            view_formats: Default::default(),
+        });
+        let view = texture.create_view(&Default::default());
+        stats::texture_created();
+
+        let hdr = &own.as_ref().unwrap_or_else(|| gbuffer()).hdr_view;
+        let tonemap = device().create_bind_group(&BindGroupDescriptor {
+            label: Some("Render Target"),
+            layout: tonemap::layout(),
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::TextureView(hdr),
+            }],
+        });
+
+        Self {
+            gbuffer: own,
+            texture: Rc::new(Arc::new(LoadedTexture(texture, view))),
+            tonemap,
+            lights: RefCell::new(HashMap::new()),
+            camera: Camera::new(),
+            size: (width, height),
+            egui: Cell::new(None),
+        }
+    }
+
+    /// The camera this target is rendered from
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    /// The size of this target in pixels
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    /// Fetch the rendered texture for use in a material
+    pub fn texture(&self) -> Rc<Arc<LoadedTexture>> {
+        Rc::clone(&self.texture)
+    }
+
+    /// A view of the rendered texture
+    pub fn view(&self) -> &TextureView {
+        &self.texture.1
+    }
+
+    /// Register the rendered texture with EGUI so it can be drawn as an image
+    pub fn egui_texture(&self) -> TextureId {
+        if let Some(id) = self.egui.get() {
+            return id;
+        }
+
+        let id = egui_render().write().unwrap().register_native_texture(
+            device(),
+            self.view(),
+            FilterMode::Nearest,
+        );
+        self.egui.set(Some(id));
+        id
+    }
+
+    /// Fetch the bundles lighting our G-buffer, only lights that weren't drawn last time are
+    /// recorded
+    fn record_lights(&self, gbuffer: &GBuffer, lights: &[&dyn Light]) -> Vec<Rc<RenderBundle>> {
+        let mut cache = self.lights.borrow_mut();
+        let mut previous = mem::take(&mut *cache);
+        lights
+            .iter()
+            .map(|light| {
+                let id = light.id();
+                let bundle = cache
+                    .get(&id)
+                    .cloned()
+                    .or_else(|| previous.remove(&id))
+                    .unwrap_or_else(|| Rc::new(light.record(gbuffer)));
+                let _ = cache.insert(id, Rc::clone(&bundle));
+                bundle
+            })
+            .collect()
+    }
+
+    /// Build the pass that renders this target
+    ///
+    /// While a debug mode is active geometry is drawn with its debug bundles and lights are
+    /// skipped, like the frame's own views.
+    pub(crate) fn pass<'a>(
+        &'a self,
+        geom: &[&'a dyn Drawable],
+        lights: &[&'a dyn Light],
+        debug: DebugMode,
+    ) -> Pass<'a> {
+        let lights = if debug == DebugMode::Off { lights } else { &[] };
+        for obj in geom {
+            stats::record_bundle(obj.stats());
+        }
+        for light in lights {
+            stats::record_bundle(light.stats());
+        }
+        // emission and tone mapping
+        stats::record_draw(DrawStats::new(1, 7));
+        stats::record_draw(DrawStats::new(1, 7));
+
+        let geom: Vec<&'a RenderBundle> = geom
+            .iter()
+            .map(|g| {
+                let bundle = match debug {
+                    DebugMode::Off => None,
+                    mode => g.debug_bundle(mode),
+                };
+                bundle.unwrap_or_else(|| g.bundle())
+            })
+            .collect();
+
+        // a light's own bundle shades the frame's G-buffer so ours are recorded once and reused
+        let (recorded, shared) = match &self.gbuffer {
+            Some(gbuffer) => (self.record_lights(gbuffer, lights), vec![]),
+            None => (vec![], lights.iter().map(|l| l.bundle()).collect()),
+        };
+        let lights = lights.to_vec();
+
+        let pass = Pass::new("Render Target", move |ctx| {
+            let encoder = ctx.encoder();
+            let gbuffer = self.gbuffer.as_ref().unwrap_or_else(|| gbuffer());
+            self.camera.bind(encoder);
+            {
+                let mut rpass = gbuffer.rpass(encoder, Some(Color::BLACK));
+                rpass.execute_bundles(geom);
+            }
+            gbuffer.resolve(encoder);
+            for light in &lights {
+                light.prepare(encoder);
+            }
+            {
+                let mut rpass = gbuffer.light_rpass(encoder, true);
+                rpass.execute_bundles(iter::once(gbuffer.emission()));
+                rpass.execute_bundles(recorded.iter().map(|b| &**b).chain(shared));
+            }
+
+            let mut rpass = encoder.begin_render_pass(&RenderPassDescriptor {
+                label: Some("Render Target Tone Mapping"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: self.view(),
+                    resolve_target: None,
+                    ops: Operations {
+                        load: LoadOp::Clear(Color::BLACK),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            rpass.set_pipeline(tonemap::pipeline());
+            rpass.set_bind_group(0, &self.tonemap, &[]);
+            rpass.draw(0..7, 0..1);
+        });
+
+        // a shared target must be drawn before the frame uses the G-buffer
+        match self.gbuffer {
+            Some(_) => pass,
+            None => pass.write(Resource::GBuffer).write(Resource::Hdr),
+        }
+    }
+}
+
+impl Drop for RenderTarget {
+    fn drop(&mut self) {
+        if let Some(id) = self.egui.get() {
+            egui_render().write().unwrap().free_texture(&id);
+        }
+    }
+}
@@ -0,0 +1,237 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public License,
+ * v. 2.0. If a copy of the MPL was not distributed with this file, You can
+ * obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+//! GPU timestamp queries around render graph passes
+//!
+//! Timestamps are written into one of a small ring of query sets and read back asynchronously,
+//! so timings show up a few frames late instead of stalling the frame. Nothing is recorded if
+//! the adapter does not support timestamp queries.
+
+use std::sync::{
+    atomic::{AtomicU64, AtomicU8, AtomicUsize, Ordering},
+    Mutex, RwLock,
+};
+
+use once_cell::sync::OnceCell;
+use wgpu::{
+    Buffer, BufferDescriptor, BufferUsages, CommandEncoder, Features, Maintain, MapMode, QuerySet,
+    QuerySetDescriptor, QueryType,
+};
+
+use crate::context::{device, queue};
+
+/// Maximum number of passes that are timed in a frame
+const MAX_PASSES: u32 = 32;
+/// Number of frames that can be waiting on their timings at once
+const SLOTS: usize = 3;
+
+const FREE: u8 = 0;
+const RECORDING: u8 = 1;
+const MAPPING: u8 = 2;
+const READY: u8 = 3;
+
+static TIMER: OnceCell<Option<Timer>> = OnceCell::new();
+/// The frame number and timings of the newest frame that was read back
+static LAST_FRAME: RwLock<(u64, Vec<GpuSpan>)> = RwLock::new((0, Vec::new()));
+
+/// The time a pass spent on the GPU
+#[derive(Debug, Clone)]
+pub struct GpuSpan {
+    /// Name of the pass
+    pub name: &'static str,
+    /// Milliseconds since the first pass of the frame started
+    pub start: f64,
+    /// Milliseconds the pass took
+    pub duration: f64,
+}
+
+struct Timer {
+    slots: [Slot; SLOTS],
+    next: AtomicUsize,
+    /// number of frames that have started recording
+    frames: AtomicU64,
+}
+
+struct Slot {
+    query_set: QuerySet,
+    resolve: Buffer,
+    readback: Buffer,
+    names: Mutex<Vec<&'static str>>,
+    state: AtomicU8,
+    /// number of the frame recorded into this slot, later frames have larger numbers
+    frame: AtomicU64,
+}
+
+fn timer() -> Option<&'static Timer> {
+    TIMER
+        .get_or_init(|| {
+            let device = device();
+            if !device.features().contains(Features::TIMESTAMP_QUERY) {
+                return None;
+            }
+
+            let size = MAX_PASSES as u64 * 2 * 8;
+            let slot = || Slot {
+                query_set: device.create_query_set(&QuerySetDescriptor {
+                    label: Some("Pass timestamps"),
+                    ty: QueryType::Timestamp,
+                    count: MAX_PASSES * 2,
+                }),
+                resolve: device.create_buffer(&BufferDescriptor {
+                    label: Some("Pass timestamps"),
+                    size,
+                    usage: BufferUsages::QUERY_RESOLVE | BufferUsages::COPY_SRC,
+                    mapped_at_creation: false,
+                }),
+                readback: device.create_buffer(&BufferDescriptor {
+                    label: Some("Pass timestamps readback"),
+                    size,
+                    usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+                names: Mutex::new(Vec::new()),
+                state: AtomicU8::new(FREE),
+                frame: AtomicU64::new(0),
+            };
+
+            Some(Timer {
+                slots: [slot(), slot(), slot()],
+                next: AtomicUsize::new(0),
+                frames: AtomicU64::new(0),
+            })
+        })
+        .as_ref()
+}
+
+/// Timestamps being recorded for a single frame
+pub(crate) struct FrameTimer {
+    slot: &'static Slot,
+}
+
+impl FrameTimer {
+    /// Start timing a frame
+    ///
+    /// Returns `None` if timestamps aren't supported or every query set is still being read
+    pub(crate) fn begin() -> Option<Self> {
+        let timer = timer()?;
+        let i = timer.next.load(Ordering::Relaxed);
+        let slot = &timer.slots[i];
+        slot.state
+            .compare_exchange(FREE, RECORDING, Ordering::AcqRel, Ordering::Relaxed)
+            .ok()?;
+        timer.next.store((i + 1) % SLOTS, Ordering::Relaxed);
+        let frame = timer.frames.fetch_add(1, Ordering::Relaxed) + 1;
+        slot.frame.store(frame, Ordering::Relaxed);
+        slot.names.lock().unwrap().clear();
+        Some(Self { slot })
+    }
+
+    /// Write the timestamp before a pass
+    pub(crate) fn start(&mut self, encoder: &mut CommandEncoder, name: &'static str) {
+        let mut names = self.slot.names.lock().unwrap();
+        if names.len() < MAX_PASSES as usize {
+            encoder.write_timestamp(&self.slot.query_set, names.len() as u32 * 2);
+            names.push(name);
+        }
+    }
+
+    /// Write the timestamp after the last started pass
+    pub(crate) fn end(&mut self, encoder: &mut CommandEncoder, name: &'static str) {
+        let names = self.slot.names.lock().unwrap();
+        if names.last() == Some(&name) {
+            encoder.write_timestamp(&self.slot.query_set, names.len() as u32 * 2 - 1);
+        }
+    }
+
+    /// Copy the timestamps into a buffer that can be read once the frame is submitted
+    pub(crate) fn resolve(self, encoder: &mut CommandEncoder) -> SubmittedTimer {
+        let count = self.slot.names.lock().unwrap().len() as u32 * 2;
+        if count > 0 {
+            encoder.resolve_query_set(&self.slot.query_set, 0..count, &self.slot.resolve, 0);
+            encoder.copy_buffer_to_buffer(
+                &self.slot.resolve,
+                0,
+                &self.slot.readback,
+                0,
+                count as u64 * 8,
+            );
+        }
+        SubmittedTimer { slot: self.slot }
+    }
+}
+
+/// Timestamps that will be read after the frame is submitted
+pub(crate) struct SubmittedTimer {
+    slot: &'static Slot,
+}
+
+impl SubmittedTimer {
+    /// Start reading the timestamps back, must be called after the frame is submitted
+    pub(crate) fn read(self) {
+        let slot = self.slot;
+        if slot.names.lock().unwrap().is_empty() {
+            slot.state.store(FREE, Ordering::Release);
+            return;
+        }
+
+        slot.state.store(MAPPING, Ordering::Release);
+        slot.readback
+            .slice(..)
+            .map_async(MapMode::Read, move |res| {
+                let state = if res.is_ok() { READY } else { FREE };
+                slot.state.store(state, Ordering::Release);
+            });
+    }
+}
+
+/// Fetch the GPU timings of the most recently completed frame
+pub fn last_frame() -> Vec<GpuSpan> {
+    if let Some(timer) = timer() {
+        let _ = device().poll(Maintain::Poll);
+
+        // slots can finish mapping out of order, so only the newest frame is read and the rest
+        // are released
+        let ready = |slot: &&Slot| slot.state.load(Ordering::Acquire) == READY;
+        let newest = timer
+            .slots
+            .iter()
+            .filter(ready)
+            .max_by_key(|slot| slot.frame.load(Ordering::Relaxed));
+
+        if let Some(newest) = newest {
+            let frame = newest.frame.load(Ordering::Relaxed);
+            let mut last = LAST_FRAME.write().unwrap();
+            if frame > last.0 {
+                *last = (frame, read_spans(newest));
+            }
+        }
+
+        for slot in timer.slots.iter().filter(ready) {
+            slot.readback.unmap();
+            slot.state.store(FREE, Ordering::Release);
+        }
+    }
+    LAST_FRAME.read().unwrap().1.clone()
+}
+
+/// Convert the mapped timestamps of a slot into spans
+fn read_spans(slot: &Slot) -> Vec<GpuSpan> {
+    // nanoseconds per tick
+    let period = queue().get_timestamp_period() as f64;
+    let names = slot.names.lock().unwrap();
+    let data = slot.readback.slice(..).get_mapped_range();
+    let ticks: &[u64] = bytemuck::cast_slice(&data);
+    let first = ticks[0];
+    names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| GpuSpan {
+            name: *name,
+            start: ticks[i * 2].wrapping_sub(first) as f64 * period / 1_000_000.0,
+            duration: ticks[i * 2 + 1].wrapping_sub(ticks[i * 2]) as f64 * period / 1_000_000.0,
+        })
+        .collect()
+}
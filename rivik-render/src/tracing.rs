@@ -5,6 +5,9 @@
  */
 
 //! Draw tracing spans to an EGUI component
+//!
+//! When the adapter supports timestamp queries the GPU time of each render pass is drawn in a
+//! separate lane below the CPU spans.
 
 use std::{
     collections::{hash_map::DefaultHasher, BTreeMap},
@@ -26,6 +29,11 @@ use tracing::{event, field::Visit, span::Attributes, Id, Level, Subscriber};
 use tracing_core::Field;
 use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
 
+use crate::timestamps;
+
+/// Row of the plot that GPU passes are drawn in
+const GPU_LANE: f64 = -3.0;
+
 #[derive(Clone)]
 struct SpanBar {
     name: String,
@@ -58,8 +66,8 @@ static FRAME_START: Lazy<Arc<RwLock<Instant>>> =
     Lazy::new(|| Arc::new(RwLock::new(Instant::now())));
 
 /// Display a record of a frame's span trace to an EGUI plot
-#[tracing::instrument(skip(plot, spans, events))]
-pub fn display_traces(plot: &mut PlotUi, spans: Vec<Bar>, events: Vec<Bar>) {
+#[tracing::instrument(skip(plot, spans, events, gpu))]
+pub fn display_traces(plot: &mut PlotUi, spans: Vec<Bar>, events: Vec<Bar>, gpu: Vec<Bar>) {
     plot.bar_chart(
         BarChart::new(events.clone())
             .highlight(true)
@@ -84,6 +92,14 @@ pub fn display_traces(plot: &mut PlotUi, spans: Vec<Bar>, events: Vec<Bar>) {
             .name("Spans"),
     );
 
+    plot.bar_chart(
+        BarChart::new(gpu)
+            .highlight(true)
+            .horizontal()
+            .width(1.0)
+            .name("GPU"),
+    );
+
     plot.bar_chart(
         BarChart::new(events)
             .highlight(true)
@@ -92,6 +108,42 @@ pub fn display_traces(plot: &mut PlotUi, spans: Vec<Bar>, events: Vec<Bar>) {
     );
 }
 
+/// Create a color for a span from its name
+fn span_color(name: &str) -> Color32 {
+    let mut hasher = DefaultHasher::new();
+    hasher.write(name.as_bytes());
+    let color = hasher.finish();
+
+    let hue = (color & 0xFFFF) as f32 / (0xFFFF as f32);
+    let sat = (color >> 8 & 0xFFFF) as f32 / (0xFFFF as f32);
+    let light = (color >> 16 & 0xFFFF) as f32 / (0xFFFF as f32);
+    let light = light.max(0.4);
+    let sat = sat.max(0.7);
+
+    let color = palette::Hsl::new(hue * 360.0, sat, light);
+    let color = Srgb::from_color(color);
+    let r = (color.red * 256.0) as u8;
+    let g = (color.green * 256.0) as u8;
+    let b = (color.blue * 256.0) as u8;
+    Color32::from_rgb(r, g, b)
+}
+
+/// Bars for the GPU time of each pass in the most recently completed frame
+///
+/// GPU timings are read back a few frames late, so they are offset from the start of the first
+/// pass rather than lined up with the CPU spans.
+fn generate_gpu() -> Vec<Bar> {
+    timestamps::last_frame()
+        .into_iter()
+        .map(|pass| {
+            Bar::new(GPU_LANE, pass.duration)
+                .base_offset(pass.start)
+                .name(&format!("GPU {}", pass.name))
+                .fill(span_color(pass.name))
+        })
+        .collect()
+}
+
 #[tracing::instrument]
 fn generate_events() -> Vec<Bar> {
     let mut chart = Vec::new();
@@ -122,7 +174,9 @@ fn generate_events() -> Vec<Bar> {
 }
 
 /// Record a frame's span trace
-pub fn generate_chart() -> (Vec<Bar>, Vec<Bar>) {
+///
+/// Returns the CPU spans, events and GPU passes
+pub fn generate_chart() -> (Vec<Bar>, Vec<Bar>, Vec<Bar>) {
     let mut chart = Vec::new();
 
     for span in &*FRAME_SPANS.read().unwrap() {
@@ -131,23 +185,6 @@ pub fn generate_chart() -> (Vec<Bar>, Vec<Bar>) {
             None => panic!("This shouldn't happen"),
         };
 
-        // create a color for this span
-        let mut hasher = DefaultHasher::new();
-        hasher.write(span.name.as_bytes());
-        let color = hasher.finish();
-
-        let hue = (color & 0xFFFF) as f32 / (0xFFFF as f32);
-        let sat = (color >> 8 & 0xFFFF) as f32 / (0xFFFF as f32);
-        let light = (color >> 16 & 0xFFFF) as f32 / (0xFFFF as f32);
-        let light = light.max(0.4);
-        let sat = sat.max(0.7);
-
-        let color = palette::Hsl::new(hue * 360.0, sat, light);
-        let color = Srgb::from_color(color);
-        let r = (color.red * 256.0) as u8;
-        let g = (color.green * 256.0) as u8;
-        let b = (color.blue * 256.0) as u8;
-
         // generate a bar for this span
         chart.push(
             Bar::new(
@@ -156,17 +193,18 @@ pub fn generate_chart() -> (Vec<Bar>, Vec<Bar>) {
             )
             .base_offset((span.start - *FRAME_START.read().unwrap()).as_secs_f64() * 1_000.0)
             .name(&format!("{} {}", span.name, span.fields.join("\n")))
-            .fill(Color32::from_rgb(r, g, b)),
+            .fill(span_color(&span.name)),
         );
     }
 
     let events = generate_events();
+    let gpu = generate_gpu();
     // reset frame
     {
         FRAME_SPANS.write().unwrap().clear();
         *FRAME_START.write().unwrap() = Instant::now();
     }
-    (chart, events)
+    (chart, events, gpu)
 }
 //}
 
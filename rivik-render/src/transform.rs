@@ -8,28 +8,35 @@
 //!
 //! See the [Transform] type
 
-use std::{borrow::Borrow, num::NonZeroU64};
+use std::{borrow::Borrow, cell::Cell, num::NonZeroU64};
 
 use mint::ColumnMatrix4;
 use once_cell::sync::OnceCell;
 use ultraviolet::Mat4;
 use wgpu::{
     util::{BufferInitDescriptor, DeviceExt},
-    BindGroupLayout, BindGroupLayoutDescriptor, Buffer, BufferUsages,
+    BindGroup, BindGroupLayout, BindGroupLayoutDescriptor, Buffer, BufferUsages,
 };
 
-use crate::context::{device, queue};
+use crate::{
+    camera::{self, CAMERA_SIZE},
+    context::{device, queue},
+    stats,
+};
 
 /// A Handle around a transformation uniform buffer
 ///
-/// TODO: This should probably also cache the current value to allow for easier manipulation methods
+/// The buffer holds the model matrix, the matrix used to transform normals and the model matrix
+/// of the previous frame. It is bound together with the [camera](crate::camera) uniform.
 pub struct Transform {
     buffer: Buffer,
+    bind_group: BindGroup,
+    model: Cell<Mat4>,
 }
 
 impl Default for Transform {
     fn default() -> Self {
-        Self::new(Mat4::default(), Mat4::default(), Mat4::default())
+        Self::new(Mat4::identity())
     }
 }
 
@@ -42,6 +49,8 @@ pub trait Spatial {
 static TRANFORM_LAYOUT: OnceCell<BindGroupLayout> = OnceCell::new();
 
 /// Layout of a transform buffer
+///
+/// Binding 0 is the transform and binding 1 is the camera
 pub fn layout() -> &'static BindGroupLayout {
     if let Some(layout) = TRANFORM_LAYOUT.get() {
         layout
@@ -49,16 +58,28 @@ pub fn layout() -> &'static BindGroupLayout {
         // generate bind group layout
         let layout = device().create_bind_group_layout(&BindGroupLayoutDescriptor {
             label: None,
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::VERTEX,
-                ty: wgpu::BindingType::Buffer {
-                    ty: wgpu::BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: NonZeroU64::new(64 * 3),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: NonZeroU64::new(64 * 3),
+                    },
+                    count: None,
                 },
-                count: None,
-            }],
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: NonZeroU64::new(CAMERA_SIZE),
+                    },
+                    count: None,
+                },
+            ],
         });
         TRANFORM_LAYOUT.try_insert(layout).unwrap()
     }
@@ -66,13 +87,14 @@ pub fn layout() -> &'static BindGroupLayout {
 
 impl Transform {
     /// Create a new transform buffer
-    pub fn new(proj: Mat4, view: Mat4, model: Mat4) -> Self {
+    pub fn new(model: impl Into<ColumnMatrix4<f32>>) -> Self {
         let device = device();
+        let model = Mat4::from(model.into());
 
         let mut buffer = Vec::new();
-        buffer.extend_from_slice((proj * view * model).as_byte_slice());
-        buffer.extend_from_slice((view * model).as_byte_slice());
-        buffer.extend_from_slice((view * model).inversed().transposed().as_byte_slice());
+        buffer.extend_from_slice(model.as_byte_slice());
+        buffer.extend_from_slice(model.inversed().transposed().as_byte_slice());
+        buffer.extend_from_slice(model.as_byte_slice());
 
         // create buffer
         let buffer = device.create_buffer_init(&BufferInitDescriptor {
@@ -80,47 +102,59 @@ impl Transform {
             contents: &buffer,
             usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
         });
-        Self { buffer }
-    }
 
-    /// Updates the contents of the transform buffer
-    pub fn update(
-        &self,
-        proj: impl Into<ColumnMatrix4<f32>>,
-        view: impl Into<ColumnMatrix4<f32>>,
-        model: impl Into<ColumnMatrix4<f32>>,
-    ) {
-        let proj = Mat4::from(proj.into());
-        let view = Mat4::from(view.into());
-        let model = Mat4::from(model.into());
-        self.set_mvp(proj * view * model);
-        self.set_mv(view * model);
-        self.set_transform(view, model);
-    }
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: layout(),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: camera::buffer().as_entire_binding(),
+                },
+            ],
+            label: None,
+        });
 
-    /// Set the model-view-projection matrix
-    fn set_mvp(&self, proj: Mat4) {
-        queue().write_buffer(&self.buffer, 0, proj.as_byte_slice());
+        Self {
+            buffer,
+            bind_group,
+            model: Cell::new(model),
+        }
     }
 
-    /// Set the model-view matrix for this transform
-    fn set_mv(&self, view: Mat4) {
-        queue().write_buffer(&self.buffer, 64, view.as_byte_slice());
+    /// Updates the model matrix of this transform
+    ///
+    /// This should be called once per frame, the previous model matrix is kept to compute
+    /// motion vectors.
+    pub fn update(&self, model: impl Into<ColumnMatrix4<f32>>) {
+        let model = Mat4::from(model.into());
+        let prev = self.model.replace(model);
+
+        let mut buffer = Vec::with_capacity(64 * 3);
+        buffer.extend_from_slice(model.as_byte_slice());
+        buffer.extend_from_slice(model.inversed().transposed().as_byte_slice());
+        buffer.extend_from_slice(prev.as_byte_slice());
+        queue().write_buffer(&self.buffer, 0, &buffer);
+        stats::record_buffer_write();
     }
 
-    /// Set the model and normalized model matrix for this transform
-    fn set_transform(&self, view: Mat4, model: Mat4) {
-        queue().write_buffer(
-            &self.buffer,
-            128,
-            (view * model).inversed().transposed().as_byte_slice(),
-        );
+    /// Get the current model matrix
+    pub fn model(&self) -> Mat4 {
+        self.model.get()
     }
 
     /// Get the underlying buffer
     pub fn buffer(&self) -> &Buffer {
         &self.buffer
     }
+
+    /// Get the bind group of this transform and the camera
+    pub fn bind_group(&self) -> &BindGroup {
+        &self.bind_group
+    }
 }
 
 impl Borrow<Buffer> for Transform {
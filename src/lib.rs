@@ -17,7 +17,6 @@ use std::{
     f32::consts::FRAC_PI_2,
     marker::PhantomData,
     sync::{Arc, RwLock},
-    thread,
     time::{Duration, Instant},
 };
 
@@ -25,8 +24,13 @@ pub use egui_winit::egui;
 use egui_winit::egui::{plot::Plot, TopBottomPanel};
 use glam::{Mat4, Vec3};
 use pollster::block_on;
+pub use render::camera::Viewport;
 use render::{
+    camera,
     context::{resize, surface_config},
+    debug::display_debug_modes,
+    particles::ParticleEmitter,
+    stats::{self, display_stats},
     tracing::{display_traces, generate_chart},
     transform::Spatial,
     Drawable, Frame,
@@ -46,6 +50,7 @@ use winit::{
 };
 
 trait Renderable: Drawable + Spatial + Any {
+    fn as_drawable(&self) -> &dyn Drawable;
     fn as_any(&self) -> &dyn Any;
     fn as_any_mut(&mut self) -> &mut dyn Any;
 }
@@ -53,6 +58,10 @@ impl<T> Renderable for T
 where
     T: Drawable + Spatial + Any,
 {
+    fn as_drawable(&self) -> &dyn Drawable {
+        self
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -65,6 +74,7 @@ where
 enum RenderPassType {
     Geom,
     Light,
+    Particles,
     #[allow(dead_code)]
     Filter,
 }
@@ -79,6 +89,9 @@ impl<T: Drawable + 'static> Handle<T> {
     pub fn get<'a>(&self, ctx: &'a Context) -> &'a T {
         match self.pass {
             RenderPassType::Geom => ctx.geom[self.inner].0.as_any().downcast_ref().unwrap(),
+            RenderPassType::Particles => (ctx.particles[self.inner].0.as_ref() as &dyn Any)
+                .downcast_ref()
+                .unwrap(),
             _ => todo!(),
         }
     }
@@ -86,6 +99,9 @@ impl<T: Drawable + 'static> Handle<T> {
     pub fn get_mut<'a>(&self, ctx: &'a mut Context) -> &'a mut T {
         match self.pass {
             RenderPassType::Geom => ctx.geom[self.inner].0.as_any_mut().downcast_mut().unwrap(),
+            RenderPassType::Particles => (ctx.particles[self.inner].0.as_mut() as &mut dyn Any)
+                .downcast_mut()
+                .unwrap(),
             _ => todo!(),
         }
     }
@@ -93,24 +109,57 @@ impl<T: Drawable + 'static> Handle<T> {
     pub fn transform<'a>(&self, ctx: &'a Context) -> &'a Arc<RwLock<Node<Mat4>>> {
         match self.pass {
             RenderPassType::Geom => &ctx.geom[self.inner].1,
+            RenderPassType::Particles => &ctx.particles[self.inner].1,
             _ => todo!(),
         }
     }
 }
 
+/// A camera that draws the scene into part of the window
+#[derive(Debug, Clone, Copy)]
+pub struct Camera {
+    /// The view matrix of this camera
+    pub view: Mat4,
+    /// The part of the window this camera is drawn into
+    pub viewport: Viewport,
+}
+
+impl Camera {
+    pub fn new(view: Mat4, viewport: Viewport) -> Self {
+        Self { view, viewport }
+    }
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self::new(Mat4::IDENTITY, Viewport::FULL)
+    }
+}
+
 #[derive(Default)]
 pub struct Context {
     root: Node<Mat4>,
     geom: Vec<(Box<dyn Renderable>, Arc<RwLock<Node<Mat4>>>)>,
     lights: Vec<(Box<dyn Renderable>, Arc<RwLock<Node<Mat4>>>)>,
-    pub camera: Mat4,
+    particles: Vec<(Box<ParticleEmitter>, Arc<RwLock<Node<Mat4>>>)>,
+    /// Cameras to draw the scene from, use several for split-screen
+    pub cameras: Vec<Camera>,
     pub fov: f32,
     pub near: f32,
     pub far: f32,
     pub show_trace: bool,
 
     pub update_step: f32,
+    /// Maximum frames per second, 0 will draw frames as fast as possible
+    ///
+    /// When frames take longer than this allows they are paced to [`frame_time`](Self::frame_time)
+    ///
+    /// Use `render::context::set_present_mode` to control vsync
     pub framerate: u8,
+    /// Smoothed time the last few frames took to prepare, draw and present in seconds
+    ///
+    /// Time spent waiting for the next frame isn't counted
+    pub frame_time: f32,
 }
 
 impl Context {
@@ -167,6 +216,32 @@ impl Context {
         }
     }
 
+    /// Add a particle emitter that follows a new node of the scenegraph
+    pub fn insert_emitter(&mut self, emitter: ParticleEmitter) -> Handle<ParticleEmitter> {
+        let node = self.root.insert(Mat4::default());
+        self.particles.push((Box::new(emitter), node));
+        Handle {
+            inner: self.particles.len() - 1,
+            pass: RenderPassType::Particles,
+            ty: PhantomData::default(),
+        }
+    }
+
+    /// Add a particle emitter that follows a child of `parent`
+    pub fn insert_child_emitter(
+        &mut self,
+        parent: &mut Node<Mat4>,
+        emitter: ParticleEmitter,
+    ) -> Handle<ParticleEmitter> {
+        let node = parent.insert(Mat4::default());
+        self.particles.push((Box::new(emitter), node));
+        Handle {
+            inner: self.particles.len() - 1,
+            pass: RenderPassType::Particles,
+            ty: PhantomData::default(),
+        }
+    }
+
     pub fn insert_light<T>(&mut self, bundle: T) -> Handle<T>
     where
         T: Drawable + Spatial + Any + 'static,
@@ -207,7 +282,10 @@ pub fn run<A: App + 'static>() -> ! {
 
     let mut scene = Context::default();
 
-    scene.camera = Mat4::look_at_rh(Vec3::new(2.0, 2.0, 0.0), Vec3::default(), Vec3::Y);
+    scene.cameras = vec![Camera::new(
+        Mat4::look_at_rh(Vec3::new(2.0, 2.0, 0.0), Vec3::default(), Vec3::Y),
+        Viewport::FULL,
+    )];
     scene.fov = FRAC_PI_2;
     scene.near = 0.1;
     scene.far = 1_000.0;
@@ -218,22 +296,14 @@ pub fn run<A: App + 'static>() -> ! {
     let mut captured_trace = None;
     let mut last_frame_time = Instant::now();
 
-    let mut proj = Mat4::perspective_rh(scene.fov, 16.0 / 9.0, scene.near, scene.far);
-
-    // issue frame requests from another thread
-    {
-        let window = window.clone();
-        thread::spawn(move || loop {
-            thread::sleep(Duration::from_millis(1000 / scene.framerate as u64));
-            window.request_redraw();
-        });
-    }
+    let mut window_size = (16, 9);
+    // render cameras for every camera after the first, which uses the main camera
+    let mut view_cameras: Vec<camera::Camera> = vec![];
 
     let mut dt = 0.0;
+    let mut next_frame = Instant::now();
 
     event_loop.run(move |event, _, control_flow| {
-        *control_flow = ControlFlow::Wait;
-
         match event {
             Event::WindowEvent { event, .. } => {
                 let resp = egui_state.on_event(&egui_ctx, &event);
@@ -242,13 +312,10 @@ pub fn run<A: App + 'static>() -> ! {
                     match event {
                         WindowEvent::Resized(size) => {
                             resize(size.width, size.height);
-                            // re-compute projection matrix
-                            let aspect = {
+                            window_size = {
                                 let config = surface_config().read().unwrap();
-                                config.width as f32 / config.height as f32
+                                (config.width, config.height)
                             };
-
-                            proj = Mat4::perspective_rh(scene.fov, aspect, scene.near, scene.far);
                         }
                         WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
                         event => app.on_event(&event),
@@ -256,35 +323,55 @@ pub fn run<A: App + 'static>() -> ! {
                 }
             }
             Event::RedrawRequested(..) => {
+                let frame_start = Instant::now();
+                while view_cameras.len() + 1 < scene.cameras.len() {
+                    view_cameras.push(camera::Camera::new());
+                }
                 let mut frame = Frame::new().unwrap();
 
                 // generate dt
-                dt += last_frame_time.elapsed().as_secs_f32();
+                let elapsed = last_frame_time.elapsed().as_secs_f32();
+                dt += elapsed;
                 last_frame_time = Instant::now();
 
-                let trace_chart = generate_chart();
+                let trace_chart = (generate_chart(), stats::last_frame());
 
                 {
                     let span = debug_span!("Preparing Scenegraph");
                     let _span = span.enter();
+                    let render_cameras = Some(camera::main()).into_iter().chain(&view_cameras);
+                    for (cam, render_camera) in scene.cameras.iter().zip(render_cameras) {
+                        let aspect = cam.viewport.aspect(window_size);
+                        let proj = Mat4::perspective_rh(scene.fov, aspect, scene.near, scene.far);
+                        render_camera.set(proj, cam.view);
+                        frame.add_view(render_camera, cam.viewport);
+                    }
                     for (drawable, transform) in &scene.geom {
                         // update transform buffer
-                        drawable.transform().update(
-                            proj,
-                            scene.camera,
-                            transform.read().unwrap().global(),
-                        );
-                        frame.draw_geom(drawable.bundle());
+                        drawable
+                            .transform()
+                            .update(transform.read().unwrap().global());
+                    }
+                    // prepared after the transforms are updated, as LOD meshes are sized by theirs
+                    for (drawable, _) in &mut scene.geom {
+                        drawable.prepare();
+                    }
+                    for (drawable, _) in &scene.geom {
+                        frame.draw_geom(drawable.as_drawable());
                     }
 
                     for (light, transform) in &scene.lights {
                         // update transform buffer
-                        light.transform().update(
-                            proj,
-                            scene.camera,
-                            transform.read().unwrap().global(),
-                        );
-                        frame.draw_light(light.bundle());
+                        light.transform().update(transform.read().unwrap().global());
+                        frame.draw_light(light.as_drawable());
+                    }
+
+                    for (emitter, transform) in &scene.particles {
+                        emitter
+                            .transform()
+                            .update(transform.read().unwrap().global());
+                        emitter.update(elapsed);
+                        frame.draw_particles(emitter);
                     }
                 }
 
@@ -310,15 +397,22 @@ pub fn run<A: App + 'static>() -> ! {
                                 captured_trace = None;
                             }
 
-                            let spans = captured_trace.clone().unwrap_or(trace_chart);
-                            Plot::new("Frame Timing")
-                                .data_aspect(0.1)
-                                .view_aspect(10.0)
-                                .show_axes([false, false])
-                                .show_background(false)
-                                .show(ui, |plot| {
-                                    display_traces(plot, spans.0, spans.1);
+                            let (spans, render_stats) =
+                                captured_trace.clone().unwrap_or(trace_chart);
+                            ui.horizontal_top(|ui| {
+                                ui.vertical(|ui| {
+                                    display_debug_modes(ui);
+                                    display_stats(ui, &render_stats);
                                 });
+                                Plot::new("Frame Timing")
+                                    .data_aspect(0.1)
+                                    .view_aspect(10.0)
+                                    .show_axes([false, false])
+                                    .show_background(false)
+                                    .show(ui, |plot| {
+                                        display_traces(plot, spans.0, spans.1, spans.2);
+                                    });
+                            });
                         });
                     }
                     let span = debug_span!("Drawing user UI");
@@ -331,6 +425,8 @@ pub fn run<A: App + 'static>() -> ! {
                 drop(ui_span);
 
                 frame.present();
+                let work = frame_start.elapsed().as_secs_f32();
+                scene.frame_time = scene.frame_time * 0.9 + work * 0.1;
 
                 while dt >= scene.update_step {
                     let span = debug_span!("Updating scene", dt);
@@ -340,6 +436,34 @@ pub fn run<A: App + 'static>() -> ! {
                     dt -= scene.update_step;
                 }
             }
+            Event::MainEventsCleared => {
+                if *control_flow == ControlFlow::Exit {
+                    return;
+                }
+
+                if scene.framerate == 0 {
+                    window.request_redraw();
+                    *control_flow = ControlFlow::Poll;
+                    return;
+                }
+
+                // schedule frames against deadlines so that the time spent handling events and
+                // drawing doesn't add to the frame period. When drawing takes longer than the cap
+                // the deadlines are spaced by the measured frame time instead, so slow frames are
+                // paced evenly rather than requested in bursts to catch up. The frame time leaves
+                // out the wait, so pacing returns to the cap once frames are fast again
+                let now = Instant::now();
+                let cap = Duration::from_secs_f32(1.0 / scene.framerate as f32);
+                let period = cap.max(Duration::from_secs_f32(scene.frame_time));
+                if now >= next_frame {
+                    window.request_redraw();
+                    next_frame += period;
+                    if next_frame < now {
+                        next_frame = now + period;
+                    }
+                }
+                *control_flow = ControlFlow::WaitUntil(next_frame);
+            }
             _ => {}
         }
     })